@@ -0,0 +1,117 @@
+import argparse
+import math
+import re
+import subprocess
+
+# measures the ratings of the Skill Level settings and prints a LEVEL_ELO
+# table for src/skill.rs.
+#
+# every STEP-th level plays the one STEP above it, starting from full strength,
+# so each gap is measured while the score still says something (a level 0
+# against full strength would just lose every game).  the ratings are chained
+# down from the anchor and the levels in between are interpolated.  each gap's
+# error adds to the ones above it, so the accumulated error is printed as well.
+#
+# run it on a release build with the net it ships with and the time control
+# the ratings are meant for.  the weak levels are node limited, so they play
+# the same at any time control, but full strength and the levels just below it
+# are not.
+
+MAX_SKILL_LEVEL = 20
+FULL_STRENGTH_ELO = 2450
+
+SCORE_RE = re.compile(r"Score of (\S+) vs (\S+): (\d+) - (\d+) - (\d+)")
+
+
+def run_pair(args, upper, lower):
+    cmd = ["cutechess-cli",
+           "-engine", f"cmd={args.engine}", f"name=L{upper}", f"option.Skill Level={upper}",
+           "-engine", f"cmd={args.engine}", f"name=L{lower}", f"option.Skill Level={lower}",
+           "-each", "proto=uci", f"tc={args.tc}", "option.Threads=1", f"option.Hash={args.hash}",
+           "-games", "2", "-rounds", str(args.games // 2), "-repeat",
+           "-concurrency", str(args.concurrency), "-recover",
+           "-resign", "movecount=4", "score=800",
+           "-draw", "movenumber=40", "movecount=8", "score=10",
+           "-event", "MANTISSA_CALIBRATION"]
+    if args.book:
+        fmt = "pgn" if args.book.endswith(".pgn") else "epd"
+        cmd += ["-openings", f"file={args.book}", f"format={fmt}", "order=random", "plies=8"]
+    if args.pgnout:
+        cmd += ["-pgnout", args.pgnout]
+
+    result = subprocess.run(cmd, capture_output=True, text=True)
+    # cutechess prints a running score, the last one is the final result
+    scores = SCORE_RE.findall(result.stdout)
+    if not scores:
+        raise RuntimeError(f"no result from cutechess-cli for L{upper} vs L{lower}:\n{result.stdout}{result.stderr}")
+    _, _, wins, losses, draws = scores[-1]
+    return int(wins), int(losses), int(draws)
+
+
+# elo difference and its 95% error from the upper level's point of view
+def elo_diff(wins, losses, draws):
+    n = wins + losses + draws
+    score = (wins + 0.5 * draws) / n
+    # a clean sweep has no finite difference, so it counts as half a game less
+    score = min(max(score, 0.5 / n), 1 - 0.5 / n)
+    variance = (wins * (1 - score) ** 2 + draws * (0.5 - score) ** 2 + losses * score ** 2) / n
+    stderr = math.sqrt(variance / n)
+    slope = 400 / (math.log(10) * score * (1 - score))
+    return -400 * math.log10(1 / score - 1), 1.96 * stderr * slope
+
+
+def interpolate(measured):
+    levels = sorted(measured)
+    table = []
+    for level in range(MAX_SKILL_LEVEL + 1):
+        lo = max(l for l in levels if l <= level)
+        hi = min(l for l in levels if l >= level)
+        if lo == hi:
+            table.append(measured[lo])
+        else:
+            t = (level - lo) / (hi - lo)
+            table.append(measured[lo] + t * (measured[hi] - measured[lo]))
+    return [round(elo) for elo in table]
+
+
+def print_table(table):
+    print("pub const LEVEL_ELO: [i32; 21] = [")
+    rows = [table[:10], table[10:20]]
+    for row in rows:
+        print("    " + ", ".join(str(elo) for elo in row) + ",")
+    print("    FULL_STRENGTH_ELO")
+    print("];")
+
+
+def calibrate(args):
+    levels = list(range(MAX_SKILL_LEVEL, -1, -args.step))
+    if levels[-1] != 0:
+        levels.append(0)
+
+    measured = {MAX_SKILL_LEVEL: args.anchor}
+    total_error = 0
+    for upper, lower in zip(levels, levels[1:]):
+        wins, losses, draws = run_pair(args, upper, lower)
+        diff, error = elo_diff(wins, losses, draws)
+        # independent matches, so the errors add in quadrature
+        total_error = math.sqrt(total_error ** 2 + error ** 2)
+        measured[lower] = measured[upper] - diff
+        print(f"L{upper} vs L{lower}: +{wins} -{losses} ={draws}  "
+              f"{diff:+.0f} +- {error:.0f}  L{lower} = {measured[lower]:.0f} +- {total_error:.0f}", flush=True)
+
+    print()
+    print_table(interpolate(measured))
+
+
+if __name__ == "__main__":
+    parser = argparse.ArgumentParser(description="measure the Elo of each Skill Level")
+    parser.add_argument("engine", help="path to a release build of mantissa")
+    parser.add_argument("--games", type=int, default=1000, help="games for each pair of levels")
+    parser.add_argument("--step", type=int, default=4, help="levels between measured pairs")
+    parser.add_argument("--tc", default="10+0.1", help="cutechess-cli time control")
+    parser.add_argument("--hash", type=int, default=64)
+    parser.add_argument("--concurrency", type=int, default=8)
+    parser.add_argument("--anchor", type=int, default=FULL_STRENGTH_ELO, help="rating of full strength")
+    parser.add_argument("--book", help="EPD or PGN openings, strongly recommended")
+    parser.add_argument("--pgnout", help="save the games here")
+    calibrate(parser.parse_args())
@@ -0,0 +1,107 @@
+use std::fmt;
+
+use crate::bitboard::*;
+use crate::syzygy::*;
+use crate::uci::*;
+use crate::util::*;
+
+// whether the side to move should resign, offer a draw, or take one that's
+// offered.  tablebases and dead positions decide outright, otherwise it goes
+// by the scores of our last few searches in this game
+
+// the score of each finished search this game, in centipawns for the side
+// that searched, along with the ply it was played at
+static mut SCORE_HISTORY: Vec<(Color, usize, i32)> = Vec::new();
+
+pub struct Advice {
+    pub resign: bool,
+    pub offer_draw: bool,
+    pub accept_draw: bool,
+    pub reason: &'static str
+}
+
+impl Advice {
+    fn new(resign: bool, offer_draw: bool, accept_draw: bool, reason: &'static str) -> Advice {
+        Advice {
+            resign: resign,
+            offer_draw: offer_draw,
+            accept_draw: accept_draw,
+            reason: reason
+        }
+    }
+}
+
+impl fmt::Display for Advice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let yes_no = |b: bool| if b {"yes"} else {"no"};
+        write!(f, "advice resign {} offerdraw {} acceptdraw {} reason {}",
+               yes_no(self.resign), yes_no(self.offer_draw), yes_no(self.accept_draw), self.reason)
+    }
+}
+
+pub fn clear_score_history() {
+    unsafe {
+        SCORE_HISTORY = Vec::new();
+    }
+}
+
+// scores from later in the game than this position are from lines that were
+// taken back, so they're dropped
+pub fn record_score(pos: &Bitboard, score: i32) {
+    let ply = pos.history.len();
+    let history = unsafe { &mut *std::ptr::addr_of_mut!(SCORE_HISTORY) };
+    history.retain(|&(_, p, _)| p < ply);
+    history.push((pos.side_to_move, ply, score / 10));
+}
+
+// the last n scores for the side to move, if there are that many
+fn recent_scores(pos: &Bitboard, n: usize) -> Option<Vec<i32>> {
+    let ply = pos.history.len();
+    let history = unsafe { &*std::ptr::addr_of!(SCORE_HISTORY) };
+    let mut scores = Vec::new();
+    for &(side, p, score) in history.iter().rev() {
+        if side == pos.side_to_move && p <= ply {
+            scores.push(score);
+        }
+        if scores.len() == n {
+            return Some(scores);
+        }
+    }
+    return None;
+}
+
+pub fn advise(pos: &Bitboard, options: &UCIOptions) -> Advice {
+    if pos.insufficient_material() {
+        return Advice::new(false, true, true, "material");
+    }
+
+    if tb_active() && pos.num_pieces() <= max_tb_pieces() && pos.castling_rights == 0 {
+        if let Some(score) = probe_wdl(pos) {
+            return if score > 0 {
+                Advice::new(false, false, false, "tablebase")
+            } else if score == 0 {
+                Advice::new(false, true, true, "tablebase")
+            } else {
+                Advice::new(options.resign_score > 0, true, true, "tablebase")
+            };
+        }
+    }
+
+    let mut advice = Advice::new(false, false, false, "score");
+    if options.resign_score > 0 {
+        if let Some(scores) = recent_scores(pos, options.resign_moves as usize) {
+            advice.resign = scores.iter().all(|&s| s <= -options.resign_score);
+        }
+    }
+    // offer only when it's been level for a while, but take one whenever
+    // we aren't better
+    if let Some(scores) = recent_scores(pos, options.draw_moves as usize) {
+        let level = scores.iter().all(|&s| s.abs() <= options.draw_margin);
+        let fullmove = 1 + pos.history.len() as i32 / 2;
+        advice.offer_draw = level && fullmove >= options.draw_min_move;
+    }
+    if let Some(scores) = recent_scores(pos, 1) {
+        advice.accept_draw = scores[0] <= options.draw_margin;
+    }
+    return advice;
+}
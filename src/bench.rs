@@ -0,0 +1,51 @@
+use crate::bitboard::*;
+use crate::search::*;
+use crate::searchutil::*;
+use crate::tt::*;
+use crate::uci::*;
+
+// a spread of openings, middlegames and endgames.  the node count over these
+// at a fixed depth works as a signature for functional changes.
+const BENCH_FENS: [&str; 16] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+    "rnbqkb1r/pp1p1ppp/4pn2/2p5/2PP4/2N5/PP2PPPP/R1BQKBNR w KQkq - 0 4",
+    "r1bq1rk1/pp2ppbp/2np1np1/8/3NP3/2N1BP2/PPPQ2PP/R3KB1R w KQ - 3 9",
+    "r2q1rk1/1b2bppp/p2ppn2/1p6/3NP3/1BN1B3/PPP1QPPP/R4RK1 w - - 0 12",
+    "2rq1rk1/pp1bppbp/3p1np1/4n3/3NP2P/1BN1BP2/PPPQ2P1/2KR3R b - - 0 13",
+    "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+    "3r1rk1/p4ppp/1pq1pn2/2p5/2P5/1P2PN2/P2Q1PPP/3R1RK1 w - - 0 18",
+    "6k1/5pp1/1p2p2p/p2pP3/P2P4/1P1Q1P2/5qPP/3R3K b - - 3 31",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "8/8/4k3/3p4/3P4/4K3/8/8 w - - 0 1",
+    "8/5pk1/6p1/R7/5P2/6P1/r4K2/8 w - - 1 48",
+    "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1",
+    "8/8/1p1r1k2/p1pPN1p1/P3KnP1/1P6/8/3R4 b - - 0 1",
+    "r1b1k2r/ppppnppp/2n2q2/2b5/3NP3/2P1B3/PP3PPP/RN1QKB1R w KQkq - 0 7",
+];
+
+pub fn bench(depth: i32) {
+    let options = UCIOptions::default();
+    set_silent(true);
+
+    let mut total_nodes: u64 = 0;
+    let mut total_time: u128 = 0;
+    for fen in BENCH_FENS.iter() {
+        clear_tt();
+        let mut board = Bitboard::from_position(fen.to_string());
+        let start = get_time_millis();
+        let result = best_move(&mut board, 1, SearchLimits::depth(depth), options.clone());
+        total_time += get_time_millis() - start;
+        let nodes = match result.iterations.last() {
+            Some(it) => it.nodes,
+            None => 0
+        };
+        total_nodes += nodes;
+        println!("{:<72} {:>10} nodes  bestmove {}", fen, nodes, result.best_move);
+    }
+    set_silent(false);
+
+    let total_time = total_time.max(1);
+    println!("{} nodes {} nps", total_nodes, (total_nodes as u128 * 1000) / total_time);
+}
@@ -32,7 +32,6 @@ pub struct Bitboard {
     cap_stack: Vec<u8>,
     castling_rights_stack: Vec<u8>,
     halfmove_stack: Vec<u8>,
-    activation_stack: Vec<[f32x8; 64]>,
 
     pub halfmove: u8,
 
@@ -78,7 +77,6 @@ impl Bitboard {
             cap_stack: Vec::new(),
             castling_rights_stack: Vec::new(),
             halfmove_stack: Vec::new(),
-            activation_stack: Vec::new(),
 
             halfmove: 0,
             hash: 0,
@@ -96,6 +94,54 @@ impl Bitboard {
         return bitboard;
     }
 
+    pub fn validate_fen(fen: &str) -> Result<(), String> {
+        // from_position panics on malformed input, so anything reading FENs
+        // out of files should run them through here first
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        if fields.len() < 5 {
+            return Err(format!("expected at least 5 fields, found {}", fields.len()));
+        }
+
+        let ranks: Vec<&str> = fields[0].split('/').collect();
+        if ranks.len() != 8 {
+            return Err(format!("expected 8 ranks, found {}", ranks.len()));
+        }
+        let mut kings = [0, 0];
+        for rank in ranks {
+            let mut squares = 0;
+            for c in rank.bytes() {
+                match c {
+                    b'1'..=b'8' => { squares += (c - b'0') as i32; },
+                    b'k' => { kings[0] += 1; squares += 1; },
+                    b'K' => { kings[1] += 1; squares += 1; },
+                    b'q' | b'r' | b'b' | b'n' | b'p' | b'Q' | b'R' | b'B' | b'N' | b'P' => { squares += 1; },
+                    _ => { return Err(format!("unexpected character '{}' in piece placement", c as char)); }
+                }
+            }
+            if squares != 8 {
+                return Err(format!("rank '{}' does not cover 8 squares", rank));
+            }
+        }
+        if kings != [1, 1] {
+            return Err("each side must have exactly one king".to_string());
+        }
+
+        if fields[1] != "w" && fields[1] != "b" {
+            return Err(format!("bad side to move '{}'", fields[1]));
+        }
+        if fields[2] != "-" && (fields[2].is_empty() || !fields[2].bytes().all(|c| b"KQkq".contains(&c))) {
+            return Err(format!("bad castling rights '{}'", fields[2]));
+        }
+        let ep = fields[3].as_bytes();
+        if fields[3] != "-" && (ep.len() != 2 || ep[0] < b'a' || ep[0] > b'h' || (ep[1] != b'3' && ep[1] != b'6')) {
+            return Err(format!("bad en passant square '{}'", fields[3]));
+        }
+        if fields[4].parse::<u8>().is_err() {
+            return Err(format!("bad halfmove clock '{}'", fields[4]));
+        }
+        return Ok(());
+    }
+
     pub fn from_position(fen: String) -> Bitboard {
         let mut black_king: u64 = 0;
         let mut white_king: u64 = 0;
@@ -118,7 +164,8 @@ impl Bitboard {
         let mut rank: i32 = 7;
         let mut file: i32 = 0;
 
-        let mut fen_split = fen.split(' ');
+        // tokenized the same way validate_fen does it
+        let mut fen_split = fen.split_whitespace();
         let positions = match fen_split.next() {
             Some(s) => String::from(s),
             None => panic!("bad FEN string")
@@ -205,17 +252,29 @@ impl Bitboard {
             None => panic!("Bad FEN string.  Missing halfmove clock")
         };
 
-        let white_composite = white_king | white_queen | white_rook | white_bishop | white_knight | white_pawn;
-        let black_composite = black_king | black_queen | black_rook | black_bishop | black_knight | black_pawn;
+        let pieces = [
+            [black_pawn, black_knight, black_bishop, black_rook, black_queen, black_king],
+            [white_pawn, white_knight, white_bishop, white_rook, white_queen, white_king]
+        ];
+        return Bitboard::from_pieces(pieces, side_to_move, castling_rights, ep_file, halfmove);
+    }
+
+    // builds a board straight from piece bitboards, indexed [color][piece]
+    // with pieces in PAWN..KING order.  castling rights are the KQkq bits
+    pub fn from_pieces(pieces: [[u64; 6]; 2], side_to_move: Color, castling_rights: u8, ep_file: i32, halfmove: u8) -> Bitboard {
+        let black = Color::Black as usize;
+        let white = Color::White as usize;
+        let black_composite = pieces[black].iter().fold(0, |acc, bb| acc | bb);
+        let white_composite = pieces[white].iter().fold(0, |acc, bb| acc | bb);
 
         let mut bitboard = Bitboard {
             side_to_move: side_to_move,
-            king: [black_king, white_king],
-            queen: [black_queen, white_queen],
-            rook: [black_rook, white_rook],
-            bishop: [black_bishop, white_bishop],
-            knight: [black_knight, white_knight],
-            pawn: [black_pawn, white_pawn],
+            king: [pieces[black][KING as usize], pieces[white][KING as usize]],
+            queen: [pieces[black][QUEEN as usize], pieces[white][QUEEN as usize]],
+            rook: [pieces[black][ROOK as usize], pieces[white][ROOK as usize]],
+            bishop: [pieces[black][BISHOP as usize], pieces[white][BISHOP as usize]],
+            knight: [pieces[black][KNIGHT as usize], pieces[white][KNIGHT as usize]],
+            pawn: [pieces[black][PAWN as usize], pieces[white][PAWN as usize]],
             composite: [black_composite, white_composite],
 
             castling_rights: castling_rights,
@@ -226,7 +285,6 @@ impl Bitboard {
             cap_stack: Vec::new(),
             castling_rights_stack: Vec::new(),
             halfmove_stack: Vec::new(),
-            activation_stack: Vec::new(),
 
             halfmove: halfmove,
             hash: 0,            // for the moment, we fill it in late
@@ -343,7 +401,6 @@ impl Bitboard {
             cap_stack: Vec::new(),
             castling_rights_stack: Vec::new(),
             halfmove_stack: Vec::new(),
-            activation_stack: Vec::new(),
 
             halfmove: self.halfmove,
             hash: self.hash,
@@ -496,7 +553,7 @@ impl Bitboard {
         self.ep_file = -1;
         self.hash ^= null_move_hash();
 
-        self.activation_stack.push(self.net.hidden_activations);
+        self.net.push();
         if self.side_to_move == Color::White {
             self.net.white_turn();
         } else {
@@ -514,10 +571,7 @@ impl Bitboard {
             Some(p) => p,
             None => panic!("empty ep stack!")
         };
-        self.net.hidden_activations = match self.activation_stack.pop() {
-            Some(p) => p,
-            None => panic!("empty activation stack!")
-        };
+        self.net.pop();
 
         if self.side_to_move == Color::White {
             self.net.white_turn();
@@ -687,33 +741,28 @@ impl Bitboard {
         self.history.push(self.hash);
         self.ep_stack.push(self.ep_file);
         self.castling_rights_stack.push(self.castling_rights);
-        self.activation_stack.push(self.net.hidden_activations);
-
-        let wkr = region(self.king[1].trailing_zeros() as i8) as usize * 768;
-        let bkr = region((self.king[0].trailing_zeros() ^ 56) as i8) as usize * 768;
-        // let wkr = region(self.king[1].trailing_zeros() as i8);
-        // let bkr = region((self.king[0].trailing_zeros() ^ 56) as i8);
+        self.net.push();
         // capture
         // check ep first
         if is_ep {
             self.hash ^= en_passant_hash(ep_idx as i32, !self.side_to_move);
-            self.net.deactivate(PAWN, !self.side_to_move, ep_idx, wkr, bkr);
+            self.net.deactivate(PAWN, !self.side_to_move, ep_idx);
         } else if captured_piece != 0 {
             match captured_piece {
                 b'p' => {
-                    self.net.deactivate(PAWN, !self.side_to_move, mv.end, wkr, bkr);
+                    self.net.deactivate(PAWN, !self.side_to_move, mv.end);
                 },
                 b'n' => {
-                    self.net.deactivate(KNIGHT, !self.side_to_move, mv.end, wkr, bkr);
+                    self.net.deactivate(KNIGHT, !self.side_to_move, mv.end);
                 },
                 b'b' => {
-                    self.net.deactivate(BISHOP, !self.side_to_move, mv.end, wkr, bkr);
+                    self.net.deactivate(BISHOP, !self.side_to_move, mv.end);
                 },
                 b'r' => {
-                    self.net.deactivate(ROOK, !self.side_to_move, mv.end, wkr, bkr);
+                    self.net.deactivate(ROOK, !self.side_to_move, mv.end);
                 },
                 b'q' => {
-                    self.net.deactivate(QUEEN, !self.side_to_move, mv.end, wkr, bkr);
+                    self.net.deactivate(QUEEN, !self.side_to_move, mv.end);
                 },
                 _ => panic!("Captured uncapturable piece {}!", captured_piece)
             };
@@ -725,16 +774,16 @@ impl Bitboard {
             let old_rook_idx: i8 = if mv.end > mv.start { mv.start + 3 } else { mv.start - 4 };
             let new_rook_idx: i8 = if mv.end > mv.start { mv.start + 1 } else { mv.start - 1 };
 
-            self.net.move_piece(ROOK, self.side_to_move, old_rook_idx, new_rook_idx, wkr, bkr);
+            self.net.move_piece(ROOK, self.side_to_move, old_rook_idx, new_rook_idx);
             self.hash ^= simple_move_hash(b'r', old_rook_idx as i32, new_rook_idx as i32, self.side_to_move);
         }
 
         // piece move
         if mv.piece == b'p' && mv.promote_to != 0 {
-            self.net.deactivate(PAWN, self.side_to_move, mv.start, wkr, bkr);
-            self.net.activate(promo_num, self.side_to_move, mv.end, wkr, bkr);
+            self.net.deactivate(PAWN, self.side_to_move, mv.start);
+            self.net.activate(promo_num, self.side_to_move, mv.end);
         } else {
-            self.net.move_piece(piece_num, self.side_to_move, mv.start, mv.end, wkr, bkr);
+            self.net.move_piece(piece_num, self.side_to_move, mv.start, mv.end);
         }
 
         let old_castling_rights = self.castling_rights;
@@ -779,70 +828,37 @@ impl Bitboard {
         self.side_to_move = !self.side_to_move;
 
         if mv.piece == b'k' {
-            if self.side_to_move != Color::White {
-                if region(mv.start) != region(mv.end) {
-                    self.set_kr_activations();
-                }
-            } else {
-                if region(mv.start ^ 56) != region(mv.end ^ 56) {
-                    self.set_kr_activations();
-                }
-            }
+            let (wkr, bkr) = self.king_regions();
+            self.net.set_regions(wkr, bkr);
         }
         return true;
     }
 
     pub fn set_activations(&mut self) {
-        for i in 0..64 {
-            self.net.hidden_activations[i] = self.net.hidden_biases[i];
-        }
-
-        let wkr = region(self.king[1].trailing_zeros() as i8) as usize * 768;
-        let bkr = region((self.king[0].trailing_zeros() ^ 56) as i8) as usize * 768;
-        for side in [Color::White, Color::Black] {
-            let is_white = side == Color::White;
-            let me = side as usize;
+        let (wkr, bkr) = self.king_regions();
+        let pieces = self.piece_boards();
+        self.net.reset(&pieces, wkr, bkr, self.side_to_move);
+    }
 
-            self.net.set_bb_activations(self.pawn[me], PAWN, is_white, wkr, bkr);
-            self.net.set_bb_activations(self.knight[me], KNIGHT, is_white, wkr, bkr);
-            self.net.set_bb_activations(self.bishop[me], BISHOP, is_white, wkr, bkr);
-            self.net.set_bb_activations(self.rook[me], ROOK, is_white, wkr, bkr);
-            self.net.set_bb_activations(self.queen[me], QUEEN, is_white, wkr, bkr);
-            self.net.set_bb_activations(self.king[me], KING, is_white, wkr, bkr);
-        }
-
-        if self.side_to_move == Color::White {
-            self.net.white_turn();
-        } else {
-            self.net.black_turn();
+    // piece bitboards indexed [color][piece], the layout from_pieces takes
+    pub fn piece_boards(&self) -> [[u64; 6]; 2] {
+        let mut pieces = [[0; 6]; 2];
+        for (side, boards) in pieces.iter_mut().enumerate() {
+            *boards = [self.pawn[side], self.knight[side], self.bishop[side], self.rook[side], self.queen[side], self.king[side]];
         }
+        return pieces;
     }
 
-    pub fn set_kr_activations(&mut self) {
-        // for i in 0..64 {
-        //     self.net.hidden_activations[i] = self.net.hidden_biases[i];
-        // }
-        self.net.clear_kr();
-
-        let wkr = region(self.king[1].trailing_zeros() as i8) as usize * 768;
-        let bkr = region((self.king[0].trailing_zeros() ^ 56) as i8) as usize * 768;
-        for side in [Color::White, Color::Black] {
-            let is_white = side == Color::White;
-            let me = side as usize;
-
-            self.net.set_kr_bb_activations(self.pawn[me], PAWN, is_white, wkr, bkr);
-            self.net.set_kr_bb_activations(self.knight[me], KNIGHT, is_white, wkr, bkr);
-            self.net.set_kr_bb_activations(self.bishop[me], BISHOP, is_white, wkr, bkr);
-            self.net.set_kr_bb_activations(self.rook[me], ROOK, is_white, wkr, bkr);
-            self.net.set_kr_bb_activations(self.queen[me], QUEEN, is_white, wkr, bkr);
-            self.net.set_kr_bb_activations(self.king[me], KING, is_white, wkr, bkr);
-        }
+    // the NNUE king region of each side as an offset into the inputs
+    pub fn king_regions(&self) -> (usize, usize) {
+        let wkr = self.net.king_region(self.king[1].trailing_zeros() as i8);
+        let bkr = self.net.king_region((self.king[0].trailing_zeros() ^ 56) as i8);
+        return (wkr, bkr);
+    }
 
-        if self.side_to_move == Color::White {
-            self.net.white_turn();
-        } else {
-            self.net.black_turn();
-        }
+    pub fn nnue_eval(&mut self) -> i32 {
+        let pieces = self.piece_boards();
+        return self.net.nnue_eval(&pieces);
     }
 
     pub fn do_move(&mut self, mv: &Move) {
@@ -862,12 +878,7 @@ impl Bitboard {
         self.history.push(self.hash);
         self.ep_stack.push(self.ep_file);
         self.castling_rights_stack.push(self.castling_rights);
-        self.activation_stack.push(self.net.hidden_activations);
-
-        let wkr = region(self.king[1].trailing_zeros() as i8) as usize * 768;
-        let bkr = region((self.king[0].trailing_zeros() ^ 56) as i8) as usize * 768;
-        // let wkr = region(self.king[1].trailing_zeros() as i8);
-        // let bkr = region((self.king[0].trailing_zeros() ^ 56) as i8);
+        self.net.push();
 
         let start_point: u64 = idx_to_bb(mv.start);
         let end_point: u64 = idx_to_bb(mv.end);
@@ -890,28 +901,28 @@ impl Bitboard {
             self.pawn[them] ^= idx_to_bb(actual_pawn_idx);
             self.hash ^= en_passant_hash(actual_pawn_idx as i32, !self.side_to_move);
 
-            self.net.deactivate(PAWN, !self.side_to_move, actual_pawn_idx, wkr, bkr);
+            self.net.deactivate(PAWN, !self.side_to_move, actual_pawn_idx);
         } else if captured_piece != 0 {
             match captured_piece {
                 b'p' => {
                     self.pawn[them] ^= end_point;
-                    self.net.deactivate(PAWN, !self.side_to_move, mv.end, wkr, bkr);
+                    self.net.deactivate(PAWN, !self.side_to_move, mv.end);
                 },
                 b'n' => {
                     self.knight[them] ^= end_point;
-                    self.net.deactivate(KNIGHT, !self.side_to_move, mv.end, wkr, bkr);
+                    self.net.deactivate(KNIGHT, !self.side_to_move, mv.end);
                 },
                 b'b' => {
                     self.bishop[them] ^= end_point;
-                    self.net.deactivate(BISHOP, !self.side_to_move, mv.end, wkr, bkr);
+                    self.net.deactivate(BISHOP, !self.side_to_move, mv.end);
                 },
                 b'r' => {
                     self.rook[them] ^= end_point;
-                    self.net.deactivate(ROOK, !self.side_to_move, mv.end, wkr, bkr);
+                    self.net.deactivate(ROOK, !self.side_to_move, mv.end);
                 },
                 b'q' => {
                     self.queen[them] ^= end_point;
-                    self.net.deactivate(QUEEN, !self.side_to_move, mv.end, wkr, bkr);
+                    self.net.deactivate(QUEEN, !self.side_to_move, mv.end);
                 },
                 _ => panic!("Captured uncapturable piece {}!", captured_piece)
             };
@@ -926,14 +937,14 @@ impl Bitboard {
             // move the rook
             let rook_mask = idx_to_bb(old_rook_idx) | idx_to_bb(new_rook_idx);
             self.rook[me] ^= rook_mask;
-            self.net.move_piece(ROOK, self.side_to_move, old_rook_idx, new_rook_idx, wkr, bkr);
+            self.net.move_piece(ROOK, self.side_to_move, old_rook_idx, new_rook_idx);
             self.hash ^= simple_move_hash(b'r', old_rook_idx as i32, new_rook_idx as i32, self.side_to_move);
         }
 
         // move piece
         if mv.piece == b'p' && mv.promote_to != 0 {
             self.pawn[me] ^= start_point;
-            self.net.deactivate(PAWN, self.side_to_move, mv.start, wkr, bkr);
+            self.net.deactivate(PAWN, self.side_to_move, mv.start);
             let promo_num;
             match mv.promote_to {
                 b'q' => { self.queen[me] |= end_point; promo_num = QUEEN; },
@@ -942,33 +953,33 @@ impl Bitboard {
                 b'n' => { self.knight[me] |= end_point; promo_num = KNIGHT; },
                 _ => { panic!("illegal promotion on mv {}", mv); }
             }
-            self.net.activate(promo_num, self.side_to_move, mv.end, wkr, bkr);
+            self.net.activate(promo_num, self.side_to_move, mv.end);
         } else {
             let move_mask = start_point | end_point;
             match mv.piece {
                 b'k' => {
                     self.king[me] ^= move_mask;
-                    self.net.move_piece(KING, self.side_to_move, mv.start, mv.end, wkr, bkr);
+                    self.net.move_piece(KING, self.side_to_move, mv.start, mv.end);
                 },
                 b'q' => {
                     self.queen[me] ^= move_mask;
-                    self.net.move_piece(QUEEN, self.side_to_move, mv.start, mv.end, wkr, bkr);
+                    self.net.move_piece(QUEEN, self.side_to_move, mv.start, mv.end);
                 },
                 b'r' => {
                     self.rook[me] ^= move_mask;
-                    self.net.move_piece(ROOK, self.side_to_move, mv.start, mv.end, wkr, bkr);
+                    self.net.move_piece(ROOK, self.side_to_move, mv.start, mv.end);
                 },
                 b'b' => {
                     self.bishop[me] ^= move_mask;
-                    self.net.move_piece(BISHOP, self.side_to_move, mv.start, mv.end, wkr, bkr);
+                    self.net.move_piece(BISHOP, self.side_to_move, mv.start, mv.end);
                 },
                 b'n' => {
                     self.knight[me] ^= move_mask;
-                    self.net.move_piece(KNIGHT, self.side_to_move, mv.start, mv.end, wkr, bkr);
+                    self.net.move_piece(KNIGHT, self.side_to_move, mv.start, mv.end);
                 },
                 b'p' => {
                     self.pawn[me] ^= move_mask;
-                    self.net.move_piece(PAWN, self.side_to_move, mv.start, mv.end, wkr, bkr);
+                    self.net.move_piece(PAWN, self.side_to_move, mv.start, mv.end);
                 },
                 _ => { panic!("moved nonexistent piece {} in mv {}", mv.piece, mv); }
             }
@@ -1016,15 +1027,8 @@ impl Bitboard {
         self.side_to_move = !self.side_to_move;
 
         if mv.piece == b'k' {
-            if self.side_to_move != Color::White {
-                if region(mv.start) != region(mv.end) {
-                    self.set_kr_activations();
-                }
-            } else {
-                if region(mv.start ^ 56) != region(mv.end ^ 56) {
-                    self.set_kr_activations();
-                }
-            }
+            let (wkr, bkr) = self.king_regions();
+            self.net.set_regions(wkr, bkr);
         }
     }
 
@@ -1132,10 +1136,7 @@ impl Bitboard {
             self.net.black_turn();
         }
 
-        self.net.hidden_activations = match self.activation_stack.pop() {
-            Some(p) => p,
-            None => panic!("empty activation stack!")
-        };
+        self.net.pop();
     }
 
     pub fn is_repetition(&self) -> bool {
@@ -0,0 +1,294 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufRead, BufWriter, Write};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::bitboard::*;
+use crate::movegen::*;
+use crate::moveutil::*;
+use crate::packed::*;
+use crate::pgn::*;
+use crate::rand::*;
+use crate::search::*;
+use crate::searchutil::*;
+use crate::syzygy::*;
+use crate::tt::*;
+use crate::uci::*;
+use crate::util::*;
+
+// self-play training data generation.  every thread plays its own games
+// with its own search slot, all sharing the one transposition table.
+// scores and thresholds here are in centipawns, not internal units
+
+pub struct DatagenOptions {
+    pub games: u64,
+    pub threads: usize,
+    pub depth: i32,
+    pub nodes: u64,
+    pub random_plies: u32,
+    pub book: Option<String>,
+    pub win_score: i32,         // adjudicate a win after win_plies plies beyond this
+    pub win_plies: u32,
+    pub draw_score: i32,        // adjudicate a draw after draw_plies plies within this
+    pub draw_plies: u32,
+    pub draw_after: u32,        // ...but only once the game is this long
+    pub max_plies: u32,
+    pub max_opening_score: i32, // openings the engine already scores beyond this are dropped
+    pub out: String,
+    pub seed: u64,
+    pub hash: i32,
+    pub syzygy_path: String
+}
+
+impl DatagenOptions {
+    pub fn default() -> DatagenOptions {
+        DatagenOptions {
+            games: 100,
+            threads: 1,
+            depth: 0,
+            nodes: 5000,
+            random_plies: 8,
+            book: None,
+            win_score: 1000,
+            win_plies: 6,
+            draw_score: 10,
+            draw_plies: 10,
+            draw_after: 80,
+            max_plies: 400,
+            max_opening_score: 400,
+            out: "datagen.txt".to_string(),
+            seed: 0,
+            hash: 64,
+            syzygy_path: "".to_string()
+        }
+    }
+}
+
+fn is_threefold(pos: &Bitboard) -> bool {
+    return pos.history.iter().filter(|&h| *h == pos.hash).count() >= 2;
+}
+
+fn read_book(fname: &str) -> Result<Vec<String>, String> {
+    // one position per line.  FENs, EPD records and training data lines all
+    // work since only the board fields are kept
+    let f = match File::open(fname) {
+        Ok(f) => f,
+        Err(e) => { return Err(format!("unable to open book {}: {}", fname, e)); }
+    };
+    let mut fens = Vec::new();
+    for (i, line) in BufReader::new(f).lines().enumerate() {
+        let line = match line {
+            Ok(l) => l,
+            Err(e) => { return Err(format!("unable to read book {}: {}", fname, e)); }
+        };
+        let board_part = line.split(';').next().unwrap_or("");
+        let fields: Vec<&str> = board_part.split_whitespace().take(4).collect();
+        if fields.is_empty() { continue; }
+        let fen = format!("{} 0 1", fields.join(" "));
+        if let Err(e) = Bitboard::validate_fen(&fen) {
+            return Err(format!("bad position on line {} of {}: {}", i + 1, fname, e));
+        }
+        fens.push(fen);
+    }
+    if fens.is_empty() {
+        return Err(format!("no positions in book {}", fname));
+    }
+    return Ok(fens);
+}
+
+// the starting position plus random legal plies.  returns None if the
+// random moves ran into the end of the game
+fn opening(book: &[String], random_plies: u32, rng: &mut u64) -> Option<Bitboard> {
+    let fen = if book.is_empty() {
+        START_FEN.to_string()
+    } else {
+        book[(u64::rand_with(rng) % book.len() as u64) as usize].clone()
+    };
+    let mut pos = Bitboard::from_position(fen);
+    for _ in 0..random_plies {
+        let legal = legal_moves(&mut pos);
+        if legal.is_empty() { return None; }
+        let mv = legal[(u64::rand_with(rng) % legal.len() as u64) as usize];
+        pos.do_move(&mv);
+    }
+    if legal_moves(&mut pos).is_empty() { return None; }
+    return Some(pos);
+}
+
+fn search_depth(options: &DatagenOptions) -> i32 {
+    return if options.depth > 0 { options.depth } else { MAX_DEPTH as i32 - 1 };
+}
+
+// plays out one game, returning the recorded positions and the result
+fn play_game(pos: &mut Bitboard, options: &DatagenOptions, thread_num: usize) -> (Vec<PackedPosition>, GameResult) {
+    let depth = search_depth(options);
+    let mut positions: Vec<PackedPosition> = Vec::new();
+    let mut win_streak: u32 = 0;
+    let mut win_side: i32 = 0;
+    let mut draw_streak: u32 = 0;
+    let mut ply: u32 = 0;
+
+    // from white's point of view
+    let result_for = |white_score: i32| -> GameResult {
+        if white_score > 0 { GameResult::WhiteWin } else if white_score < 0 { GameResult::BlackWin } else { GameResult::Draw }
+    };
+
+    loop {
+        let mul = if pos.side_to_move == Color::White { 1 } else { -1 };
+        let legal = legal_moves(pos);
+        if legal.is_empty() {
+            // checkmate or stalemate
+            let result = if pos.is_check(pos.side_to_move) { result_for(-mul) } else { GameResult::Draw };
+            return (positions, result);
+        }
+        if is_threefold(pos) || pos.is_fifty_move() || pos.insufficient_material() || ply >= options.max_plies {
+            return (positions, GameResult::Draw);
+        }
+        if tb_active() && pos.castling_rights == 0 && pos.num_pieces() <= max_tb_pieces() {
+            if let Some(wdl) = probe_wdl(pos) {
+                return (positions, result_for(mul * wdl));
+            }
+        }
+
+        let (mv, val) = fixed_search(pos, depth, options.nodes, thread_num);
+        if mv.is_null() {
+            // shouldn't happen with legal moves available, but don't loop forever
+            return (positions, GameResult::Draw);
+        }
+        let white_score = mul * val / 10;
+
+        // score adjudication
+        if white_score.abs() >= options.win_score {
+            let side = white_score.signum();
+            win_streak = if side == win_side { win_streak + 1 } else { 1 };
+            win_side = side;
+        } else {
+            win_streak = 0;
+            win_side = 0;
+        }
+        if white_score.abs() <= options.draw_score && ply >= options.draw_after {
+            draw_streak += 1;
+        } else {
+            draw_streak = 0;
+        }
+        if win_streak >= options.win_plies {
+            return (positions, result_for(win_side));
+        }
+        if draw_streak >= options.draw_plies {
+            return (positions, GameResult::Draw);
+        }
+
+        // only quiet positions with a real score are worth training on
+        if !pos.is_check(pos.side_to_move) && is_quiet_move(&mv, pos) && val.abs() < MIN_TB_WIN_SCORE {
+            positions.push(PackedPosition::from_board(pos, white_score, 0.5));
+        }
+
+        pos.do_move(&mv);
+        ply += 1;
+    }
+}
+
+fn datagen_worker(options: &DatagenOptions, book: &[String], thread_num: usize, next_game: &AtomicU64, positions_written: &AtomicU64, out: &Mutex<BufWriter<File>>, start_time: u128) {
+    let mut rng = options.seed ^ ((thread_num as u64 + 1).wrapping_mul(0x9e3779b97f4a7c15));
+    for _ in 0..64 { u64::rand_with(&mut rng); }
+
+    loop {
+        let game_num = next_game.fetch_add(1, Ordering::SeqCst);
+        if game_num >= options.games { return; }
+
+        let mut pos = loop {
+            let mut pos = match opening(book, options.random_plies, &mut rng) {
+                Some(p) => p,
+                None => { continue; }
+            };
+            // skip openings that are already decided
+            unsafe { TI[thread_num] = ThreadInfo::new(UCIOptions::default()); }
+            let (_, val) = fixed_search(&mut pos, search_depth(options), options.nodes, thread_num);
+            if (val / 10).abs() <= options.max_opening_score { break pos; }
+        };
+
+        unsafe { TI[thread_num] = ThreadInfo::new(UCIOptions::default()); }
+        let (mut positions, result) = play_game(&mut pos, options, thread_num);
+        let outcome = result.outcome().unwrap_or(0.5);
+
+        let total = {
+            let mut writer = out.lock().unwrap();
+            for p in positions.iter_mut() {
+                p.set_outcome(outcome);
+                let written = if is_packed_file(&options.out) {
+                    writer.write_all(&p.to_bytes())
+                } else {
+                    writeln!(writer, "{}", p.to_text())
+                };
+                if let Err(e) = written {
+                    eprintln!("ERR: unable to write {}: {}", options.out, e);
+                    std::process::exit(1);
+                }
+            }
+            let _ = writer.flush();
+            positions_written.fetch_add(positions.len() as u64, Ordering::SeqCst) + positions.len() as u64
+        };
+
+        let elapsed = (get_time_millis() - start_time).max(1);
+        println!("game {} {} ({} positions), {} positions total, {} positions/s",
+                 game_num + 1, result.to_str(), positions.len(), total, (total as u128 * 1000) / elapsed);
+    }
+}
+
+pub fn datagen(options: &DatagenOptions) -> Result<(), String> {
+    let book = match &options.book {
+        Some(fname) => read_book(fname)?,
+        None => Vec::new()
+    };
+    if !options.syzygy_path.is_empty() && !unsafe { setup_tb(&options.syzygy_path) } {
+        return Err(format!("unable to load tablebases from {}", options.syzygy_path));
+    }
+
+    let f = match OpenOptions::new().create(true).append(true).open(&options.out) {
+        Ok(f) => f,
+        Err(e) => { return Err(format!("unable to open {}: {}", options.out, e)); }
+    };
+    let out = Mutex::new(BufWriter::new(f));
+
+    allocate_tt(options.hash as usize);
+    set_silent(true);
+    setup_search_slots(options.threads, &UCIOptions::default());
+
+    let next_game = AtomicU64::new(0);
+    let positions_written = AtomicU64::new(0);
+    let start_time = get_time_millis();
+    std::thread::scope(|s| {
+        for t in 0..options.threads {
+            let (book, next_game, positions_written, out) = (&book, &next_game, &positions_written, &out);
+            s.spawn(move || datagen_worker(options, book, t, next_game, positions_written, out, start_time));
+        }
+    });
+
+    println!("wrote {} positions from {} games to {}", positions_written.load(Ordering::SeqCst), options.games, options.out);
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eval::*;
+
+    // the transposition table keeps each entry's ply in an i8, so a game
+    // that runs past 127 plies has to wrap the entry ages, not overflow them.
+    // draws aren't claimed here, and neither side can do much to the other,
+    // so the game is sure to get that far
+    #[test]
+    fn long_game() {
+        crate::test_init();
+        set_eval_mode(EvalMode::Hce);
+        setup_search_slots(1, &UCIOptions::default());
+        // a single bucket, so every store has to weigh up the entry's age
+        allocate_tt(0);
+        let mut pos = Bitboard::from_position("r3k3/pp6/8/8/8/8/PP6/R3K3 w - - 0 1".to_string());
+        while pos.history.len() < 160 {
+            assert!(!legal_moves(&mut pos).is_empty(), "game ended after {} plies: {}", pos.history.len(), pos.fen());
+            let (mv, _) = fixed_search(&mut pos, 5, 0, 0);
+            pos.do_move(&mv);
+        }
+    }
+}
@@ -0,0 +1,119 @@
+// default weights for the hand-crafted evaluation
+// this file is written by the tuner, see EvalParams::to_rust
+
+use crate::eval::*;
+use crate::evalparams::*;
+
+macro_rules! S {
+    ($a:expr, $b:expr) => {
+        make_score($a, $b)
+    }
+}
+
+pub const DEFAULT_EVAL_PARAMS: EvalParams = EvalParams {
+    queen_value: S!(10949, 16252),
+    rook_value: S!(4919, 8583),
+    bishop_value: S!(3523, 5315),
+    knight_value: S!(3466, 4834),
+    pawn_value: S!(841, 1440),
+    knight_mobility: [S!(-507, 133), S!(-112, 2), S!(-5, 326), S!(151, 520), S!(224, 528), S!(226, 538), S!(272, 528), S!(320, 427), S!(313, 359)],
+    bishop_mobility: [S!(-108, 52), S!(-26, -198), S!(275, -808), S!(271, -251), S!(448, -61), S!(440, 190), S!(474, 373), S!(571, 478), S!(559, 590), S!(553, 661), S!(551, 686), S!(567, 627), S!(449, 664), S!(1135, 336)],
+    rook_mobility: [S!(147, -2), S!(519, 255), S!(-154, -376), S!(-54, -73), S!(-33, 177), S!(9, 446), S!(-23, 627), S!(-24, 788), S!(19, 832), S!(60, 903), S!(113, 962), S!(170, 988), S!(262, 968), S!(288, 975), S!(541, 780)],
+    queen_mobility: [S!(30, -158), S!(-204, 340), S!(-361, -96), S!(-231, -84), S!(6, -217), S!(89, -183), S!(87, 4), S!(145, -33), S!(193, -169), S!(191, 100), S!(225, 231), S!(222, 436), S!(239, 594), S!(239, 705), S!(299, 673), S!(260, 780), S!(284, 868), S!(265, 904), S!(223, 929), S!(257, 874), S!(159, 981), S!(260, 741), S!(224, 792), S!(-71, 938), S!(46, 830), S!(320, 565), S!(84, 759), S!(208, 699)],
+    knight_outpost_value: S!(318, 334),
+    bishop_outpost_value: S!(374, 73),
+    bishop_long_diagonal_value: S!(118, 120),
+    pawn_proximity_value: [S!(110, 130), S!(-61, -71), S!(-124, 136), S!(-35, -10), S!(-44, -17), S!(59, -5), S!(-74, -270), S!(30, -15)],
+    pawn_shelter_value: [
+        [S!(240, 25), S!(88, -11), S!(0, 57), S!(-120, -61), S!(-6, -251), S!(-13, -328), S!(-41, -70), S!(-268, 31)],
+        [S!(-48, -119), S!(188, 31), S!(155, 34), S!(25, -55), S!(-80, -37), S!(19, -242), S!(-334, -176), S!(-266, 29)]
+    ],
+    pawn_storm_value: [
+        [S!(516, 602), S!(871, 555), S!(-341, 140), S!(-154, -12), S!(6, -55), S!(43, -94), S!(-5, -66), S!(-17, -107)],
+        [S!(2, -70), S!(16, 22), S!(-398, -195), S!(43, -190), S!(92, -110), S!(8, -154), S!(104, 142), S!(-55, -235)]
+    ],
+    queen_king_danger: [S!(0, 0), S!(95, 1), S!(314, 64), S!(662, 152), S!(938, 1005), S!(473, 563)],
+    rook_king_danger: [S!(0, 0), S!(4, 113), S!(167, 48), S!(184, 147), S!(336, 211), S!(652, 668)],
+    bishop_king_danger: [S!(0, 0), S!(51, 301), S!(207, 88), S!(235, 34), S!(114, 71), S!(171, 1)],
+    knight_king_danger: [S!(0, 0), S!(1, 392), S!(178, 81), S!(332, 1), S!(62, 321), S!(22, 151)],
+    no_queen_attack_value: S!(-276, -462),
+    weak_square_value: S!(0, 0),
+    queen_check_value: S!(241, 256),
+    rook_check_value: S!(436, 112),
+    bishop_check_value: S!(118, 131),
+    knight_check_value: S!(1000, 21),
+    double_bishop_bonus: S!(149, 978),
+    passed_pawn_value: [S!(0, 0), S!(0, 188), S!(0, 214), S!(0, 537), S!(260, 823), S!(661, 1261), S!(663, 1188), S!(0, 0)],
+    candidate_passed_pawn_value: [S!(0, 0), S!(0, 0), S!(0, 0), S!(0, 191), S!(410, 506), S!(315, 604), S!(175, 245), S!(0, 0)],
+    center_pawn_value: S!(0, 1),
+    isolated_pawn_value: S!(-33, -80),
+    doubled_pawn_value: S!(0, -252),
+    backwards_pawn_value: S!(-45, -62),
+    advanced_pawn_value: [S!(0, 0), S!(25, 0), S!(63, 43), S!(68, 69), S!(104, 156), S!(353, 302), S!(478, 414), S!(0, 0)],
+    supported_pawn_bonus: S!(137, 64),
+    space_value: S!(16, 0),
+    bishop_color: S!(-33, -111),
+    tempo_bonus: S!(130, 130),
+    rook_on_seventh: S!(0, 74),
+    rook_on_open: S!(118, 90),
+    pawn_psqt: [
+        [S!(0, 0), S!(0, 0), S!(0, 0), S!(0, 0)],
+        [S!(-227, 37), S!(-40, 131), S!(-134, 269), S!(-220, 229)],
+        [S!(-209, -5), S!(-220, 20), S!(-193, 73), S!(-150, 51)],
+        [S!(-164, 61), S!(-54, 57), S!(-72, -47), S!(-21, -111)],
+        [S!(-92, 247), S!(48, 139), S!(18, 47), S!(169, -157)],
+        [S!(-214, 816), S!(212, 680), S!(524, 349), S!(199, 108)],
+        [S!(1086, 1493), S!(954, 1454), S!(1365, 1065), S!(1701, 662)],
+        [S!(0, 0), S!(0, 0), S!(0, 0), S!(0, 0)]
+    ],
+    knight_psqt: [
+        [S!(-955, -783), S!(-288, -708), S!(-309, -347), S!(-318, -213)],
+        [S!(-214, -402), S!(-417, -147), S!(-265, -118), S!(-199, 73)],
+        [S!(-372, -471), S!(-123, 7), S!(-134, 291), S!(21, 496)],
+        [S!(-122, -106), S!(162, 232), S!(16, 657), S!(121, 674)],
+        [S!(81, -133), S!(25, 167), S!(307, 450), S!(129, 662)],
+        [S!(-74, -357), S!(90, 25), S!(332, 412), S!(443, 369)],
+        [S!(-123, -342), S!(-422, -18), S!(360, -85), S!(410, 282)],
+        [S!(-1512, -1098), S!(-427, -256), S!(-939, 234), S!(-21, 28)]
+    ],
+    bishop_psqt: [
+        [S!(8, -169), S!(-26, -55), S!(-160, -52), S!(-278, -12)],
+        [S!(1, -242), S!(-26, -262), S!(-18, -198), S!(-183, -15)],
+        [S!(-116, 41), S!(-62, 3), S!(-176, -55), S!(-63, 103)],
+        [S!(-139, 47), S!(-68, 95), S!(-101, 206), S!(78, 236)],
+        [S!(-297, 194), S!(-3, 159), S!(-120, 144), S!(43, 254)],
+        [S!(-144, 202), S!(80, 161), S!(26, 112), S!(64, 60)],
+        [S!(-530, 234), S!(-414, 113), S!(-143, 237), S!(-286, 128)],
+        [S!(-575, 113), S!(-62, 201), S!(-1113, 314), S!(-735, 319)]
+    ],
+    rook_psqt: [
+        [S!(-255, -103), S!(-236, -29), S!(-160, -19), S!(-141, -91)],
+        [S!(-644, -111), S!(-265, -167), S!(-222, -178), S!(-216, -185)],
+        [S!(-368, -71), S!(-201, -17), S!(-441, 76), S!(-262, -35)],
+        [S!(-345, 132), S!(-234, 260), S!(-270, 264), S!(-215, 186)],
+        [S!(-36, 242), S!(106, 232), S!(195, 258), S!(260, 210)],
+        [S!(-31, 359), S!(547, 135), S!(351, 294), S!(629, 138)],
+        [S!(211, 335), S!(65, 418), S!(452, 281), S!(531, 357)],
+        [S!(733, 301), S!(511, 394), S!(224, 535), S!(372, 420)]
+    ],
+    queen_psqt: [
+        [S!(197, -561), S!(49, -694), S!(75, -745), S!(109, -469)],
+        [S!(68, -694), S!(192, -770), S!(194, -848), S!(123, -402)],
+        [S!(112, -518), S!(124, -311), S!(23, -93), S!(7, -131)],
+        [S!(69, 3), S!(114, 64), S!(-41, 348), S!(-128, 487)],
+        [S!(88, 182), S!(49, 683), S!(-124, 633), S!(-159, 672)],
+        [S!(-22, 235), S!(208, 223), S!(-19, 673), S!(33, 590)],
+        [S!(-187, 570), S!(-622, 561), S!(-170, 649), S!(-459, 959)],
+        [S!(202, -10), S!(287, 140), S!(-23, 589), S!(247, 280)]
+    ],
+    king_psqt: [
+        [S!(178, -1242), S!(337, -382), S!(-163, -140), S!(-53, -437)],
+        [S!(73, -436), S!(-55, -36), S!(-434, 261), S!(-524, 297)],
+        [S!(-359, -285), S!(200, -31), S!(104, 236), S!(151, 397)],
+        [S!(-684, -375), S!(790, 20), S!(597, 274), S!(349, 480)],
+        [S!(-346, -158), S!(481, 131), S!(717, 322), S!(165, 474)],
+        [S!(-8, -28), S!(678, 406), S!(865, 366), S!(746, 321)],
+        [S!(-165, -468), S!(17, 519), S!(546, 145), S!(-187, 170)],
+        [S!(-444, -1606), S!(48, -437), S!(41, -264), S!(-302, -287)]
+    ],
+};
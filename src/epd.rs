@@ -0,0 +1,328 @@
+use std::fs::File;
+use std::io::{BufReader, BufRead};
+
+use crate::bitboard::*;
+use crate::moveutil::*;
+use crate::pgn::*;
+use crate::util::*;
+
+// an EPD record is the first four fields of a FEN followed by any number of
+// operations of the form `opcode operand ...;`
+#[derive(Clone, Debug)]
+pub struct EpdRecord {
+    pub position: String,
+    pub ops: Vec<(String, Vec<String>)>
+}
+
+fn split_operands(s: &str) -> Result<Vec<String>, String> {
+    let mut operands = Vec::new();
+    let mut chars = s.chars().peekable();
+    loop {
+        while let Some(c) = chars.peek() {
+            if c.is_whitespace() { chars.next(); } else { break; }
+        }
+        let c = match chars.next() {
+            Some(c) => c,
+            None => { return Ok(operands); }
+        };
+        let mut operand = String::new();
+        if c == '"' {
+            loop {
+                match chars.next() {
+                    Some('\\') => { if let Some(e) = chars.next() { operand.push(e); } },
+                    Some('"') => { break; },
+                    Some(c) => { operand.push(c); },
+                    None => { return Err(format!("unterminated string operand in '{}'", s.trim())); }
+                }
+            }
+        } else {
+            operand.push(c);
+            while let Some(c) = chars.peek() {
+                if c.is_whitespace() { break; }
+                operand.push(*c);
+                chars.next();
+            }
+        }
+        operands.push(operand);
+    }
+}
+
+fn is_string_op(opcode: &str) -> bool {
+    return opcode == "id" || (opcode.len() == 2 && opcode.starts_with('c') && opcode.as_bytes()[1].is_ascii_digit());
+}
+
+impl EpdRecord {
+    pub fn parse(line: &str) -> Result<EpdRecord, String> {
+        let line = line.trim();
+        let mut rest = line;
+        let mut fields: Vec<&str> = Vec::new();
+        for _ in 0..4 {
+            rest = rest.trim_start();
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            if end == 0 {
+                return Err(format!("expected 4 position fields in '{}'", line));
+            }
+            fields.push(&rest[..end]);
+            rest = &rest[end..];
+        }
+        let position = fields.join(" ");
+        Bitboard::validate_fen(&format!("{} 0 1", position))?;
+
+        // split the operations on semicolons that aren't inside a string
+        let mut ops = Vec::new();
+        let mut current = String::new();
+        let mut in_string = false;
+        let mut escaped = false;
+        for c in rest.chars() {
+            if c == ';' && !in_string {
+                let operands = split_operands(&current)?;
+                if !operands.is_empty() {
+                    ops.push((operands[0].clone(), operands[1..].to_vec()));
+                }
+                current.clear();
+                continue;
+            }
+            if c == '"' && !escaped {
+                in_string = !in_string;
+            }
+            escaped = c == '\\' && !escaped;
+            current.push(c);
+        }
+        if !current.trim().is_empty() {
+            // be lenient about a missing final semicolon
+            let operands = split_operands(&current)?;
+            ops.push((operands[0].clone(), operands[1..].to_vec()));
+        }
+
+        return Ok(EpdRecord {
+            position: position,
+            ops: ops
+        });
+    }
+
+    pub fn from_board(pos: &Bitboard) -> EpdRecord {
+        let fen = pos.fen();
+        let fields: Vec<&str> = fen.split(' ').collect();
+        let mut record = EpdRecord {
+            position: fields[..4].join(" "),
+            ops: Vec::new()
+        };
+        record.set_op("hmvc", vec![fields[4].to_string()]);
+        record.set_op("fmvn", vec![fields[5].to_string()]);
+        return record;
+    }
+
+    pub fn op(&self, opcode: &str) -> Option<&Vec<String>> {
+        for (op, operands) in &self.ops {
+            if op == opcode {
+                return Some(operands);
+            }
+        }
+        return None;
+    }
+
+    pub fn set_op(&mut self, opcode: &str, operands: Vec<String>) {
+        for (op, existing) in self.ops.iter_mut() {
+            if op == opcode {
+                *existing = operands;
+                return;
+            }
+        }
+        self.ops.push((opcode.to_string(), operands));
+    }
+
+    pub fn remove_op(&mut self, opcode: &str) {
+        self.ops.retain(|(op, _)| op != opcode);
+    }
+
+    fn int_op(&self, opcode: &str) -> Option<i64> {
+        return self.op(opcode)?.first()?.parse().ok();
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        return self.op("id")?.first().map(|s| s.as_str());
+    }
+
+    // centipawn evaluation from the side to move
+    pub fn ce(&self) -> Option<i64> {
+        return self.int_op("ce");
+    }
+
+    pub fn acd(&self) -> Option<i64> {
+        return self.int_op("acd");
+    }
+
+    pub fn fen(&self) -> String {
+        let halfmove = self.int_op("hmvc").unwrap_or(0);
+        let fullmove = self.int_op("fmvn").unwrap_or(1);
+        return format!("{} {} {}", self.position, halfmove, fullmove);
+    }
+
+    pub fn to_board(&self) -> Result<Bitboard, String> {
+        let fen = self.fen();
+        Bitboard::validate_fen(&fen)?;
+        return Ok(Bitboard::from_position(fen));
+    }
+
+    fn op_moves(&self, opcode: &str) -> Result<Vec<Move>, String> {
+        let operands = match self.op(opcode) {
+            Some(o) => o,
+            None => { return Ok(Vec::new()); }
+        };
+        let mut board = self.to_board()?;
+        let mut moves = Vec::new();
+        for s in operands {
+            let mv = parse_move(&mut board, s);
+            if mv.is_null() {
+                return Err(format!("{}: bad move '{}' in position {}", opcode, s, self.position));
+            }
+            moves.push(mv);
+        }
+        return Ok(moves);
+    }
+
+    pub fn best_moves(&self) -> Result<Vec<Move>, String> {
+        return self.op_moves("bm");
+    }
+
+    pub fn avoid_moves(&self) -> Result<Vec<Move>, String> {
+        return self.op_moves("am");
+    }
+
+    // the pv is a sequence, so each move is parsed in the position left by
+    // the previous one
+    pub fn pv(&self) -> Result<Vec<Move>, String> {
+        let operands = match self.op("pv") {
+            Some(o) => o,
+            None => { return Ok(Vec::new()); }
+        };
+        let mut board = self.to_board()?;
+        let mut moves = Vec::new();
+        for s in operands {
+            let mv = parse_move(&mut board, s);
+            if mv.is_null() {
+                return Err(format!("pv: bad move '{}' in position {}", s, board.fen()));
+            }
+            board.do_move(&mv);
+            moves.push(mv);
+        }
+        return Ok(moves);
+    }
+
+    pub fn set_pv(&mut self, pv: &[Move]) -> Result<(), String> {
+        let mut board = self.to_board()?;
+        let mut sans = Vec::new();
+        for mv in pv {
+            sans.push(move_to_san(&mut board, mv));
+            board.do_move(mv);
+        }
+        self.set_op("pv", sans);
+        return Ok(());
+    }
+
+    pub fn to_epd(&self) -> String {
+        let mut s = self.position.clone();
+        for (op, operands) in &self.ops {
+            s.push(' ');
+            s.push_str(op);
+            for operand in operands {
+                s.push(' ');
+                if is_string_op(op) || operand.is_empty() || operand.contains(|c: char| c.is_whitespace() || c == ';' || c == '"') {
+                    s.push_str(&format!("\"{}\"", operand.replace('\\', "\\\\").replace('"', "\\\"")));
+                } else {
+                    s.push_str(operand);
+                }
+            }
+            s.push(';');
+        }
+        return s;
+    }
+}
+
+// accepts SAN as well as coordinate notation, since suites use both
+pub fn parse_move(pos: &mut Bitboard, s: &str) -> Move {
+    let mv = san_to_move(pos, s.to_string());
+    if !mv.is_null() {
+        return mv;
+    }
+    let b = s.as_bytes();
+    if b.len() < 4 || b.len() > 5 {
+        return Move::null_move();
+    }
+    for mv in legal_moves(pos) {
+        if mv.get_repr() == s {
+            return mv;
+        }
+    }
+    return Move::null_move();
+}
+
+pub fn read_epd_file(fname: &str) -> Result<Vec<EpdRecord>, String> {
+    let f = match File::open(fname) {
+        Ok(f) => f,
+        Err(e) => { return Err(format!("unable to open file {}: {}", fname, e)); }
+    };
+
+    let mut records = Vec::new();
+    for (i, line) in BufReader::new(f).lines().enumerate() {
+        let line = match line {
+            Ok(l) => l,
+            Err(e) => { return Err(format!("unable to read line {}: {}", i + 1, e)); }
+        };
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match EpdRecord::parse(&line) {
+            Ok(r) => { records.push(r); },
+            Err(e) => { eprintln!("ERR: skipping line {} of {}: {}", i + 1, fname, e); }
+        }
+    }
+    return Ok(records);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_write() {
+        crate::test_init();
+        let line = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - bm Bb5 Bc4; ce 35; id \"a \\\"quoted\\\" id; with a semicolon\";";
+        let record = EpdRecord::parse(line).unwrap();
+        assert_eq!(record.position, "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq -");
+        assert_eq!(record.op("bm").unwrap(), &vec!["Bb5".to_string(), "Bc4".to_string()]);
+        assert_eq!(record.ce(), Some(35));
+        assert_eq!(record.id(), Some("a \"quoted\" id; with a semicolon"));
+        assert_eq!(record.to_epd(), line);
+
+        let bm: Vec<String> = record.best_moves().unwrap().iter().map(|m| m.get_repr()).collect();
+        assert_eq!(bm, vec!["f1b5", "f1c4"]);
+    }
+
+    #[test]
+    fn missing_final_semicolon() {
+        let record = EpdRecord::parse("8/8/8/8/8/8/k7/K7 b - - acd 12").unwrap();
+        assert_eq!(record.acd(), Some(12));
+        assert_eq!(record.to_epd(), "8/8/8/8/8/8/k7/K7 b - - acd 12;");
+    }
+
+    #[test]
+    fn too_few_fields() {
+        assert!(EpdRecord::parse("8/8/8/8/8/8/k7/K7 b -").is_err());
+    }
+
+    // the board doesn't keep a fullmove number, so these all start at 1
+    #[test]
+    fn board_round_trip() {
+        crate::test_init();
+        for fen in [
+            "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+            "r3k2r/8/8/8/8/8/8/R3K2R w Kq - 12 1",
+        ] {
+            let record = EpdRecord::from_board(&Bitboard::from_position(fen.to_string()));
+            let parsed = EpdRecord::parse(&record.to_epd()).unwrap();
+            assert_eq!(parsed.fen(), fen);
+            assert_eq!(parsed.to_board().unwrap().fen(), fen);
+        }
+    }
+}
@@ -1,7 +1,9 @@
 use std::cmp;
 
 use crate::bitboard::*;
+use crate::evalparams::*;
 use crate::evalutil::*;
+use crate::json::*;
 use crate::movegen::*;
 use crate::pht::*;
 use crate::psqt::*;
@@ -30,90 +32,65 @@ pub fn taper_score(s: Score, phase: i32) -> i32 {
     ((256 - phase) * mg_score(s) + phase * eg_score(s)) >> 8
 }
 
-// we're going to have large arrays and so on
-// where "make_score" will become cumbersome
-// we'll use the longer form where we can (e.g. in functions)
-// but use "S" when it makes things easier to read
-macro_rules! S {
-    ($a:expr, $b:expr) => {
-        make_score($a, $b)
+// a term's value for each side, indexed by color.  the eval only needs the
+// difference, but traces show both
+pub type SideScores = [Score; 2];
+
+fn side_difference(s: SideScores) -> Score {
+    return s[Color::White as usize] - s[Color::Black as usize];
+}
+
+// which evaluation static_eval uses.  hybrid falls back on the cheaper
+// handcrafted eval when one side is far ahead in material
+#[derive(Clone, Copy, PartialEq)]
+pub enum EvalMode {
+    Nnue,
+    Hce,
+    Hybrid
+}
+
+impl EvalMode {
+    pub fn from_str(s: &str) -> Option<EvalMode> {
+        return match s.to_ascii_lowercase().as_str() {
+            "nnue" => Some(EvalMode::Nnue),
+            "hce" => Some(EvalMode::Hce),
+            "hybrid" => Some(EvalMode::Hybrid),
+            _ => None
+        };
     }
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            EvalMode::Nnue => "NNUE",
+            EvalMode::Hce => "HCE",
+            EvalMode::Hybrid => "Hybrid"
+        };
+    }
+}
+
+static mut EVAL_MODE: EvalMode = EvalMode::Nnue;
+
+pub fn eval_mode() -> EvalMode {
+    return unsafe { EVAL_MODE };
+}
+
+pub fn set_eval_mode(mode: EvalMode) {
+    unsafe { EVAL_MODE = mode; }
 }
 
-pub const QUEEN_VALUE: Score = S!(10949, 16252);
-pub const ROOK_VALUE: Score = S!(4919, 8583);
-pub const BISHOP_VALUE: Score = S!(3523, 5315);
-pub const KNIGHT_VALUE: Score = S!(3466, 4834);
-pub const PAWN_VALUE: Score = S!(841, 1440);
-
-pub const KNIGHT_MOBILITY: [Score; 9] = [S!(-507, 133), S!(-112, 2), S!(-5, 326), S!(151, 520), S!(224, 528), S!(226, 538), S!(272, 528), S!(320, 427), S!(313, 359)];
-pub const BISHOP_MOBILITY: [Score; 14] = [S!(-108, 52), S!(-26, -198), S!(275, -808), S!(271, -251), S!(448, -61), S!(440, 190), S!(474, 373), S!(571, 478), S!(559, 590), S!(553, 661), S!(551, 686), S!(567, 627), S!(449, 664), S!(1135, 336)];
-pub const ROOK_MOBILITY: [Score; 15] = [S!(147, -2), S!(519, 255), S!(-154, -376), S!(-54, -73), S!(-33, 177), S!(9, 446), S!(-23, 627), S!(-24, 788), S!(19, 832), S!(60, 903), S!(113, 962), S!(170, 988), S!(262, 968), S!(288, 975), S!(541, 780)];
-pub const QUEEN_MOBILITY: [Score; 28] = [S!(30, -158), S!(-204, 340), S!(-361, -96), S!(-231, -84), S!(6, -217), S!(89, -183), S!(87, 4), S!(145, -33), S!(193, -169), S!(191, 100), S!(225, 231), S!(222, 436), S!(239, 594), S!(239, 705), S!(299, 673), S!(260, 780), S!(284, 868), S!(265, 904), S!(223, 929), S!(257, 874), S!(159, 981), S!(260, 741), S!(224, 792), S!(-71, 938), S!(46, 830), S!(320, 565), S!(84, 759), S!(208, 699)];
-pub const KNIGHT_OUTPOST_VALUE: Score = S!(318, 334);
-pub const BISHOP_OUTPOST_VALUE: Score = S!(374, 73);
-pub const BISHOP_LONG_DIAGONAL_VALUE: Score = S!(118, 120);
-
-pub const PAWN_PROXIMITY_VALUE: [Score; 8] = [S!(110, 130), S!(-61, -71), S!(-124, 136), S!(-35, -10), S!(-44, -17), S!(59, -5), S!(-74, -270), S!(30, -15)];
-pub const PAWN_SHELTER_VALUE: [[Score; 8]; 2] = [
-    [S!(240, 25), S!(88, -11), S!(0, 57), S!(-120, -61), S!(-6, -251), S!(-13, -328), S!(-41, -70), S!(-268, 31)],
-    [S!(-48, -119), S!(188, 31), S!(155, 34), S!(25, -55), S!(-80, -37), S!(19, -242), S!(-334, -176), S!(-266, 29)]
-];
-pub const PAWN_STORM_VALUE: [[Score; 8]; 2] = [
-    [S!(516, 602), S!(871, 555), S!(-341, 140), S!(-154, -12), S!(6, -55), S!(43, -94), S!(-5, -66), S!(-17, -107)],
-    [S!(2, -70), S!(16, 22), S!(-398, -195), S!(43, -190), S!(92, -110), S!(8, -154), S!(104, 142), S!(-55, -235)]
-];
-
-pub const QUEEN_KING_DANGER: [Score; 6] = [
-    S!(0, 0), S!(95, 1), S!(314, 64), S!(662, 152), S!(938, 1005), S!(473, 563)
-];
-pub const ROOK_KING_DANGER: [Score; 6] = [
-    S!(0, 0), S!(4, 113), S!(167, 48), S!(184, 147), S!(336, 211), S!(652, 668)
-];
-pub const BISHOP_KING_DANGER: [Score; 6] = [
-    S!(0, 0), S!(51, 301), S!(207, 88), S!(235, 34), S!(114, 71), S!(171, 1)
-];
-pub const KNIGHT_KING_DANGER: [Score; 6] = [
-    S!(0, 0), S!(1, 392), S!(178, 81), S!(332, 1), S!(62, 321), S!(22, 151)
-];
-pub const NO_QUEEN_ATTACK_VALUE: Score = S!(-276, -462);
-pub const WEAK_SQUARE_VALUE: Score = S!(0, 0);
-pub const QUEEN_CHECK_VALUE: Score = S!(241, 256);
-pub const ROOK_CHECK_VALUE: Score = S!(436, 112);
-pub const BISHOP_CHECK_VALUE: Score = S!(118, 131);
-pub const KNIGHT_CHECK_VALUE: Score = S!(1000, 21);
-
-pub const DOUBLE_BISHOP_BONUS: Score = S!(149, 978);
-
-pub const PASSED_PAWN_VALUE: [Score; 8] = [
-    S!(  0,   0), S!(0, 188), S!(0, 214), S!(0, 537), S!(260, 823), S!(661, 1261), S!(663, 1188), S!(0,   0)
-];
-
-pub const CANDIDATE_PASSED_PAWN_VALUE: [Score; 8] = [
-    S!(  0,   0),S!(0, 0), S!(0, 0), S!(0, 191), S!(410, 506), S!(315, 604), S!(175, 245), S!(0,   0)
-];
-pub const CENTER_PAWN_VALUE: Score = S!(0, 1);
-pub const ISOLATED_PAWN_VALUE: Score = S!(-33, -80);
-pub const DOUBLED_PAWN_VALUE: Score = S!(0, -252);
-pub const BACKWARDS_PAWN_VALUE: Score = S!(-45, -62);
-pub const ADVANCED_PAWN_VALUE: [Score; 8] = [
-    S!( 0,   0), S!(25, 0), S!(63, 43), S!(68, 69), S!(104, 156), S!(353, 302), S!(478, 414), S!( 0,  0)
-];
-pub const SUPPORTED_PAWN_BONUS: Score = S!(137, 64);
-pub const SPACE_VALUE: Score = S!(16, 0);
-
-pub const BISHOP_COLOR: Score = S!(-33, -111);
-
-pub const TEMPO_BONUS: Score = S!(130, 130);
-
-pub const ROOK_ON_SEVENTH: Score = S!(0, 74);
-pub const ROOK_ON_OPEN: Score = S!(118, 90);
+// a material imbalance past which hybrid mode doesn't bother with the net
+const HYBRID_MATERIAL_MARGIN: i32 = 4000;
+
+fn is_lopsided(pos: &Bitboard) -> bool {
+    let imbalance = taper_score(material_score(pos, eval_params()), pos.get_phase());
+    return imbalance.abs() > HYBRID_MATERIAL_MARGIN;
+}
 
 pub fn static_eval(pos: &mut Bitboard, pht: &mut PHT) -> i32 {
-    let score = if pos.net.is_valid() {
-        pos.net.nnue_eval()
-    } else {
-        evaluate_position(pos, pht)
+    let score = match eval_mode() {
+        EvalMode::Nnue => pos.nnue_eval(),
+        EvalMode::Hce => evaluate_position(pos, pht),
+        EvalMode::Hybrid => if is_lopsided(pos) { evaluate_position(pos, pht) } else { pos.nnue_eval() }
     };
 
     return if pos.side_to_move == Color::White {score} else {-score};
@@ -125,21 +102,44 @@ fn halfmove_scale(score: i32, pos: &Bitboard) -> i32 {
 
 pub fn evaluate_position(pos: &Bitboard, pht: &mut PHT) -> i32 {
     // positive is white-favored, negative black-favored
-    let mut score: Score = make_score(0, 0);
-    score += material_score(pos);
-    score += mobility_and_king_danger(pos);
-    score += pawn_value(pos, pht);
-    score += double_bishop_bonus(pos);
-    score += bishop_color_value(pos);
-    score += rook_on_seventh_value(pos);
-    score += rook_on_open_value(pos);
-    score += nonpawn_psqt_value(pos);
-    score += outpost_value(pos);
-    score += king_pawns_value(pos);
-    score += if pos.side_to_move == Color::White {TEMPO_BONUS} else {-TEMPO_BONUS};
+    let p = eval_params();
+    let score = pawn_value(pos, pht, p) + unhashed_value(pos, p);
     return halfmove_scale(taper_score(score, pos.get_phase()), pos);
 }
 
+// the same evaluation for an arbitrary set of weights, bypassing the
+// pawn hash table since its entries were computed with the global ones
+pub fn evaluate_with_params(pos: &Bitboard, p: &EvalParams) -> i32 {
+    return halfmove_scale(taper_score(evaluate_score(pos, p), pos.get_phase()), pos);
+}
+
+// the untapered score. every term is a weight times some count, so this
+// is linear in the weights, which the tuner relies on
+pub fn evaluate_score(pos: &Bitboard, p: &EvalParams) -> Score {
+    return pawn_hash_value(pos, p) + unhashed_value(pos, p);
+}
+
+fn unhashed_value(pos: &Bitboard, p: &EvalParams) -> Score {
+    let mut score: Score = make_score(0, 0);
+    score += material_score(pos, p);
+    score += mobility_and_king_danger(pos, p);
+    score += double_bishop_bonus(pos, p);
+    score += bishop_color_value(pos, p);
+    score += rook_on_seventh_value(pos, p);
+    score += rook_on_open_value(pos, p);
+    score += nonpawn_psqt_value(pos, p);
+    score += outpost_value(pos, p);
+    score += king_pawns_value(pos, p);
+    score += side_difference(tempo_sides(pos, p));
+    return score;
+}
+
+fn tempo_sides(pos: &Bitboard, p: &EvalParams) -> SideScores {
+    let mut tempo = [0, 0];
+    tempo[pos.side_to_move as usize] = p.tempo_bonus;
+    return tempo;
+}
+
 fn pawnless_endgame_drawish(pos: &Bitboard) -> bool {
     // some endgames are known to be drawish
     // for these, until I can produce more intelligent
@@ -265,6 +265,86 @@ pub fn print_eval(board: &mut Bitboard) {
     }
     eprintln!("Classical evaluation (White View): {:+.2}", base_score);
 }
+
+fn centipawns(value: i32) -> Json {
+    return Json::from(value as f64 / 10.0);
+}
+
+fn score_json(s: Score, phase: i32) -> Json {
+    return Json::object()
+        .with("mg", centipawns(mg_score(s)))
+        .with("eg", centipawns(eg_score(s)))
+        .with("tapered", centipawns(taper_score(s, phase)));
+}
+
+fn term_json(sides: SideScores, phase: i32) -> Json {
+    return Json::object()
+        .with("white", score_json(sides[Color::White as usize], phase))
+        .with("black", score_json(sides[Color::Black as usize], phase))
+        .with("total", score_json(side_difference(sides), phase));
+}
+
+// the whole evaluation broken down for tools, in centipawns from white's
+// point of view.  every handcrafted term is given for each side, and the
+// nnue part has what each piece adds to the network's eval.  the phase runs
+// from 0 with all the pieces on to 256 with none
+pub fn eval_trace(pos: &mut Bitboard) -> Json {
+    let p = eval_params();
+    let phase = pos.get_phase();
+    let activity = piece_activity(pos, p);
+    let pawns = pawn_structure(pos, p);
+    let terms: [(&str, SideScores); 15] = [
+        ("material", material_sides(pos, p)),
+        ("psqt", psqt_sides(pos, p)),
+        ("mobility", activity.mobility),
+        ("king_danger", activity.king_danger),
+        ("king_pawns", king_pawns_sides(pos, p)),
+        ("passed_pawns", pawns.passed),
+        ("pawn_structure", pawns.structure),
+        ("center_pawns", center_pawns_sides(pos, p)),
+        ("outposts", outpost_sides(pos, p)),
+        ("bishop_pair", double_bishop_sides(pos, p)),
+        ("bishop_long_diagonal", activity.long_diagonals),
+        ("bishop_color", bishop_color_sides(pos, p)),
+        ("rook_on_seventh", rook_on_seventh_sides(pos, p)),
+        ("rook_on_open_file", rook_on_open_sides(pos, p)),
+        ("tempo", tempo_sides(pos, p))
+    ];
+    let mut hce_terms = Json::object();
+    let mut total: Score = 0;
+    for (name, sides) in terms.iter() {
+        hce_terms = hce_terms.with(name, term_json(*sides, phase));
+        total += side_difference(*sides);
+    }
+    let hce = Json::object()
+        .with("terms", hce_terms)
+        .with("total", score_json(total, phase))
+        .with("halfmove_clock", Json::from(pos.halfmove as i32))
+        .with("halfmove_scale", Json::from((100 - pos.halfmove as i32) as f64 / 100.0))
+        .with("score", centipawns(halfmove_scale(taper_score(total, phase), pos)));
+
+    let mut pieces = Vec::new();
+    for (idx, contribution) in pos.net.piece_contributions(pos) {
+        let color = if pos.composite[Color::White as usize] & idx_to_bb(idx) != 0 {Color::White} else {Color::Black};
+        let mut piece = pos.piece_at_square(idx, color);
+        if color == Color::White { piece = piece.to_ascii_uppercase(); }
+        pieces.push(Json::object()
+            .with("square", Json::from(idx_to_str(idx).as_str()))
+            .with("piece", Json::String((piece as char).to_string()))
+            .with("contribution", centipawns(contribution)));
+    }
+    let nnue = Json::object()
+        .with("network", Json::from(pos.net.shape().describe().as_str()))
+        .with("score", centipawns(pos.nnue_eval()))
+        .with("pieces", Json::Array(pieces));
+
+    return Json::object()
+        .with("fen", Json::from(pos.fen().as_str()))
+        .with("side_to_move", Json::from(if pos.side_to_move == Color::White {"white"} else {"black"}))
+        .with("phase", Json::from(phase))
+        .with("hce", hce)
+        .with("nnue", nnue);
+}
 // fn king_distance(k1: i8, k2: i8) -> i32 {
 //     let (k1f, k1r) = idx_to_coord(k1);
 //     let (k2f, k2r) = idx_to_coord(k2);
@@ -299,51 +379,45 @@ pub fn print_eval(board: &mut Bitboard) {
 // }
 
 pub fn material(pos: &Bitboard) -> i32 {
+    let p = eval_params();
     let mut score: Score = make_score(0, 0);
     let white = Color::White as usize;
     let black = Color::Black as usize;
 
-    score += QUEEN_VALUE * pos.queen[white].count_ones() as Score;
-    score += QUEEN_VALUE * pos.queen[black].count_ones() as Score;
+    score += p.queen_value * pos.queen[white].count_ones() as Score;
+    score += p.queen_value * pos.queen[black].count_ones() as Score;
 
-    score += ROOK_VALUE * pos.rook[white].count_ones() as Score;
-    score += ROOK_VALUE * pos.rook[black].count_ones() as Score;
+    score += p.rook_value * pos.rook[white].count_ones() as Score;
+    score += p.rook_value * pos.rook[black].count_ones() as Score;
 
-    score += BISHOP_VALUE * pos.bishop[white].count_ones() as Score;
-    score += BISHOP_VALUE * pos.bishop[black].count_ones() as Score;
+    score += p.bishop_value * pos.bishop[white].count_ones() as Score;
+    score += p.bishop_value * pos.bishop[black].count_ones() as Score;
 
-    score += KNIGHT_VALUE * pos.knight[white].count_ones() as Score;
-    score += KNIGHT_VALUE * pos.knight[black].count_ones() as Score;
+    score += p.knight_value * pos.knight[white].count_ones() as Score;
+    score += p.knight_value * pos.knight[black].count_ones() as Score;
 
-    score += PAWN_VALUE * pos.pawn[white].count_ones() as Score;
-    score += PAWN_VALUE * pos.pawn[black].count_ones() as Score;
+    score += p.pawn_value * pos.pawn[white].count_ones() as Score;
+    score += p.pawn_value * pos.pawn[black].count_ones() as Score;
 
     return mg_score(score);
 }
 
 
-pub fn material_score(pos: &Bitboard) -> Score {
-    let mut score: Score = make_score(0, 0);
+pub fn material_score(pos: &Bitboard, p: &EvalParams) -> Score {
     // if pawnless_endgame_drawish(pos) { return score; }
-    let white = Color::White as usize;
-    let black = Color::Black as usize;
-
-    score += QUEEN_VALUE * pos.queen[white].count_ones() as Score;
-    score -= QUEEN_VALUE * pos.queen[black].count_ones() as Score;
-
-    score += ROOK_VALUE * pos.rook[white].count_ones() as Score;
-    score -= ROOK_VALUE * pos.rook[black].count_ones() as Score;
-
-    score += BISHOP_VALUE * pos.bishop[white].count_ones() as Score;
-    score -= BISHOP_VALUE * pos.bishop[black].count_ones() as Score;
-
-    score += KNIGHT_VALUE * pos.knight[white].count_ones() as Score;
-    score -= KNIGHT_VALUE * pos.knight[black].count_ones() as Score;
-
-    score += PAWN_VALUE * pos.pawn[white].count_ones() as Score;
-    score -= PAWN_VALUE * pos.pawn[black].count_ones() as Score;
+    return side_difference(material_sides(pos, p));
+}
 
-    return score;
+fn material_sides(pos: &Bitboard, p: &EvalParams) -> SideScores {
+    let mut material: SideScores = [0, 0];
+    for side in [Color::White as usize, Color::Black as usize] {
+        material[side] += p.queen_value * pos.queen[side].count_ones() as Score;
+        material[side] += p.rook_value * pos.rook[side].count_ones() as Score;
+        material[side] += p.bishop_value * pos.bishop[side].count_ones() as Score;
+        material[side] += p.knight_value * pos.knight[side].count_ones() as Score;
+        material[side] += p.pawn_value * pos.pawn[side].count_ones() as Score;
+    }
+    return material;
 }
 
 pub fn simple_material_score(pos: &Bitboard) -> Score {
@@ -377,10 +451,22 @@ fn pawn_attacks(pawn_bb: u64, side_to_move: Color) -> u64 {
     }
 }
 
-pub fn pawn_structure_value(pos: &Bitboard) -> Score {
+// passed and candidate passed pawns, and everything else about the pawns
+struct PawnStructure {
+    passed: SideScores,
+    structure: SideScores
+}
+
+pub fn pawn_structure_value(pos: &Bitboard, p: &EvalParams) -> Score {
+    let pawns = pawn_structure(pos, p);
+    return side_difference(pawns.passed) + side_difference(pawns.structure);
+}
+
+fn pawn_structure(pos: &Bitboard, p: &EvalParams) -> PawnStructure {
     let white = Color::White;
     let black = Color::Black;
-    let mut pawn_score: [Score; 2] = [0, 0];
+    let mut passed_score: SideScores = [0, 0];
+    let mut pawn_score: SideScores = [0, 0];
 
     for side in [white, black] {
         let me = side as usize;
@@ -427,7 +513,7 @@ pub fn pawn_structure_value(pos: &Bitboard) -> Score {
 
 
             // passed pawns
-            if stoppers == 0 { pawn_score[me] += PASSED_PAWN_VALUE[score_r];}
+            if stoppers == 0 { passed_score[me] += p.passed_pawn_value[score_r];}
 
             // candidate passed pawns
             // we do a brief calculation to see if we have enough supporters
@@ -435,23 +521,23 @@ pub fn pawn_structure_value(pos: &Bitboard) -> Score {
             // supporters and threateners and seeing if we come out on top.
             else if leftover_stoppers == 0 && push_support.count_ones() >= push_threats.count_ones() {
                 if support.count_ones() >= threats.count_ones() {
-                    pawn_score[me] += CANDIDATE_PASSED_PAWN_VALUE[score_r];
+                    passed_score[me] += p.candidate_passed_pawn_value[score_r];
                 }
             }
 
             // Isolated pawns
             if neighbors == 0 && threats == 0 {
-                pawn_score[me] += ISOLATED_PAWN_VALUE;
+                pawn_score[me] += p.isolated_pawn_value;
             }
 
             // doubled pawns
             if own_blockers != 0 {
-                pawn_score[me] += DOUBLED_PAWN_VALUE;
+                pawn_score[me] += p.doubled_pawn_value;
             }
 
             // backwards pawns
             if push_threats != 0 && backup == 0 && push_support == 0 {
-                pawn_score[me] += BACKWARDS_PAWN_VALUE;
+                pawn_score[me] += p.backwards_pawn_value;
             }
 
             // connected pawns
@@ -461,8 +547,8 @@ pub fn pawn_structure_value(pos: &Bitboard) -> Score {
             let opposed = if enemy_blockers != 0 {1} else {0};
 
             if supported_count != 0 || phalanx != 0 {
-                pawn_score[me] += ADVANCED_PAWN_VALUE[score_r] * (2 + phalanx - opposed) as i64;
-                pawn_score[me] += SUPPORTED_PAWN_BONUS * supported_count as i64;
+                pawn_score[me] += p.advanced_pawn_value[score_r] * (2 + phalanx - opposed) as i64;
+                pawn_score[me] += p.supported_pawn_bonus * supported_count as i64;
             }
 
             // pop off this pawn
@@ -470,7 +556,10 @@ pub fn pawn_structure_value(pos: &Bitboard) -> Score {
         }
     }
 
-    return pawn_score[1] - pawn_score[0];
+    return PawnStructure {
+        passed: passed_score,
+        structure: pawn_score
+    };
 }
 
 fn is_outpost(pos: &Bitboard, idx: i8, side: Color) -> bool {
@@ -488,7 +577,11 @@ fn is_outpost(pos: &Bitboard, idx: i8, side: Color) -> bool {
     return (atk_span & enemy_pawns) == 0;
 }
 
-fn outpost_value(pos: &Bitboard) -> Score {
+fn outpost_value(pos: &Bitboard, p: &EvalParams) -> Score {
+    return side_difference(outpost_sides(pos, p));
+}
+
+fn outpost_sides(pos: &Bitboard, p: &EvalParams) -> SideScores {
     let mut outpost = [0, 0];
     for side in [Color::White, Color::Black] {
         let me = side as usize;
@@ -500,7 +593,7 @@ fn outpost_value(pos: &Bitboard) -> Score {
             let idx = bishop_bb.trailing_zeros() as i8;
             if is_outpost(pos, idx, side) {
                 // println!("bishop outpost at {}", idx_to_str(idx));
-                outpost[me] += BISHOP_OUTPOST_VALUE;
+                outpost[me] += p.bishop_outpost_value;
             }
 
             // pop bishop
@@ -511,17 +604,21 @@ fn outpost_value(pos: &Bitboard) -> Score {
             let idx = knight_bb.trailing_zeros() as i8;
             if is_outpost(pos, idx, side) {
                 // println!("knight outpost at {}", idx_to_str(idx));
-                outpost[me] += KNIGHT_OUTPOST_VALUE;
+                outpost[me] += p.knight_outpost_value;
             }
 
             // pop knight
             knight_bb &= knight_bb - 1;
         }
     }
-    return outpost[1] - outpost[0];
+    return outpost;
+}
+
+fn king_pawns_value(pos: &Bitboard, p: &EvalParams) -> Score {
+    return side_difference(king_pawns_sides(pos, p));
 }
 
-fn king_pawns_value(pos: &Bitboard) -> Score {
+fn king_pawns_sides(pos: &Bitboard, p: &EvalParams) -> SideScores {
     // credit to SF Evaluation Guide
     // and Ethereal Source
     let mut pawn_proximity: [Score; 2] = [0, 0];
@@ -554,7 +651,7 @@ fn king_pawns_value(pos: &Bitboard) -> Score {
                 }
             }
 
-            pawn_proximity[me] += PAWN_PROXIMITY_VALUE[pawn_distance];
+            pawn_proximity[me] += p.pawn_proximity_value[pawn_distance];
         }
 
         // king shelter and storm
@@ -589,22 +686,41 @@ fn king_pawns_value(pos: &Bitboard) -> Score {
 
             // going a bit more basic than Ethereal for now
             // just consider whether this is the king file and the distance
-            pawn_shelter[me] += PAWN_SHELTER_VALUE[(file == king_file) as usize][friendly_distance as usize];
+            pawn_shelter[me] += p.pawn_shelter_value[(file == king_file) as usize][friendly_distance as usize];
 
             // check if we have a pawn in the way before updating storm score
             let blocked = friendly_distance == enemy_distance - 1;
             // println!("blocked {}", blocked);
-            pawn_storm[me] += PAWN_STORM_VALUE[blocked as usize][enemy_distance as usize];
+            pawn_storm[me] += p.pawn_storm_value[blocked as usize][enemy_distance as usize];
         }
     }
 
-    return (pawn_shelter[1] + pawn_storm[1]) - (pawn_shelter[0] + pawn_storm[0]);
+    let white = Color::White as usize;
+    let black = Color::Black as usize;
+    let mut king_pawns = [0, 0];
+    king_pawns[white] = pawn_shelter[white] + pawn_storm[white];
+    king_pawns[black] = pawn_shelter[black] + pawn_storm[black];
+    return king_pawns;
+}
+
+// king danger is credited to the attacking side
+struct PieceActivity {
+    mobility: SideScores,
+    long_diagonals: SideScores,
+    king_danger: SideScores
 }
 
-fn mobility_and_king_danger(pos: &Bitboard) -> Score {
-    let mut mobility: Score = make_score(0, 0);
+fn mobility_and_king_danger(pos: &Bitboard, p: &EvalParams) -> Score {
+    let activity = piece_activity(pos, p);
+    return side_difference(activity.mobility) + side_difference(activity.long_diagonals) + side_difference(activity.king_danger);
+}
+
+fn piece_activity(pos: &Bitboard, p: &EvalParams) -> PieceActivity {
+    let mut mobility: SideScores = [0, 0];
     let mut piece_bonus: [Score; 2] = [0, 0];
     let mut king_danger: [Score; 2] = [0, 0];
+    // kept separately from the score so the eval stays linear in its weights
+    let mut king_attacked: [bool; 2] = [false, false];
     let white = Color::White as usize;
     let black = Color::Black as usize;
     let occ = pos.composite[white] | pos.composite[black];
@@ -621,7 +737,6 @@ fn mobility_and_king_danger(pos: &Bitboard) -> Score {
 
     for side in [white, black] {
         let other_side = if side == white {black} else {white};
-        let multiplier = if side == white {1} else {-1};
 
         let king_bb = pos.king[other_side];
         let mut attackers = 0;
@@ -648,7 +763,7 @@ fn mobility_and_king_danger(pos: &Bitboard) -> Score {
                 attackers += 1;
                 queen_attacks += attacks.count_ones();
             }
-            mobility += multiplier * QUEEN_MOBILITY[moves];
+            mobility[side] += p.queen_mobility[moves];
             board &= board - 1;
         }
 
@@ -666,7 +781,7 @@ fn mobility_and_king_danger(pos: &Bitboard) -> Score {
                 attackers += 1;
                 rook_attacks += attacks.count_ones();
             }
-            mobility += multiplier * ROOK_MOBILITY[moves];
+            mobility[side] += p.rook_mobility[moves];
             board &= board - 1;
         }
 
@@ -675,8 +790,8 @@ fn mobility_and_king_danger(pos: &Bitboard) -> Score {
             let start_idx = board.trailing_zeros() as i8;
             let move_board = bishop_moves_board(start_idx, occ & !(pos.queen[side] | pos.bishop[side]));
 
-            if move_board & center_diagonal_1 == center_diagonal_1 { piece_bonus[side] += BISHOP_LONG_DIAGONAL_VALUE; }
-            else if move_board & center_diagonal_2 == center_diagonal_2 { piece_bonus[side] += BISHOP_LONG_DIAGONAL_VALUE; }
+            if move_board & center_diagonal_1 == center_diagonal_1 { piece_bonus[side] += p.bishop_long_diagonal_value; }
+            else if move_board & center_diagonal_2 == center_diagonal_2 { piece_bonus[side] += p.bishop_long_diagonal_value; }
 
             let moves = move_board.count_ones() as usize;
             attacked_by_two[side] |= attacked[side] & move_board;
@@ -688,7 +803,7 @@ fn mobility_and_king_danger(pos: &Bitboard) -> Score {
                 attackers += 1;
                 bishop_attacks += attacks.count_ones();
             }
-            mobility += multiplier * BISHOP_MOBILITY[moves];
+            mobility[side] += p.bishop_mobility[moves];
             board &= board - 1;
         }
 
@@ -706,19 +821,20 @@ fn mobility_and_king_danger(pos: &Bitboard) -> Score {
                 attackers += 1;
                 knight_attacks += attacks.count_ones();
             }
-            mobility += multiplier * KNIGHT_MOBILITY[moves];
+            mobility[side] += p.knight_mobility[moves];
             board &= board - 1;
         }
 
         if attackers > 5 { attackers = 5; }
         // println!("num attackers side {}: {}", side, attackers);
         if attackers >= if pos.queen[side] != 0 {1} else {2} {
-            attack_value += QUEEN_KING_DANGER[attackers] * queen_attacks as i64;
-            attack_value += ROOK_KING_DANGER[attackers] * rook_attacks as i64;
-            attack_value += BISHOP_KING_DANGER[attackers] * bishop_attacks as i64;
-            attack_value += KNIGHT_KING_DANGER[attackers] * knight_attacks as i64;
+            attack_value += p.queen_king_danger[attackers] * queen_attacks as i64;
+            attack_value += p.rook_king_danger[attackers] * rook_attacks as i64;
+            attack_value += p.bishop_king_danger[attackers] * bishop_attacks as i64;
+            attack_value += p.knight_king_danger[attackers] * knight_attacks as i64;
 
             king_danger[side] = attack_value;
+            king_attacked[side] = true;
         }
 
         // if piece_bonus[side] != 0 {println!("LONG DIAGONAL");}
@@ -746,87 +862,94 @@ fn mobility_and_king_danger(pos: &Bitboard) -> Score {
         let bishop_checks = attacked_by_bishops[me] & safe_squares & king_bishop_threats;
         let knight_checks = attacked_by_knights[me] & safe_squares & king_knight_threats;
 
-        if king_danger[side] != 0 {
+        if king_attacked[side] {
             // println!("safe queen checks side {}: {}", side, queen_checks.count_ones());
             // println!("safe rook checks side {}: {}", side, rook_checks.count_ones());
             // println!("safe bishop checks side {}: {}", side, bishop_checks.count_ones());
             // println!("safe knight checks side {}: {}", side, knight_checks.count_ones());
             // println!("enemy weak squares side {}: {}", side, weak_squares.count_ones());
 
-            king_danger[side] += queen_checks.count_ones() as i64 * QUEEN_CHECK_VALUE;
-            king_danger[side] += rook_checks.count_ones() as i64 * ROOK_CHECK_VALUE;
-            king_danger[side] += bishop_checks.count_ones() as i64 * BISHOP_CHECK_VALUE;
-            king_danger[side] += knight_checks.count_ones() as i64 * KNIGHT_CHECK_VALUE;
+            king_danger[side] += queen_checks.count_ones() as i64 * p.queen_check_value;
+            king_danger[side] += rook_checks.count_ones() as i64 * p.rook_check_value;
+            king_danger[side] += bishop_checks.count_ones() as i64 * p.bishop_check_value;
+            king_danger[side] += knight_checks.count_ones() as i64 * p.knight_check_value;
 
-            king_danger[side] += WEAK_SQUARE_VALUE * weak_squares.count_ones() as i64;
+            king_danger[side] += p.weak_square_value * weak_squares.count_ones() as i64;
             if pos.queen[side] == 0 {
-                king_danger[side] += NO_QUEEN_ATTACK_VALUE;
+                king_danger[side] += p.no_queen_attack_value;
             }
             // println!("king danger! {}", mg_score(king_danger[side]));
         }
     }
 
-    let score = mobility + (piece_bonus[white] - piece_bonus[black]) + (king_danger[white] - king_danger[black]);
-    return score;
+    return PieceActivity {
+        mobility: mobility,
+        long_diagonals: piece_bonus,
+        king_danger: king_danger
+    };
 }
 
-fn double_bishop_bonus(pos: &Bitboard) -> Score {
-    let mut score: Score = make_score(0, 0);
-    let white = Color::White as usize;
-    let black = Color::Black as usize;
-    if pos.bishop[white].count_ones() >= 2 {
-        score += DOUBLE_BISHOP_BONUS;
-    }
-    if pos.bishop[black].count_ones() >= 2 {
-        score -= DOUBLE_BISHOP_BONUS;
-    }
+fn double_bishop_bonus(pos: &Bitboard, p: &EvalParams) -> Score {
+    return side_difference(double_bishop_sides(pos, p));
+}
 
-    return score;
+fn double_bishop_sides(pos: &Bitboard, p: &EvalParams) -> SideScores {
+    let mut bonus = [0, 0];
+    for side in [Color::White as usize, Color::Black as usize] {
+        if pos.bishop[side].count_ones() >= 2 {
+            bonus[side] += p.double_bishop_bonus;
+        }
+    }
+    return bonus;
 }
 
 pub fn print_value(pos: &Bitboard) {
+    let p = eval_params();
     eprintln!("HCE Score Breakdown:");
-    eprintln!("material: {}", taper_score(material_score(pos), pos.get_phase()));
-    eprintln!("mobility and king_danger: {}", taper_score(mobility_and_king_danger(pos), pos.get_phase()));
-    eprintln!("passed_pawns: {}", taper_score(passed_pawns_value(pos), pos.get_phase()));
-    eprintln!("center_pawns: {}", taper_score(center_pawns_value(pos), pos.get_phase()));
-    eprintln!("isolated_pawns: {}", taper_score(isolated_pawns_value(pos), pos.get_phase()));
-    eprintln!("doubled_pawns: {}", taper_score(doubled_pawns_value(pos), pos.get_phase()));
-    eprintln!("backwards_pawns: {}", taper_score(backwards_pawns_value(pos), pos.get_phase()));
-    eprintln!("connected_pawns: {}", taper_score(connected_pawns_value(pos), pos.get_phase()));
-    eprintln!("space: {}", taper_score(space_control_value(pos), pos.get_phase()));
-    eprintln!("rook on 7th: {}", taper_score(rook_on_seventh_value(pos), pos.get_phase()));
-    eprintln!("rook on open: {}", taper_score(rook_on_open_value(pos), pos.get_phase()));
-    eprintln!("double_bishop_bonus: {}", taper_score(double_bishop_bonus(pos), pos.get_phase()));
-    eprintln!("bishop_color: {}", taper_score(bishop_color_value(pos), pos.get_phase()));
-    eprintln!("psqt: {}", taper_score(nonpawn_psqt_value(pos) + pawn_psqt_value(pos), pos.get_phase()));
+    eprintln!("material: {}", taper_score(material_score(pos, p), pos.get_phase()));
+    eprintln!("mobility and king_danger: {}", taper_score(mobility_and_king_danger(pos, p), pos.get_phase()));
+    eprintln!("passed_pawns: {}", taper_score(passed_pawns_value(pos, p), pos.get_phase()));
+    eprintln!("center_pawns: {}", taper_score(center_pawns_value(pos, p), pos.get_phase()));
+    eprintln!("isolated_pawns: {}", taper_score(isolated_pawns_value(pos, p), pos.get_phase()));
+    eprintln!("doubled_pawns: {}", taper_score(doubled_pawns_value(pos, p), pos.get_phase()));
+    eprintln!("backwards_pawns: {}", taper_score(backwards_pawns_value(pos, p), pos.get_phase()));
+    eprintln!("connected_pawns: {}", taper_score(connected_pawns_value(pos, p), pos.get_phase()));
+    eprintln!("space: {}", taper_score(space_control_value(pos, p), pos.get_phase()));
+    eprintln!("rook on 7th: {}", taper_score(rook_on_seventh_value(pos, p), pos.get_phase()));
+    eprintln!("rook on open: {}", taper_score(rook_on_open_value(pos, p), pos.get_phase()));
+    eprintln!("double_bishop_bonus: {}", taper_score(double_bishop_bonus(pos, p), pos.get_phase()));
+    eprintln!("bishop_color: {}", taper_score(bishop_color_value(pos, p), pos.get_phase()));
+    eprintln!("psqt: {}", taper_score(nonpawn_psqt_value(pos, p) + pawn_psqt_value(pos, p), pos.get_phase()));
 }
 
-fn pawn_value(pos: &Bitboard, pht: &mut PHT) -> Score {
-    // let pht;
-    // unsafe {
-    //     pht = &mut PHT;
-    // }
-    let mut val: Score = 0;
+fn pawn_value(pos: &Bitboard, pht: &mut PHT, p: &EvalParams) -> Score {
+    let val: Score;
     let pht_entry = pht.get(pos.pawn_hash);
     if pht_entry.valid {
         val = pht_entry.value;
     } else {
-        // val += passed_pawns_value(pos);
-        val += center_pawns_value(pos);
-        // val += isolated_pawns_value(pos);
-        // val += doubled_pawns_value(pos);
-        // val += backwards_pawns_value(pos);
-        // val += connected_pawns_value(pos);
-        // val += space_control_value(pos);
-        val += pawn_structure_value(pos);
-        val += pawn_psqt_value(pos);
+        val = pawn_hash_value(pos, p);
         pht.set(pos.pawn_hash, val);
     }
     return val;
 }
 
-fn passed_pawns_value(pos: &Bitboard) -> Score {
+// everything that only depends on the pawns, so it can live in the pawn hash table
+fn pawn_hash_value(pos: &Bitboard, p: &EvalParams) -> Score {
+    let mut val: Score = 0;
+    // val += passed_pawns_value(pos, p);
+    val += center_pawns_value(pos, p);
+    // val += isolated_pawns_value(pos, p);
+    // val += doubled_pawns_value(pos, p);
+    // val += backwards_pawns_value(pos, p);
+    // val += connected_pawns_value(pos, p);
+    // val += space_control_value(pos, p);
+    val += pawn_structure_value(pos, p);
+    val += pawn_psqt_value(pos, p);
+    return val;
+}
+
+fn passed_pawns_value(pos: &Bitboard, p: &EvalParams) -> Score {
     let mut passed_pawns: [Score; 2] = [0, 0];
     let white = Color::White as usize;
     let black = Color::Black as usize;
@@ -864,7 +987,7 @@ fn passed_pawns_value(pos: &Bitboard) -> Score {
                 enemy_mask &= rank_mask;
 
                 if enemy_mask & pos.pawn[if side == white {black} else {white}] == 0 {
-                    passed_pawns[side] += PASSED_PAWN_VALUE[pp_rank as usize];
+                    passed_pawns[side] += p.passed_pawn_value[pp_rank as usize];
                 }
             }
         }
@@ -872,13 +995,19 @@ fn passed_pawns_value(pos: &Bitboard) -> Score {
     return passed_pawns[white] - passed_pawns[black];
 }
 
-fn center_pawns_value(pos: &Bitboard) -> Score {
-    let white_center = (CENTER_MASK & pos.pawn[Color::White as usize]).count_ones() as i32;
-    let black_center = (CENTER_MASK & pos.pawn[Color::Black as usize]).count_ones() as i32;
-    return CENTER_PAWN_VALUE * (white_center - black_center) as i64;
+fn center_pawns_value(pos: &Bitboard, p: &EvalParams) -> Score {
+    return side_difference(center_pawns_sides(pos, p));
 }
 
-fn isolated_pawns_value(pos: &Bitboard) -> Score {
+fn center_pawns_sides(pos: &Bitboard, p: &EvalParams) -> SideScores {
+    let mut center = [0, 0];
+    for side in [Color::White as usize, Color::Black as usize] {
+        center[side] = p.center_pawn_value * (CENTER_MASK & pos.pawn[side]).count_ones() as i64;
+    }
+    return center;
+}
+
+fn isolated_pawns_value(pos: &Bitboard, p: &EvalParams) -> Score {
     let mut isolated_pawns: [i32; 2] = [0, 0];
     let white = Color::White as usize;
     let black = Color::Black as usize;
@@ -901,10 +1030,10 @@ fn isolated_pawns_value(pos: &Bitboard) -> Score {
             }
         }
     }
-    return ISOLATED_PAWN_VALUE * (isolated_pawns[white] - isolated_pawns[black]) as i64;
+    return p.isolated_pawn_value * (isolated_pawns[white] - isolated_pawns[black]) as i64;
 }
 
-fn doubled_pawns_value(pos: &Bitboard) -> Score {
+fn doubled_pawns_value(pos: &Bitboard, p: &EvalParams) -> Score {
     let mut doubled_pawns: [i32; 2] = [0, 0];
     let white = Color::White as usize;
     let black = Color::Black as usize;
@@ -920,10 +1049,10 @@ fn doubled_pawns_value(pos: &Bitboard) -> Score {
         }
     }
 
-    return DOUBLED_PAWN_VALUE * (doubled_pawns[white] - doubled_pawns[black]) as i64;
+    return p.doubled_pawn_value * (doubled_pawns[white] - doubled_pawns[black]) as i64;
 }
 
-fn backwards_pawns_value(pos: &Bitboard) -> Score {
+fn backwards_pawns_value(pos: &Bitboard, p: &EvalParams) -> Score {
     let white = Color::White as usize;
     let black = Color::Black as usize;
     let white_atks = pawn_attacks(pos.pawn[white], Color::White);
@@ -940,10 +1069,10 @@ fn backwards_pawns_value(pos: &Bitboard) -> Score {
     let black_backwards_pawns = (pos.pawn[black] << 8) & white_atks & !black_atk_proj;
     let backwards_pawn_balance = white_backwards_pawns.count_ones() as i32 - black_backwards_pawns.count_ones() as i32;
 
-    return BACKWARDS_PAWN_VALUE * backwards_pawn_balance as i64;
+    return p.backwards_pawn_value * backwards_pawn_balance as i64;
 }
 
-fn connected_pawns_value(pos: &Bitboard) -> Score {
+fn connected_pawns_value(pos: &Bitboard, p: &EvalParams) -> Score {
     let mut connected_pawns: [Score; 2] = [0, 0];
     let white = Color::White as usize;
     let black = Color::Black as usize;
@@ -961,7 +1090,7 @@ fn connected_pawns_value(pos: &Bitboard) -> Score {
             // aka is it protected by another pawn
             let supported = idx_to_bb(idx) & my_atks != 0;
             if supported {
-                connected_pawns[me] += SUPPORTED_PAWN_BONUS;
+                connected_pawns[me] += p.supported_pawn_bonus;
             }
 
             // part of a phalanx?
@@ -970,12 +1099,12 @@ fn connected_pawns_value(pos: &Bitboard) -> Score {
             if phalanx || supported {
                 let rank = if color == Color::White { idx / 8 } else { 7 - (idx / 8) } as usize;
                 let file = (idx % 8) as usize;
-                connected_pawns[me] += ADVANCED_PAWN_VALUE[rank];
+                connected_pawns[me] += p.advanced_pawn_value[rank];
                 if phalanx {
-                    connected_pawns[me] += ADVANCED_PAWN_VALUE[rank];
+                    connected_pawns[me] += p.advanced_pawn_value[rank];
                 }
                 if FILE_MASKS[file] & pos.pawn[them] == 0 {
-                    connected_pawns[me] += ADVANCED_PAWN_VALUE[rank];
+                    connected_pawns[me] += p.advanced_pawn_value[rank];
                 }
             }
         }
@@ -984,7 +1113,7 @@ fn connected_pawns_value(pos: &Bitboard) -> Score {
     return connected_pawns[white] - connected_pawns[black];
 }
 
-fn space_control_value(pos: &Bitboard) -> Score {
+fn space_control_value(pos: &Bitboard, p: &EvalParams) -> Score {
     let mut space_control: [i32; 2] = [0, 0];
     let white = Color::White as usize;
     let black = Color::Black as usize;
@@ -1007,10 +1136,14 @@ fn space_control_value(pos: &Bitboard) -> Score {
     space_control[white] = (base_white_space.count_ones() + bonus_white_space.count_ones()) as i32;
     space_control[black] = (base_black_space.count_ones() + bonus_black_space.count_ones()) as i32;
 
-    return SPACE_VALUE * (space_control[white] - space_control[black]) as i64;
+    return p.space_value * (space_control[white] - space_control[black]) as i64;
+}
+
+fn rook_on_seventh_value(pos: &Bitboard, p: &EvalParams) -> Score {
+    return side_difference(rook_on_seventh_sides(pos, p));
 }
 
-fn rook_on_seventh_value(pos: &Bitboard) -> Score {
+fn rook_on_seventh_sides(pos: &Bitboard, p: &EvalParams) -> SideScores {
     let white = Color::White as usize;
     let black = Color::Black as usize;
     let white_seventh_rooks = (pos.rook[white] & RANK_MASKS[6]).count_ones() as i32;
@@ -1019,12 +1152,17 @@ fn rook_on_seventh_value(pos: &Bitboard) -> Score {
     let white_condition = (pos.king[black] & RANK_MASKS[7]) != 0 || (pos.pawn[black] & RANK_MASKS[6]) != 0;
     let black_condition = (pos.king[white] & RANK_MASKS[7]) != 0 || (pos.pawn[white] & RANK_MASKS[6]) != 0;
 
-    return ROOK_ON_SEVENTH
-        * (if white_condition {white_seventh_rooks} else {0}
-         - if black_condition {black_seventh_rooks} else {0}) as i64;
+    let mut rooks = [0, 0];
+    rooks[white] = p.rook_on_seventh * (if white_condition {white_seventh_rooks} else {0}) as i64;
+    rooks[black] = p.rook_on_seventh * (if black_condition {black_seventh_rooks} else {0}) as i64;
+    return rooks;
+}
+
+fn rook_on_open_value(pos: &Bitboard, p: &EvalParams) -> Score {
+    return side_difference(rook_on_open_sides(pos, p));
 }
 
-fn rook_on_open_value(pos: &Bitboard) -> Score {
+fn rook_on_open_sides(pos: &Bitboard, p: &EvalParams) -> SideScores {
     let white = Color::White as usize;
     let black = Color::Black as usize;
     let mut openish_file_rooks: [i32; 2] = [0, 0];
@@ -1048,10 +1186,17 @@ fn rook_on_open_value(pos: &Bitboard) -> Score {
         }
     }
 
-    return ROOK_ON_OPEN * (openish_file_rooks[white] - openish_file_rooks[black]) as i64;
+    let mut rooks = [0, 0];
+    rooks[white] = p.rook_on_open * openish_file_rooks[white] as i64;
+    rooks[black] = p.rook_on_open * openish_file_rooks[black] as i64;
+    return rooks;
+}
+
+pub fn bishop_color_value(pos: &Bitboard, p: &EvalParams) -> Score {
+    return side_difference(bishop_color_sides(pos, p));
 }
 
-pub fn bishop_color_value(pos: &Bitboard) -> Score {
+fn bishop_color_sides(pos: &Bitboard, p: &EvalParams) -> SideScores {
     let mut bishop_color: [i32; 2] = [0, 0];
     let white = Color::White as usize;
     let black = Color::Black as usize;
@@ -1070,5 +1215,8 @@ pub fn bishop_color_value(pos: &Bitboard) -> Score {
         bishop_color[side] = bishops_on_dark * pawns_on_dark + bishops_on_light * pawns_on_light;
     }
 
-    return BISHOP_COLOR * (bishop_color[white] - bishop_color[black]) as i64;
+    let mut bishops = [0, 0];
+    bishops[white] = p.bishop_color * bishop_color[white] as i64;
+    bishops[black] = p.bishop_color * bishop_color[black] as i64;
+    return bishops;
 }
@@ -0,0 +1,371 @@
+use crate::defaultparams::*;
+use crate::eval::*;
+use crate::json::*;
+
+// every weight of the hand-crafted evaluation lives here, once.
+// evaluate_position reads them through eval_params() and the tuner
+// works on its own EvalParams copy through the flat vector view below,
+// so the two can't drift apart any more.
+
+// a parameter is a single score, a table of scores, or a table of tables.
+// the tuner only cares about the scores themselves, in order.
+pub trait ParamScores {
+    fn scores(&self) -> &[Score];
+    fn scores_mut(&mut self) -> &mut [Score];
+    // (rows, columns) for printing, rows is 0 for a single score
+    fn shape(&self) -> (usize, usize);
+}
+
+impl ParamScores for Score {
+    fn scores(&self) -> &[Score] { return std::slice::from_ref(self); }
+    fn scores_mut(&mut self) -> &mut [Score] { return std::slice::from_mut(self); }
+    fn shape(&self) -> (usize, usize) { return (0, 1); }
+}
+
+impl<const N: usize> ParamScores for [Score; N] {
+    fn scores(&self) -> &[Score] { return self; }
+    fn scores_mut(&mut self) -> &mut [Score] { return self; }
+    fn shape(&self) -> (usize, usize) { return (1, N); }
+}
+
+impl<const N: usize, const M: usize> ParamScores for [[Score; N]; M] {
+    fn scores(&self) -> &[Score] { return self.as_flattened(); }
+    fn scores_mut(&mut self) -> &mut [Score] { return self.as_flattened_mut(); }
+    fn shape(&self) -> (usize, usize) { return (M, N); }
+}
+
+pub struct ParamInfo<'a> {
+    pub name: &'static str,
+    // inclusive bounds applied to both the mg and eg values
+    pub bounds: (i32, i32),
+    pub shape: (usize, usize),
+    pub scores: &'a [Score]
+}
+
+macro_rules! eval_params {
+    ($($name:ident: $t:ty = ($min:expr, $max:expr),)*) => {
+        #[derive(Clone)]
+        pub struct EvalParams {
+            $(pub $name: $t,)*
+        }
+
+        impl EvalParams {
+            pub fn params(&self) -> Vec<ParamInfo<'_>> {
+                return vec![$(ParamInfo {
+                    name: stringify!($name),
+                    bounds: ($min, $max),
+                    shape: self.$name.shape(),
+                    scores: self.$name.scores()
+                },)*];
+            }
+
+            pub fn params_mut(&mut self) -> Vec<(&'static str, &mut [Score])> {
+                return vec![$((stringify!($name), self.$name.scores_mut()),)*];
+            }
+        }
+    }
+}
+
+eval_params! {
+    queen_value: Score = (8000, 18000),
+    rook_value: Score = (4000, 12000),
+    bishop_value: Score = (2500, 8000),
+    knight_value: Score = (2500, 8000),
+    pawn_value: Score = (800, 3000),
+
+    knight_mobility: [Score; 9] = (-1000, 1500),
+    bishop_mobility: [Score; 14] = (-1000, 1500),
+    rook_mobility: [Score; 15] = (-1000, 1500),
+    queen_mobility: [Score; 28] = (-1000, 1500),
+    knight_outpost_value: Score = (0, 1000),
+    bishop_outpost_value: Score = (0, 1000),
+    bishop_long_diagonal_value: Score = (0, 1000),
+
+    pawn_proximity_value: [Score; 8] = (-2000, 2000),
+    pawn_shelter_value: [[Score; 8]; 2] = (-2000, 2000),
+    pawn_storm_value: [[Score; 8]; 2] = (-2000, 2000),
+
+    queen_king_danger: [Score; 6] = (0, 1400),
+    rook_king_danger: [Score; 6] = (0, 1400),
+    bishop_king_danger: [Score; 6] = (0, 1400),
+    knight_king_danger: [Score; 6] = (0, 1400),
+    no_queen_attack_value: Score = (-1000, 0),
+    weak_square_value: Score = (0, 500),
+    queen_check_value: Score = (0, 1000),
+    rook_check_value: Score = (0, 1000),
+    bishop_check_value: Score = (0, 1000),
+    knight_check_value: Score = (0, 1000),
+
+    double_bishop_bonus: Score = (0, 1500),
+
+    passed_pawn_value: [Score; 8] = (0, 2000),
+    candidate_passed_pawn_value: [Score; 8] = (0, 2000),
+    center_pawn_value: Score = (0, 1000),
+    isolated_pawn_value: Score = (-1000, 0),
+    doubled_pawn_value: Score = (-1000, 0),
+    backwards_pawn_value: Score = (-1000, 0),
+    advanced_pawn_value: [Score; 8] = (0, 500),
+    supported_pawn_bonus: Score = (0, 300),
+    space_value: Score = (0, 300),
+
+    bishop_color: Score = (-500, 0),
+    tempo_bonus: Score = (0, 500),
+    rook_on_seventh: Score = (0, 500),
+    rook_on_open: Score = (0, 500),
+
+    pawn_psqt: [[Score; 4]; 8] = (-2000, 2000),
+    knight_psqt: [[Score; 4]; 8] = (-2000, 2000),
+    bishop_psqt: [[Score; 4]; 8] = (-2000, 2000),
+    rook_psqt: [[Score; 4]; 8] = (-2000, 2000),
+    queen_psqt: [[Score; 4]; 8] = (-2000, 2000),
+    king_psqt: [[Score; 4]; 8] = (-2000, 2000),
+}
+
+pub static mut EVAL_PARAMS: EvalParams = DEFAULT_EVAL_PARAMS;
+
+pub fn eval_params() -> &'static EvalParams {
+    return unsafe { &*std::ptr::addr_of!(EVAL_PARAMS) };
+}
+
+pub fn set_eval_params(params: EvalParams) {
+    unsafe { EVAL_PARAMS = params; }
+}
+
+impl EvalParams {
+    // the flat view the tuner works with: mg and eg of every score in turn
+    pub fn to_vector(&self) -> Vec<i32> {
+        let mut v = Vec::new();
+        for param in self.params() {
+            for s in param.scores {
+                v.push(mg_score(*s));
+                v.push(eg_score(*s));
+            }
+        }
+        return v;
+    }
+
+    pub fn set_from_vector(&mut self, v: &[i32]) {
+        let mut i = 0;
+        for (_, scores) in self.params_mut() {
+            for s in scores.iter_mut() {
+                *s = make_score(v[i], v[i + 1]);
+                i += 2;
+            }
+        }
+    }
+
+    pub fn from_vector(v: &[i32]) -> EvalParams {
+        let mut params = DEFAULT_EVAL_PARAMS;
+        params.set_from_vector(v);
+        return params;
+    }
+
+    pub fn vector_len(&self) -> usize {
+        return self.params().iter().map(|p| p.scores.len() * 2).sum();
+    }
+
+    // bounds for each entry of to_vector
+    pub fn vector_bounds(&self) -> Vec<(i32, i32)> {
+        let mut bounds = Vec::new();
+        for param in self.params() {
+            for _ in 0..param.scores.len() * 2 {
+                bounds.push(param.bounds);
+            }
+        }
+        return bounds;
+    }
+
+    // readable names for each entry of to_vector, e.g. "rook_mobility[3].eg"
+    pub fn vector_names(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        for param in self.params() {
+            for i in 0..param.scores.len() {
+                let index = match param.shape {
+                    (0, _) => String::new(),
+                    (1, _) => format!("[{}]", i),
+                    (_, cols) => format!("[{}][{}]", i / cols, i % cols)
+                };
+                names.push(format!("{}{}.mg", param.name, index));
+                names.push(format!("{}{}.eg", param.name, index));
+            }
+        }
+        return names;
+    }
+
+    pub fn clamp(&mut self) {
+        let bounds: Vec<(i32, i32)> = self.params().iter().map(|p| p.bounds).collect();
+        for ((_, scores), (min, max)) in self.params_mut().into_iter().zip(bounds) {
+            for s in scores.iter_mut() {
+                *s = make_score(mg_score(*s).clamp(min, max), eg_score(*s).clamp(min, max));
+            }
+        }
+    }
+
+    // writes the parameters as a defaultparams.rs that can be dropped into src/
+    pub fn to_rust(&self) -> String {
+        let mut out = String::new();
+        out.push_str("// default weights for the hand-crafted evaluation\n");
+        out.push_str("// this file is written by the tuner, see EvalParams::to_rust\n\n");
+        out.push_str("use crate::eval::*;\nuse crate::evalparams::*;\n\n");
+        out.push_str("macro_rules! S {\n    ($a:expr, $b:expr) => {\n        make_score($a, $b)\n    }\n}\n\n");
+        out.push_str("pub const DEFAULT_EVAL_PARAMS: EvalParams = EvalParams {\n");
+        for param in self.params() {
+            let s = |score: &Score| format!("S!({}, {})", mg_score(*score), eg_score(*score));
+            match param.shape {
+                (0, _) => {
+                    out.push_str(&format!("    {}: {},\n", param.name, s(&param.scores[0])));
+                },
+                (1, _) => {
+                    let row: Vec<String> = param.scores.iter().map(s).collect();
+                    out.push_str(&format!("    {}: [{}],\n", param.name, row.join(", ")));
+                },
+                (_, cols) => {
+                    out.push_str(&format!("    {}: [\n", param.name));
+                    let rows: Vec<String> = param.scores.chunks(cols).map(|r| {
+                        let row: Vec<String> = r.iter().map(s).collect();
+                        format!("        [{}]", row.join(", "))
+                    }).collect();
+                    out.push_str(&rows.join(",\n"));
+                    out.push_str("\n    ],\n");
+                }
+            }
+        }
+        out.push_str("};\n");
+        return out;
+    }
+}
+
+fn param_json(param: &ParamInfo) -> Json {
+    let pair = |s: &Score| Json::Array(vec![Json::from(mg_score(*s)), Json::from(eg_score(*s))]);
+    return match param.shape {
+        (0, _) => pair(&param.scores[0]),
+        (1, _) => Json::Array(param.scores.iter().map(pair).collect()),
+        (_, cols) => Json::Array(param.scores.chunks(cols).map(|r| Json::Array(r.iter().map(pair).collect())).collect())
+    };
+}
+
+// pulls every number out of arbitrarily nested arrays
+fn flatten_json(value: &Json, out: &mut Vec<i32>) -> Result<(), String> {
+    match value {
+        Json::Number(n) => {
+            if n.fract() != 0.0 {
+                return Err(format!("{} is not an integer", n));
+            }
+            out.push(*n as i32);
+        },
+        Json::Array(items) => {
+            for item in items {
+                flatten_json(item, out)?;
+            }
+        },
+        _ => { return Err("expected numbers or arrays of numbers".to_string()); }
+    }
+    return Ok(());
+}
+
+impl EvalParams {
+    // one line per parameter, the name then mg and eg of every score in turn
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        for param in self.params() {
+            out.push_str(param.name);
+            for s in param.scores {
+                out.push_str(&format!(" {} {}", mg_score(*s), eg_score(*s)));
+            }
+            out.push('\n');
+        }
+        return out;
+    }
+
+    pub fn to_json(&self) -> Json {
+        let mut json = Json::object();
+        for param in self.params() {
+            json = json.with(param.name, param_json(&param));
+        }
+        return json;
+    }
+
+    fn set_param(&mut self, name: &str, values: &[i32]) -> Result<(), String> {
+        for (param_name, scores) in self.params_mut() {
+            if param_name != name { continue; }
+            if values.len() != scores.len() * 2 {
+                return Err(format!("{} takes {} values, got {}", name, scores.len() * 2, values.len()));
+            }
+            for (i, s) in scores.iter_mut().enumerate() {
+                *s = make_score(values[2 * i], values[2 * i + 1]);
+            }
+            return Ok(());
+        }
+        return Err(format!("unknown parameter {}", name));
+    }
+
+    // parameters missing from the file keep their default values
+    pub fn from_text(text: &str) -> Result<EvalParams, String> {
+        let mut params = DEFAULT_EVAL_PARAMS;
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') { continue; }
+            let mut fields = line.split_whitespace();
+            let name = fields.next().unwrap();
+            let mut values = Vec::new();
+            for f in fields {
+                match f.parse() {
+                    Ok(v) => { values.push(v); },
+                    Err(_) => { return Err(format!("line {}: bad value '{}'", i + 1, f)); }
+                }
+            }
+            if let Err(e) = params.set_param(name, &values) {
+                return Err(format!("line {}: {}", i + 1, e));
+            }
+        }
+        return Ok(params);
+    }
+
+    pub fn from_json(text: &str) -> Result<EvalParams, String> {
+        let mut params = DEFAULT_EVAL_PARAMS;
+        let members = match Json::parse(text)? {
+            Json::Object(m) => m,
+            _ => { return Err("expected an object of parameters".to_string()); }
+        };
+        for (name, value) in members {
+            let mut values = Vec::new();
+            if let Err(e) = flatten_json(&value, &mut values) {
+                return Err(format!("{}: {}", name, e));
+            }
+            params.set_param(&name, &values)?;
+        }
+        return Ok(params);
+    }
+}
+
+// json if it looks like json, otherwise the text format
+pub fn load_eval_params(fname: &str) -> Result<EvalParams, String> {
+    let text = match std::fs::read_to_string(fname) {
+        Ok(t) => t,
+        Err(e) => { return Err(format!("unable to read {}: {}", fname, e)); }
+    };
+    let params = if text.trim_start().starts_with('{') {
+        EvalParams::from_json(&text)
+    } else {
+        EvalParams::from_text(&text)
+    };
+    return match params {
+        Ok(p) => Ok(p),
+        Err(e) => Err(format!("{}: {}", fname, e))
+    };
+}
+
+// the format follows the extension: .rs, .json, or text for anything else
+pub fn write_eval_params(params: &EvalParams, fname: &str) -> Result<(), String> {
+    let contents = if fname.ends_with(".rs") {
+        params.to_rust()
+    } else if fname.ends_with(".json") {
+        format!("{}\n", params.to_json())
+    } else {
+        params.to_text()
+    };
+    return match std::fs::write(fname, contents) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("unable to write {}: {}", fname, e))
+    };
+}
@@ -0,0 +1,86 @@
+use crate::moveutil::*;
+use crate::util::*;
+
+// hand and brain, where one player (the brain) names a type of piece and the
+// other (the hand) picks the move with it.  as the brain Mantissa ranks the
+// piece types by the best score each can get, and as the hand she plays the
+// best move with the piece she's given
+
+pub const OFF: u8 = 0;
+pub const BRAIN: u8 = 1;
+pub const HAND: u8 = 2;
+
+pub const PIECE_KINDS: [u8; 6] = [b'p', b'n', b'b', b'r', b'q', b'k'];
+
+pub fn bh_mode_from_str(s: &str) -> Option<u8> {
+    return match s.to_ascii_lowercase().as_str() {
+        "off" => Some(OFF),
+        "brain" => Some(BRAIN),
+        "hand" => Some(HAND),
+        _ => None
+    };
+}
+
+pub fn bh_mode_name(mode: u8) -> &'static str {
+    return match mode {
+        BRAIN => "Brain",
+        HAND => "Hand",
+        _ => "Off"
+    };
+}
+
+pub fn piece_name(piece: u8) -> &'static str {
+    return match piece {
+        b'p' => "pawn",
+        b'n' => "knight",
+        b'b' => "bishop",
+        b'r' => "rook",
+        b'q' => "queen",
+        b'k' => "king",
+        _ => "???"
+    };
+}
+
+// which moves the hand may play
+#[derive(Copy, Clone, PartialEq)]
+pub enum HandPiece {
+    Any,
+    Square(i8),
+    Kind(u8)        // a piece type as moves store it, e.g. b'n'
+}
+
+impl HandPiece {
+    // a piece name ("knight"), letter ("n") or the square it's on ("g1")
+    pub fn from_str(s: &str) -> Option<HandPiece> {
+        let s = s.trim().to_ascii_lowercase();
+        if s == "any" || s == "<empty>" {
+            return Some(HandPiece::Any);
+        }
+        for &kind in PIECE_KINDS.iter() {
+            if s == piece_name(kind) || s.as_bytes() == [kind] {
+                return Some(HandPiece::Kind(kind));
+            }
+        }
+        let b = s.as_bytes();
+        if b.len() == 2 && (b'a'..=b'h').contains(&b[0]) && (b'1'..=b'8').contains(&b[1]) {
+            return Some(HandPiece::Square(str_to_idx(s)));
+        }
+        return None;
+    }
+
+    pub fn allows(&self, mv: &Move) -> bool {
+        return match *self {
+            HandPiece::Any => true,
+            HandPiece::Square(sq) => mv.start == sq,
+            HandPiece::Kind(kind) => mv.piece == kind
+        };
+    }
+
+    pub fn name(&self) -> String {
+        return match *self {
+            HandPiece::Any => format!("any piece"),
+            HandPiece::Square(sq) => format!("the piece on {}", idx_to_str(sq)),
+            HandPiece::Kind(kind) => format!("a {}", piece_name(kind))
+        };
+    }
+}
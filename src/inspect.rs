@@ -0,0 +1,196 @@
+use std::collections::HashSet;
+
+use crate::bitboard::*;
+use crate::eval::*;
+use crate::packed::*;
+use crate::search::*;
+use crate::uci::*;
+
+// statistics over a training data file, optionally writing out the
+// positions that pass a set of filters
+
+pub struct InspectOptions {
+    pub out: Option<String>,
+    pub dedup: bool,
+    pub drop_check: bool,
+    pub drop_noisy: bool,   // qsearch disagrees with the static eval
+    pub max_score: i32,     // centipawns, 0 for no limit
+    pub min_pieces: u32
+}
+
+impl InspectOptions {
+    pub fn default() -> InspectOptions {
+        InspectOptions {
+            out: None,
+            dedup: false,
+            drop_check: false,
+            drop_noisy: false,
+            max_score: 0,
+            min_pieces: 0
+        }
+    }
+}
+
+struct Histogram {
+    labels: Vec<String>,
+    counts: Vec<u64>
+}
+
+impl Histogram {
+    fn new(labels: Vec<String>) -> Histogram {
+        let counts = vec![0; labels.len()];
+        return Histogram {
+            labels: labels,
+            counts: counts
+        };
+    }
+
+    fn add(&mut self, bucket: usize) {
+        let bucket = bucket.min(self.counts.len() - 1);
+        self.counts[bucket] += 1;
+    }
+
+    fn print(&self, title: &str) {
+        let total: u64 = self.counts.iter().sum();
+        let max = self.counts.iter().copied().max().unwrap_or(0).max(1);
+        println!("\n{}:", title);
+        for (label, count) in self.labels.iter().zip(self.counts.iter()) {
+            let pct = 100.0 * *count as f64 / total.max(1) as f64;
+            let bar = "#".repeat((40 * count / max) as usize);
+            println!("  {:>13} {:>10} {:>6.2}%  {}", label, count, pct, bar);
+        }
+    }
+}
+
+struct Stats {
+    positions: u64,
+    unreadable: u64,
+    duplicates: u64,
+    in_check: u64,
+    wdl: [u64; 3],  // black win, draw, white win
+    phase: Histogram,
+    pieces: Histogram,
+    score: Histogram
+}
+
+// score buckets are 100cp wide over -1000..1000 with a bucket for each tail
+const SCORE_BUCKETS: i32 = 20;
+
+impl Stats {
+    fn new() -> Stats {
+        let phase = (0..8).map(|i| format!("{}..{}", i * 32, if i == 7 { 256 } else { i * 32 + 31 })).collect();
+        let pieces = (0..8).map(|i| format!("{}..{}", i * 4 + 1, i * 4 + 4)).collect();
+        let mut score = vec!["< -1000".to_string()];
+        for i in 0..SCORE_BUCKETS {
+            score.push(format!("{}..{}", i * 100 - 1000, i * 100 - 901));
+        }
+        score.push(">= 1000".to_string());
+        return Stats {
+            positions: 0,
+            unreadable: 0,
+            duplicates: 0,
+            in_check: 0,
+            wdl: [0; 3],
+            phase: Histogram::new(phase),
+            pieces: Histogram::new(pieces),
+            score: Histogram::new(score)
+        };
+    }
+
+    fn add(&mut self, p: &PackedPosition, pos: &Bitboard, duplicate: bool) {
+        self.positions += 1;
+        if duplicate { self.duplicates += 1; }
+        if pos.is_check(pos.side_to_move) { self.in_check += 1; }
+        self.wdl[p.result as usize] += 1;
+        self.phase.add(pos.get_phase() as usize / 32);
+        self.pieces.add((pos.num_pieces() as usize).saturating_sub(1) / 4);
+        let score = p.score as i32;
+        self.score.add((score.clamp(-1001, 1000).div_euclid(100) + SCORE_BUCKETS / 2 + 1) as usize);
+    }
+
+    fn print(&self) {
+        let pct = |n: u64| 100.0 * n as f64 / self.positions.max(1) as f64;
+        println!("positions      {:>10}", self.positions);
+        if self.unreadable > 0 {
+            println!("unreadable     {:>10}", self.unreadable);
+        }
+        println!("duplicates     {:>10} {:>6.2}%", self.duplicates, pct(self.duplicates));
+        println!("in check       {:>10} {:>6.2}%", self.in_check, pct(self.in_check));
+        println!("white wins     {:>10} {:>6.2}%", self.wdl[2], pct(self.wdl[2]));
+        println!("draws          {:>10} {:>6.2}%", self.wdl[1], pct(self.wdl[1]));
+        println!("black wins     {:>10} {:>6.2}%", self.wdl[0], pct(self.wdl[0]));
+        self.phase.print("game phase (0 opening, 256 endgame)");
+        self.pieces.print("pieces on the board");
+        self.score.print("score (cp, white's point of view)");
+    }
+}
+
+fn is_noisy(pos: &mut Bitboard) -> bool {
+    let eval = static_eval(pos, unsafe { &mut TI[0].pht });
+    return qsearch(pos, -1000000, 1000000, 0) != eval;
+}
+
+pub fn inspect_data(fname: &str, options: &InspectOptions) -> Result<(), String> {
+    let reader = DataReader::open(fname)?;
+    let mut writer = match &options.out {
+        Some(out) => Some(DataWriter::create(out)?),
+        None => None
+    };
+    if options.drop_noisy {
+        setup_search_slots(1, &UCIOptions::default());
+    }
+
+    let mut stats = Stats::new();
+    let mut seen: HashSet<u64> = HashSet::new();
+    // dropped for being a duplicate, in check, noisy, beyond max_score, too few pieces
+    let mut dropped = [0u64; 5];
+    let mut kept = 0;
+    for p in reader {
+        let p = match p {
+            Ok(p) => p,
+            Err(e) if is_packed_file(fname) => { return Err(e); },
+            Err(_) => { stats.unreadable += 1; continue; }
+        };
+        let mut pos = p.to_board();
+        let duplicate = !seen.insert(pos.hash);
+        stats.add(&p, &pos, duplicate);
+
+        let writer = match writer.as_mut() {
+            Some(w) => w,
+            None => { continue; }
+        };
+        let drop = if options.dedup && duplicate {
+            Some(0)
+        } else if options.drop_check && pos.is_check(pos.side_to_move) {
+            Some(1)
+        } else if options.drop_noisy && is_noisy(&mut pos) {
+            Some(2)
+        } else if options.max_score > 0 && (p.score as i32).abs() > options.max_score {
+            Some(3)
+        } else if pos.num_pieces() < options.min_pieces {
+            Some(4)
+        } else {
+            None
+        };
+        match drop {
+            Some(reason) => { dropped[reason] += 1; },
+            None => {
+                writer.write(&p)?;
+                kept += 1;
+            }
+        }
+    }
+
+    stats.print();
+    if let (Some(mut writer), Some(out)) = (writer, &options.out) {
+        writer.flush()?;
+        println!("\nwrote {} of {} positions to {}", kept, stats.positions, out);
+        let reasons = ["duplicate", "in check", "noisy", "score too large", "too few pieces"];
+        for (reason, count) in reasons.iter().zip(dropped.iter()) {
+            if *count > 0 {
+                println!("  dropped {:>10} {}", count, reason);
+            }
+        }
+    }
+    return Ok(());
+}
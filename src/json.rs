@@ -0,0 +1,280 @@
+use std::fmt;
+
+// just enough JSON for parameter files and machine-readable output.
+// objects keep their keys in order so what we write stays readable.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>)
+}
+
+struct Parser<'a> {
+    s: &'a [u8],
+    pos: usize
+}
+
+impl<'a> Parser<'a> {
+    fn err(&self, msg: &str) -> String {
+        return format!("{} at byte {}", msg, self.pos);
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.s.len() && self.s[self.pos].is_ascii_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        return self.s.get(self.pos).copied();
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), String> {
+        if self.peek() != Some(c) {
+            return Err(self.err(&format!("expected '{}'", c as char)));
+        }
+        self.pos += 1;
+        return Ok(());
+    }
+
+    fn literal(&mut self, word: &str, value: Json) -> Result<Json, String> {
+        if self.s[self.pos..].starts_with(word.as_bytes()) {
+            self.pos += word.len();
+            return Ok(value);
+        }
+        return Err(self.err("unexpected token"));
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        return match self.peek() {
+            Some(b'{') => self.object(),
+            Some(b'[') => self.array(),
+            Some(b'"') => Ok(Json::String(self.string()?)),
+            Some(b't') => self.literal("true", Json::Bool(true)),
+            Some(b'f') => self.literal("false", Json::Bool(false)),
+            Some(b'n') => self.literal("null", Json::Null),
+            Some(c) if c == b'-' || c.is_ascii_digit() => self.number(),
+            Some(_) => Err(self.err("unexpected character")),
+            None => Err(self.err("unexpected end of input"))
+        };
+    }
+
+    fn object(&mut self) -> Result<Json, String> {
+        self.expect(b'{')?;
+        let mut members = Vec::new();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(Json::Object(members));
+        }
+        loop {
+            if self.peek() != Some(b'"') {
+                return Err(self.err("expected a key"));
+            }
+            let key = self.string()?;
+            self.expect(b':')?;
+            members.push((key, self.value()?));
+            match self.peek() {
+                Some(b',') => { self.pos += 1; },
+                Some(b'}') => { self.pos += 1; return Ok(Json::Object(members)); },
+                _ => { return Err(self.err("expected ',' or '}'")); }
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Json, String> {
+        self.expect(b'[')?;
+        let mut items = Vec::new();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(Json::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            match self.peek() {
+                Some(b',') => { self.pos += 1; },
+                Some(b']') => { self.pos += 1; return Ok(Json::Array(items)); },
+                _ => { return Err(self.err("expected ',' or ']'")); }
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect(b'"')?;
+        let mut bytes = Vec::new();
+        loop {
+            let c = match self.s.get(self.pos) {
+                Some(c) => *c,
+                None => { return Err(self.err("unterminated string")); }
+            };
+            self.pos += 1;
+            match c {
+                b'"' => { break; },
+                b'\\' => {
+                    let e = match self.s.get(self.pos) {
+                        Some(e) => *e,
+                        None => { return Err(self.err("unterminated string")); }
+                    };
+                    self.pos += 1;
+                    match e {
+                        b'"' | b'\\' | b'/' => { bytes.push(e); },
+                        b'b' => { bytes.push(8); },
+                        b'f' => { bytes.push(12); },
+                        b'n' => { bytes.push(b'\n'); },
+                        b'r' => { bytes.push(b'\r'); },
+                        b't' => { bytes.push(b'\t'); },
+                        b'u' => {
+                            let hex = match self.s.get(self.pos..self.pos + 4) {
+                                Some(h) => String::from_utf8_lossy(h).to_string(),
+                                None => { return Err(self.err("bad unicode escape")); }
+                            };
+                            self.pos += 4;
+                            let code = match u32::from_str_radix(&hex, 16) {
+                                Ok(c) => c,
+                                Err(_) => { return Err(self.err("bad unicode escape")); }
+                            };
+                            let ch = char::from_u32(code).unwrap_or('\u{fffd}');
+                            let mut buf = [0; 4];
+                            bytes.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
+                        },
+                        _ => { return Err(self.err("bad escape")); }
+                    }
+                },
+                _ => { bytes.push(c); }
+            }
+        }
+        return match String::from_utf8(bytes) {
+            Ok(s) => Ok(s),
+            Err(_) => Err(self.err("invalid utf-8 in string"))
+        };
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let start = self.pos;
+        while self.pos < self.s.len() && (self.s[self.pos].is_ascii_digit() || b"+-.eE".contains(&self.s[self.pos])) {
+            self.pos += 1;
+        }
+        let text = String::from_utf8_lossy(&self.s[start..self.pos]);
+        return match text.parse() {
+            Ok(n) => Ok(Json::Number(n)),
+            Err(_) => Err(self.err(&format!("bad number '{}'", text)))
+        };
+    }
+}
+
+impl Json {
+    pub fn parse(s: &str) -> Result<Json, String> {
+        let mut parser = Parser { s: s.as_bytes(), pos: 0 };
+        let value = parser.value()?;
+        if parser.peek().is_some() {
+            return Err(parser.err("trailing characters"));
+        }
+        return Ok(value);
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        if let Json::Object(members) = self {
+            for (k, v) in members {
+                if k == key {
+                    return Some(v);
+                }
+            }
+        }
+        return None;
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        return if let Json::Number(n) = self { Some(*n) } else { None };
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        return if let Json::String(s) = self { Some(s) } else { None };
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<Json>> {
+        return if let Json::Array(a) = self { Some(a) } else { None };
+    }
+
+    pub fn object() -> Json {
+        return Json::Object(Vec::new());
+    }
+
+    // adds a member to an object, for building output
+    pub fn with(mut self, key: &str, value: Json) -> Json {
+        if let Json::Object(members) = &mut self {
+            members.push((key.to_string(), value));
+        }
+        return self;
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?
+        }
+    }
+    return write!(f, "\"");
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(n) => {
+                if !n.is_finite() {
+                    write!(f, "null")
+                } else if n.fract() == 0.0 && n.abs() < 1e15 {
+                    write!(f, "{}", *n as i64)
+                } else {
+                    write!(f, "{}", n)
+                }
+            },
+            Json::String(s) => write_string(f, s),
+            Json::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 { write!(f, ",")?; }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            },
+            Json::Object(members) => {
+                write!(f, "{{")?;
+                for (i, (k, v)) in members.iter().enumerate() {
+                    if i > 0 { write!(f, ",")?; }
+                    write_string(f, k)?;
+                    write!(f, ":{}", v)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+impl From<f64> for Json {
+    fn from(n: f64) -> Json { return Json::Number(n); }
+}
+
+impl From<i32> for Json {
+    fn from(n: i32) -> Json { return Json::Number(n as f64); }
+}
+
+impl From<bool> for Json {
+    fn from(b: bool) -> Json { return Json::Bool(b); }
+}
+
+impl From<&str> for Json {
+    fn from(s: &str) -> Json { return Json::String(s.to_string()); }
+}
@@ -6,43 +6,72 @@
 // #![feature(exclusive_range_pattern)]
 
 
+mod advisor;
+mod bench;
 mod bitboard;
+mod datagen;
 mod default_nnue;
+mod defaultparams;
+mod epd;
 mod eval;
+mod evalparams;
 mod evalutil;
+mod handbrain;
+mod inspect;
+mod json;
 mod magic;
 mod movegen;
 mod moveorder;
 mod moveutil;
 mod nnue;
+mod nnuecheck;
+mod packed;
 mod perft;
 mod pgn;
 mod pht;
 mod psqt;
+mod rand;
 mod search;
 mod searchparams;
 mod searchutil;
 mod see;
+mod skill;
+mod solve;
 mod syzygy;
 mod time;
+mod train;
 mod tt;
+mod tuning;
 mod uci;
 mod util;
+mod wdl;
 mod zobrist;
 
+use crate::bench::*;
 use crate::bitboard::*;
+use crate::datagen::*;
+use crate::eval::*;
+use crate::evalparams::*;
 use crate::evalutil::*;
+use crate::inspect::*;
 use crate::magic::*;
 use crate::movegen::*;
 use crate::nnue::*;
+use crate::nnuecheck::*;
+use crate::packed::*;
 use crate::perft::*;
 use crate::pgn::*;
 use crate::pht::*;
 use crate::syzygy::*;
+use crate::search::get_time_millis;
 use crate::searchutil::*;
+use crate::solve::*;
+use crate::train::*;
 use crate::tt::*;
+use crate::tuning::*;
 use crate::uci::*;
 use crate::util::*;
+use crate::wdl::*;
 
 fn init() {
     initialize_masks();
@@ -53,16 +82,504 @@ fn init() {
     allocate_tt(64);
 }
 
+// the tests share one set of tables, set up by whichever gets there first
+#[cfg(test)]
+fn test_init() {
+    static INIT: std::sync::Once = std::sync::Once::new();
+    INIT.call_once(init);
+}
+
+// command line handling.  with no arguments mantissa speaks UCI, anything
+// else is one of the tool subcommands below.
+
+struct CommandArgs {
+    positional: Vec<String>,
+    flags: Vec<(String, String)>
+}
+
+impl CommandArgs {
+    // switches are options that take no value
+    fn parse(args: &[String], allowed: &[&str], switches: &[&str]) -> Result<CommandArgs, String> {
+        let mut parsed = CommandArgs {
+            positional: Vec::new(),
+            flags: Vec::new()
+        };
+        let mut i = 0;
+        while i < args.len() {
+            let arg = &args[i];
+            if let Some(name) = arg.strip_prefix("--") {
+                if switches.contains(&name) {
+                    parsed.flags.push((name.to_string(), String::new()));
+                    i += 1;
+                    continue;
+                }
+                if !allowed.contains(&name) && !GLOBAL_OPTIONS.contains(&name) {
+                    return Err(format!("unknown option {}", arg));
+                }
+                let value = match args.get(i + 1) {
+                    Some(v) => v.clone(),
+                    None => { return Err(format!("missing value for {}", arg)); }
+                };
+                parsed.flags.push((name.to_string(), value));
+                i += 2;
+            } else {
+                parsed.positional.push(arg.clone());
+                i += 1;
+            }
+        }
+        return Ok(parsed);
+    }
+
+    fn has(&self, name: &str) -> bool {
+        return self.flags.iter().any(|(n, _)| n == name);
+    }
+
+    fn get<T: std::str::FromStr>(&self, name: &str, default: T) -> Result<T, String> {
+        for (n, v) in &self.flags {
+            if n == name {
+                return match v.parse() {
+                    Ok(v) => Ok(v),
+                    Err(_) => Err(format!("invalid value '{}' for --{}", v, name))
+                };
+            }
+        }
+        return Ok(default);
+    }
+
+    fn get_str(&self, name: &str) -> Option<&str> {
+        for (n, v) in &self.flags {
+            if n == name {
+                return Some(v.as_str());
+            }
+        }
+        return None;
+    }
+
+    fn positional(&self, idx: usize, what: &str) -> Result<&str, String> {
+        return match self.positional.get(idx) {
+            Some(p) => Ok(p.as_str()),
+            None => Err(format!("missing {}", what))
+        };
+    }
+}
+
+// accepted by every command
+const GLOBAL_OPTIONS: [&str; 3] = ["eval-params", "eval-file", "eval-mode"];
+
+struct Command {
+    name: &'static str,
+    usage: &'static str,
+    description: &'static str,
+    options: &'static [&'static str],
+    switches: &'static [&'static str],
+    run: fn(&CommandArgs) -> Result<(), String>
+}
+
+const COMMANDS: [Command; 14] = [
+    Command {
+        name: "uci",
+        usage: "uci",
+        description: "Run the UCI protocol loop (the default with no arguments).",
+        options: &[],
+        switches: &[],
+        run: uci_command
+    },
+    Command {
+        name: "bench",
+        usage: "bench [--depth n]",
+        description: "Search the built-in bench positions and report the node count and speed.",
+        options: &["depth"],
+        switches: &[],
+        run: bench_command
+    },
+    Command {
+        name: "perft",
+        usage: "perft <depth> [--fen fen]",
+        description: "Count leaf nodes of the move generation tree.",
+        options: &["fen"],
+        switches: &[],
+        run: perft_command
+    },
+    Command {
+        name: "convert-pgn",
+        usage: "convert-pgn <games.pgn>",
+        description: "Extract quiet, engine-scored positions from annotated games as training data.",
+        options: &[],
+        switches: &[],
+        run: convert_pgn_command
+    },
+    Command {
+        name: "convert-data",
+        usage: "convert-data <input> <output>",
+        description: "Convert training data between text lines and packed .bin records.",
+        options: &[],
+        switches: &[],
+        run: convert_data_command
+    },
+    Command {
+        name: "inspect-data",
+        usage: "inspect-data <data> [--out file] [--dedup] [--drop-check] [--drop-noisy] [--max-score cp] [--min-pieces n]",
+        description: "Report statistics on training data and optionally write a filtered copy.",
+        options: &["out", "max-score", "min-pieces"],
+        switches: &["dedup", "drop-check", "drop-noisy"],
+        run: inspect_data_command
+    },
+    Command {
+        name: "tune",
+        usage: "tune <positions.txt> [--epochs n] [--batch n] [--lr x] [--k x] [--threads n] [--out file] [--method adam|local]",
+        description: "Tune the handcrafted evaluation against labelled positions and write a parameter file.",
+        options: &["epochs", "batch", "lr", "k", "threads", "out", "method"],
+        switches: &[],
+        run: tune_command
+    },
+    Command {
+        name: "datagen",
+        usage: "datagen [--games n] [--threads n] [--depth n] [--nodes n] [--random-plies n] [--book file] [--win-score cp] [--win-plies n] [--draw-score cp] [--draw-plies n] [--draw-after n] [--max-plies n] [--max-opening-score cp] [--syzygy path] [--hash mb] [--seed n] [--out file]",
+        description: "Play self-play games and append their quiet, scored positions as training data.",
+        options: &["games", "threads", "depth", "nodes", "random-plies", "book", "win-score", "win-plies", "draw-score", "draw-plies", "draw-after", "max-plies", "max-opening-score", "syzygy", "hash", "seed", "out"],
+        switches: &[],
+        run: datagen_command
+    },
+    Command {
+        name: "train",
+        usage: "train <data> [--epochs n] [--batch n] [--lr x] [--lr-schedule constant|step|cosine] [--lr-step n] [--lr-gamma x] [--lambda x] [--k x] [--output-buckets n] [--threads n] [--out file] [--save-every n] [--resume net] [--start-epoch n] [--chunk n] [--tolerance x] [--seed n]",
+        description: "Train an NNUE network on CPU from training data.",
+        options: &["epochs", "batch", "lr", "lr-schedule", "lr-step", "lr-gamma", "lambda", "k", "output-buckets", "threads", "out", "save-every", "resume", "start-epoch", "chunk", "tolerance", "seed"],
+        switches: &[],
+        run: train_command
+    },
+    Command {
+        name: "net-export",
+        usage: "net-export <net.nnue> [--image prefix]",
+        description: "Print a network as default_nnue.rs source, optionally saving a weight visualization.",
+        options: &["image"],
+        switches: &[],
+        run: net_export_command
+    },
+    Command {
+        name: "fit-wdl",
+        usage: "fit-wdl <data> [--iterations n]",
+        description: "Fit the win/draw/loss model to the scores and results in training data.",
+        options: &["iterations"],
+        switches: &[],
+        run: fit_wdl_command
+    },
+    Command {
+        name: "check-nnue",
+        usage: "check-nnue <net.nnue> [--games n] [--plies n] [--tolerance n] [--seed n]",
+        description: "Play random games checking incremental NNUE evals against full refreshes and the float reference.",
+        options: &["games", "plies", "tolerance", "seed"],
+        switches: &[],
+        run: check_nnue_command
+    },
+    Command {
+        name: "solve",
+        usage: "solve <suite.epd> [--time ms] [--depth n] [--nodes n] [--threads n] [--hash mb]",
+        description: "Run an EPD test suite and check the results against bm/am.",
+        options: &["time", "depth", "nodes", "threads", "hash"],
+        switches: &[],
+        run: solve_command
+    },
+    Command {
+        name: "help",
+        usage: "help [command]",
+        description: "Show this message or the usage of a single command.",
+        options: &[],
+        switches: &[],
+        run: help_command
+    },
+];
+
+fn uci_command(_args: &CommandArgs) -> Result<(), String> {
+    uci_loop();
+    return Ok(());
+}
+
+fn bench_command(args: &CommandArgs) -> Result<(), String> {
+    let depth = args.get("depth", 12)?;
+    if !(1..=MAX_DEPTH as i32).contains(&depth) {
+        return Err(format!("depth must be between 1 and {}", MAX_DEPTH));
+    }
+    bench(depth);
+    return Ok(());
+}
+
+fn perft_command(args: &CommandArgs) -> Result<(), String> {
+    let depth: i8 = match args.positional(0, "depth")?.parse() {
+        Ok(d) => d,
+        Err(_) => { return Err("depth must be a number".to_string()); }
+    };
+    if !(1..=15).contains(&depth) {
+        return Err("depth must be between 1 and 15".to_string());
+    }
+    let fen = args.get_str("fen").unwrap_or("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+    Bitboard::validate_fen(fen)?;
+    run_perft(&mut Bitboard::from_position(fen.to_string()), depth);
+    return Ok(());
+}
+
+fn convert_pgn_command(args: &CommandArgs) -> Result<(), String> {
+    convert_pgn(args.positional(0, "PGN file")?);
+    return Ok(());
+}
+
+fn convert_data_command(args: &CommandArgs) -> Result<(), String> {
+    let input = args.positional(0, "input file")?;
+    let output = args.positional(1, "output file")?;
+    let (written, skipped) = convert_data(input, output)?;
+    println!("wrote {} positions to {}", written, output);
+    if skipped > 0 {
+        println!("skipped {} lines", skipped);
+    }
+    return Ok(());
+}
+
+fn inspect_data_command(args: &CommandArgs) -> Result<(), String> {
+    let fname = args.positional(0, "data file")?;
+    let defaults = InspectOptions::default();
+    let options = InspectOptions {
+        out: args.get_str("out").map(|o| o.to_string()),
+        dedup: args.has("dedup"),
+        drop_check: args.has("drop-check"),
+        drop_noisy: args.has("drop-noisy"),
+        max_score: args.get("max-score", defaults.max_score)?,
+        min_pieces: args.get("min-pieces", defaults.min_pieces)?
+    };
+    if options.out.is_none() && (args.has("dedup") || args.has("drop-check") || args.has("drop-noisy") || args.has("max-score") || args.has("min-pieces")) {
+        return Err("filters need --out".to_string());
+    }
+    return inspect_data(fname, &options);
+}
+
+fn tune_command(args: &CommandArgs) -> Result<(), String> {
+    let fname = args.positional(0, "position file")?;
+    match args.get_str("method").unwrap_or("adam") {
+        "adam" => {},
+        "local" => {
+            // the old random local search, kept for comparison
+            let mut positions = get_position_vector(fname);
+            tune(&mut positions);
+            return Ok(());
+        },
+        m => { return Err(format!("unknown tuning method {}", m)); }
+    }
+
+    let options = GradientOptions {
+        epochs: args.get("epochs", 100)?,
+        batch_size: args.get("batch", 16384)?,
+        learning_rate: args.get("lr", 1.0)?,
+        threads: args.get("threads", 1)?,
+        k: args.get("k", 0.0)?,
+        out: args.get_str("out").unwrap_or("defaultparams.rs").to_string()
+    };
+    let mut entries = load_tuning_entries(fname, options.threads)?;
+    gradient_tune(&mut entries, &options)?;
+    return Ok(());
+}
+
+fn datagen_command(args: &CommandArgs) -> Result<(), String> {
+    let defaults = DatagenOptions::default();
+    let mut options = DatagenOptions {
+        games: args.get("games", defaults.games)?,
+        threads: args.get("threads", defaults.threads)?,
+        depth: args.get("depth", defaults.depth)?,
+        nodes: args.get("nodes", defaults.nodes)?,
+        random_plies: args.get("random-plies", defaults.random_plies)?,
+        book: args.get_str("book").map(|b| b.to_string()),
+        win_score: args.get("win-score", defaults.win_score)?,
+        win_plies: args.get("win-plies", defaults.win_plies)?,
+        draw_score: args.get("draw-score", defaults.draw_score)?,
+        draw_plies: args.get("draw-plies", defaults.draw_plies)?,
+        draw_after: args.get("draw-after", defaults.draw_after)?,
+        max_plies: args.get("max-plies", defaults.max_plies)?,
+        max_opening_score: args.get("max-opening-score", defaults.max_opening_score)?,
+        out: args.get_str("out").unwrap_or(&defaults.out).to_string(),
+        seed: args.get("seed", get_time_millis() as u64)?,
+        hash: args.get("hash", defaults.hash)?,
+        syzygy_path: args.get_str("syzygy").unwrap_or("").to_string()
+    };
+    // a depth on its own means no node limit
+    if args.has("depth") && !args.has("nodes") {
+        options.nodes = 0;
+    }
+    if options.threads == 0 {
+        return Err("threads must be at least 1".to_string());
+    }
+    if args.has("depth") && (options.depth < 1 || options.depth >= MAX_DEPTH as i32) {
+        return Err(format!("depth must be between 1 and {}", MAX_DEPTH - 1));
+    }
+    if options.depth == 0 && options.nodes == 0 {
+        return Err("searches need a limit, give --nodes or --depth".to_string());
+    }
+    return datagen(&options);
+}
+
+fn train_command(args: &CommandArgs) -> Result<(), String> {
+    let fname = args.positional(0, "data file")?;
+    let defaults = TrainOptions::default();
+    let schedule = match args.get_str("lr-schedule") {
+        Some(s) => match LrSchedule::from_str(s) {
+            Some(s) => s,
+            None => { return Err(format!("unknown learning rate schedule {}", s)); }
+        },
+        None => defaults.schedule
+    };
+    let options = TrainOptions {
+        epochs: args.get("epochs", defaults.epochs)?,
+        start_epoch: args.get("start-epoch", defaults.start_epoch)?,
+        batch_size: args.get("batch", defaults.batch_size)?,
+        chunk_size: args.get("chunk", defaults.chunk_size)?,
+        learning_rate: args.get("lr", defaults.learning_rate)?,
+        schedule: schedule,
+        lr_step: args.get("lr-step", defaults.lr_step)?,
+        lr_gamma: args.get("lr-gamma", defaults.lr_gamma)?,
+        lambda: args.get("lambda", defaults.lambda)?,
+        k: args.get("k", defaults.k)?,
+        output_buckets: args.get("output-buckets", defaults.output_buckets)?,
+        threads: args.get("threads", defaults.threads)?,
+        resume: args.get_str("resume").map(|r| r.to_string()),
+        out: args.get_str("out").unwrap_or(&defaults.out).to_string(),
+        save_every: args.get("save-every", defaults.save_every)?,
+        tolerance: args.get("tolerance", defaults.tolerance)?,
+        seed: args.get("seed", defaults.seed)?
+    };
+    if options.start_epoch < 1 || options.start_epoch > options.epochs {
+        return Err("start-epoch must be between 1 and epochs".to_string());
+    }
+    if options.batch_size == 0 || options.chunk_size == 0 {
+        return Err("batch and chunk sizes must be positive".to_string());
+    }
+    if !(0.0..=1.0).contains(&options.lambda) {
+        return Err("lambda must be between 0 and 1".to_string());
+    }
+    if options.output_buckets == 0 || options.output_buckets > 32 {
+        return Err("output-buckets must be between 1 and 32".to_string());
+    }
+    return train(fname, &options);
+}
+
+fn net_export_command(args: &CommandArgs) -> Result<(), String> {
+    let fname = args.positional(0, "network file")?;
+    let net = match SlowNetwork::load(fname) {
+        Ok(n) => n,
+        Err(e) => { return Err(format!("unable to load {}: {}", fname, e)); }
+    };
+    if let Some(prefix) = args.get_str("image") {
+        if let Err(e) = net.save_image(prefix) {
+            return Err(format!("unable to save image: {}", e));
+        }
+    }
+    net.print();
+    return Ok(());
+}
+
+fn fit_wdl_command(args: &CommandArgs) -> Result<(), String> {
+    let fname = args.positional(0, "data file")?;
+    return fit_wdl(fname, args.get("iterations", 20000)?);
+}
+
+fn check_nnue_command(args: &CommandArgs) -> Result<(), String> {
+    let fname = args.positional(0, "network file")?;
+    let defaults = NnueCheckOptions::default();
+    let options = NnueCheckOptions {
+        games: args.get("games", defaults.games)?,
+        plies: args.get("plies", defaults.plies)?,
+        tolerance: args.get("tolerance", defaults.tolerance)?,
+        seed: args.get("seed", defaults.seed)?
+    };
+    return check_nnue(fname, &options);
+}
+
+fn solve_command(args: &CommandArgs) -> Result<(), String> {
+    let fname = args.positional(0, "EPD file")?;
+    let mut limits = SearchLimits::depth(MAX_DEPTH as i32);
+    limits.movetime = args.get("time", 0)?;
+    limits.depth = args.get("depth", MAX_DEPTH as i32)?;
+    limits.nodes = args.get("nodes", 0)?;
+    if !args.has("time") && !args.has("depth") && !args.has("nodes") {
+        limits.movetime = 1000;
+    }
+    let mut options = UCIOptions::default();
+    options.num_threads = args.get("threads", 1)?;
+    if options.num_threads == 0 {
+        return Err("threads must be at least 1".to_string());
+    }
+    options.hash = args.get("hash", 64)?;
+    solve_suite(fname, limits, options);
+    return Ok(());
+}
+
+fn help_command(args: &CommandArgs) -> Result<(), String> {
+    if let Some(name) = args.positional.first() {
+        for c in COMMANDS.iter() {
+            if c.name == name {
+                println!("usage: mantissa {}\n\n{}", c.usage, c.description);
+                return Ok(());
+            }
+        }
+        return Err(format!("unknown command {}", name));
+    }
+    println!("usage: mantissa [command] [args]\n");
+    println!("commands:");
+    for c in COMMANDS.iter() {
+        println!("  {:<14} {}", c.name, c.description);
+    }
+    println!("\nglobal options:");
+    println!("  {:<14} Load handcrafted eval weights from a text or JSON file.", "--eval-params");
+    println!("  {:<14} Use a BZ format network instead of the embedded one.", "--eval-file");
+    println!("  {:<14} Evaluate with nnue, hce or hybrid.", "--eval-mode");
+    println!("\nrun `mantissa help <command>` for a command's arguments.");
+    return Ok(());
+}
+
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let name = if args.len() > 1 { args[1].as_str() } else { "uci" };
+    let command = match COMMANDS.iter().find(|c| c.name == name || (c.name == "help" && (name == "--help" || name == "-h"))) {
+        Some(c) => c,
+        None => {
+            eprintln!("ERR: unknown command {}", name);
+            let _ = help_command(&CommandArgs {positional: Vec::new(), flags: Vec::new()});
+            std::process::exit(1);
+        }
+    };
+
+    let rest = if args.len() > 2 { &args[2..] } else { &[] };
+    let command_args = match CommandArgs::parse(rest, command.options, command.switches) {
+        Ok(a) => a,
+        Err(e) => {
+            eprintln!("ERR: {}\nusage: mantissa {}", e, command.usage);
+            std::process::exit(1);
+        }
+    };
+
     init();
-    // let n = SlowNetwork::load("/home/jtwright/chess/mantissa/epoch-299.nnue").unwrap();
-    // n.save_image("net");
-    // n.print();
-
-    // let n = SlowNetwork::load("/home/jtwright/nets_50_50/epoch-235.nnue").unwrap();
-    // n.print();
-    // unsafe {
-    //     setup_tb("/home/jtwright/chess/tablebase/3-4-5/");
-    // }
-    uci_loop();
+    if let Some(fname) = command_args.get_str("eval-params") {
+        match load_eval_params(fname) {
+            Ok(p) => { set_eval_params(p); },
+            Err(e) => {
+                eprintln!("ERR: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+    if let Some(fname) = command_args.get_str("eval-file") {
+        if let Err(e) = load_eval_file(fname) {
+            eprintln!("ERR: {}", e);
+            std::process::exit(1);
+        }
+    }
+    if let Some(mode) = command_args.get_str("eval-mode") {
+        match EvalMode::from_str(mode) {
+            Some(m) => { set_eval_mode(m); },
+            None => {
+                eprintln!("ERR: unknown eval mode {}", mode);
+                std::process::exit(1);
+            }
+        }
+    }
+    if let Err(e) = (command.run)(&command_args) {
+        eprintln!("ERR: {}\nusage: mantissa {}", e, command.usage);
+        std::process::exit(1);
+    }
 }
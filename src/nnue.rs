@@ -2,7 +2,8 @@
 use std::fs::File;
 use std::io::BufWriter;
 use std::io::prelude::*;
-use std::simd::{num::SimdFloat, *};
+use std::simd::{cmp::SimdOrd, num::SimdInt, *};
+use std::sync::{Arc, OnceLock};
 
 use crate::bitboard::Bitboard;
 use crate::default_nnue::*;
@@ -21,7 +22,7 @@ pub struct SlowNetwork {
     pub activations: Vec<Matrix>,
 }
 
-fn input_number(piece: u8, white: bool, idx: i32) -> usize {
+pub fn input_number(piece: u8, white: bool, idx: i32) -> usize {
     let piece_num = if white { piece } else { 6 + piece };
     let num = (piece_num as i32 * 64 + idx) as i16;
     return num as usize;
@@ -33,7 +34,7 @@ pub fn region(idx: i8) -> i8 {
   return ((MAP >> (idx*2)) & 3) as i8;
 }
 
-fn flip_input(input: i16) -> i16 {
+pub fn flip_input(input: i16) -> i16 {
     // need to flip "color" and "rank"
     // flip square
     if input == 768 { return 768; }
@@ -51,9 +52,179 @@ fn relu(x: f32) -> f32 {
     x.max(0.0)
 }
 
+// unpacking the embedded net is slow, so it's only done once and every
+// board after that shares the feature weights
+static DEFAULT_NET: OnceLock<Network> = OnceLock::new();
+
+// a net loaded at runtime through EvalFile, used in place of the embedded one
+static mut LOADED_NET: Option<Network> = None;
+
+// the net every new board starts with
 pub fn get_default_net() -> Network {
     unsafe {
-        Network::load_default()
+        if let Some(net) = &*std::ptr::addr_of!(LOADED_NET) {
+            return net.copy();
+        }
+    }
+    return DEFAULT_NET.get_or_init(|| unsafe { Network::load_default() }).copy();
+}
+
+// only safe to call while nothing is searching
+pub fn load_eval_file(fname: &str) -> Result<NetShape, String> {
+    let net = Network::load(fname)?;
+    let shape = net.shape().clone();
+    unsafe { LOADED_NET = Some(net); }
+    return Ok(shape);
+}
+
+pub fn use_embedded_net() {
+    unsafe { LOADED_NET = None; }
+}
+
+fn invalid_data(msg: &str) -> std::io::Error {
+    return std::io::Error::new(std::io::ErrorKind::InvalidData, msg.to_string());
+}
+
+// the topology recorded in a BZ header
+pub struct BzHeader {
+    pub inputs: usize,
+    pub outputs: usize,
+    pub hidden: Vec<usize>
+}
+
+impl BzHeader {
+    // magic, id, inputs, outputs, layer count and one size per layer
+    pub fn size(&self) -> usize {
+        return 20 + 4 * self.hidden.len();
+    }
+}
+
+// checks a BZ header, returning the topology
+fn read_bz_header(file: &mut impl Read) -> std::io::Result<BzHeader> {
+    let mut buf: [u8; 4] = [0; 4];
+
+    // magic number
+    file.read_exact(&mut buf)?;
+    if buf[0..2] != [66, 90] { return Err(invalid_data("magic word does not match expected 'BZ'")); }
+    if buf[2..4] != [2, 0] { return Err(invalid_data("unsupported BZ format version")); }
+
+    // network id
+    file.read_exact(&mut buf)?;
+    let _id = u32::from_le_bytes(buf);
+
+    // topology information
+    file.read_exact(&mut buf)?;
+    let inputs = u32::from_le_bytes(buf) as usize;
+    file.read_exact(&mut buf)?;
+    let outputs = u32::from_le_bytes(buf) as usize;
+    file.read_exact(&mut buf)?;
+    let layers = u32::from_le_bytes(buf);
+    if layers == 0 || layers > 8 {
+        return Err(invalid_data(&format!("unexpected number of layers {}", layers)));
+    }
+
+    // hidden neuron counts in layers
+    let mut hidden: Vec<usize> = Vec::new();
+    for _ in 0..layers {
+        file.read_exact(&mut buf)?;
+        hidden.push(u32::from_le_bytes(buf) as usize);
+    }
+    return Ok(BzHeader {
+        inputs: inputs,
+        outputs: outputs,
+        hidden: hidden
+    });
+}
+
+// the king bucket layouts the engine knows, by number of buckets.  4 is the
+// region map above and 64 is a bucket for every square
+pub const KING_BUCKETS: [usize; 3] = [1, 4, 64];
+
+pub fn king_bucket(buckets: usize, idx: i8) -> usize {
+    return match buckets {
+        4 => region(idx) as usize,
+        64 => idx as usize,
+        _ => 0
+    };
+}
+
+// the largest networks Network will run
+const MAX_WIDTH: usize = 2048;          // accumulator neurons per perspective
+const MAX_LAYER_SIZE: usize = 256;      // neurons in a layer after the accumulator
+const MAX_OUTPUT_BUCKETS: usize = 32;
+
+// nets with several outputs have one per material bucket, picked by the
+// number of pieces on the board.  the trainer picks them the same way
+pub fn output_bucket(num_pieces: u32, buckets: usize) -> usize {
+    let num_pieces = num_pieces.clamp(1, 32) as usize;
+    return (num_pieces - 1) * buckets / 32;
+}
+
+// the shape of a network the engine can run: 768 inputs per king bucket
+// into an accumulator of width neurons per perspective, then the layers
+// after it, the last of which is an output per material bucket
+#[derive(Clone, PartialEq)]
+pub struct NetShape {
+    pub buckets: usize,
+    pub width: usize,
+    pub layers: Vec<usize>
+}
+
+impl NetShape {
+    pub fn from_header(header: &BzHeader) -> Result<NetShape, String> {
+        let buckets = header.inputs / 768;
+        if header.inputs % 768 != 0 || !KING_BUCKETS.contains(&buckets) {
+            return Err(format!("{} inputs isn't 768 per king bucket for {:?} buckets", header.inputs, KING_BUCKETS));
+        }
+        let width = header.hidden[0] / 2;
+        if header.hidden[0] % 32 != 0 || width == 0 || width > MAX_WIDTH {
+            return Err(format!("the accumulator has to be two perspectives of a multiple of 16 neurons, at most {}, not {}", MAX_WIDTH, header.hidden[0]));
+        }
+        if header.outputs == 0 || header.outputs > MAX_OUTPUT_BUCKETS {
+            return Err(format!("networks can have at most {} output buckets, not {}", MAX_OUTPUT_BUCKETS, header.outputs));
+        }
+        let mut layers = header.hidden[1..].to_vec();
+        layers.push(header.outputs);
+        if layers.iter().any(|&n| n == 0 || n > MAX_LAYER_SIZE) {
+            return Err(format!("layers after the accumulator can have at most {} neurons", MAX_LAYER_SIZE));
+        }
+        return Ok(NetShape {
+            buckets: buckets,
+            width: width,
+            layers: layers
+        });
+    }
+
+    // the embedded net
+    pub fn default_shape() -> NetShape {
+        NetShape {
+            buckets: 4,
+            width: 256,
+            layers: vec![1]
+        }
+    }
+
+    pub fn outputs(&self) -> usize {
+        return self.layers[self.layers.len() - 1];
+    }
+
+    // number of weights and biases the file holds after the header
+    pub fn num_params(&self) -> usize {
+        let mut total = (768 * self.buckets + 1) * 2 * self.width;
+        let mut inputs = 2 * self.width;
+        for &n in self.layers.iter() {
+            total += (inputs + 1) * n;
+            inputs = n;
+        }
+        return total;
+    }
+
+    pub fn describe(&self) -> String {
+        let mut s = format!("768x{} -> {}x2", self.buckets, self.width);
+        for n in self.layers.iter() {
+            s += &format!(" -> {}", n);
+        }
+        return s;
     }
 }
 
@@ -152,43 +323,28 @@ impl SlowNetwork {
     }
 
     pub fn load(fname: &str) -> std::io::Result<SlowNetwork> {
-        let mut file = File::open(fname)?;
+        let mut file = std::io::BufReader::new(File::open(fname)?);
 
         // We'll read one 4-byte "word" at a time
         let mut buf: [u8; 4] = [0; 4];
 
-        // magic number
-        file.read(&mut buf)?;
-        if buf[0..2] != [66, 90] { panic!("Magic word does not match expected 'BZ'"); }
-        if buf[2..4] != [2, 0] { panic!("SlowNetwork binary format version is not supported."); }
-
-        // network id
-        file.read(&mut buf)?;
-        let _id = u32::from_le_bytes(buf);
-        // println!("loading network with id {}", id);
-
-        // topology information
-        file.read(&mut buf)?;
-        let _inputs = u32::from_le_bytes(buf);
-        file.read(&mut buf)?;
-        let _outputs = u32::from_le_bytes(buf);
-        file.read(&mut buf)?;
-        let layers = u32::from_le_bytes(buf);
-
-        // hidden neuron counts in layers
-        let mut hidden: Vec<u32> = Vec::new();
-        for _ in 0..layers {
-            file.read(&mut buf)?;
-            hidden.push(u32::from_le_bytes(buf));
+        let header = read_bz_header(&mut file)?;
+        let hidden = header.hidden;
+        let layers = hidden.len() as u32;
+        if hidden.iter().any(|h| *h == 0 || *h > 4096) {
+            return Err(invalid_data("unexpected hidden layer size"));
+        }
+        if header.inputs == 0 || header.inputs > 768 * 64 || header.outputs == 0 || header.outputs > 4096 {
+            return Err(invalid_data("unexpected number of inputs or outputs"));
         }
 
         let mut weights = Vec::new();
         let mut biases = Vec::new();
         let mut activations = Vec::new();
-        let mut input_size = 768*4;
+        let mut input_size = header.inputs;
         for l in 0..(layers + 1) {
             let output_size = if l == layers {
-                1
+                header.outputs
             } else {
                 hidden[l as usize]
             } as usize;
@@ -201,12 +357,12 @@ impl SlowNetwork {
 
         for i in 0..activations.len() {
             for j in 0..weights[i].size() {
-                file.read(&mut buf)?;
+                file.read_exact(&mut buf)?;
                 weights[i].data[j] = f32::from_le_bytes(buf);
             }
 
             for j in 0..biases[i].size() {
-                file.read(&mut buf)?;
+                file.read_exact(&mut buf)?;
                 biases[i].data[j] = f32::from_le_bytes(buf);
             }
         }
@@ -245,102 +401,73 @@ impl SlowNetwork {
         }
     }
 
-    fn set_bb_activations(&mut self, bb: u64, piece_num: u8, is_white: bool) {
+    // the reference for Network: plain float math from scratch, with none of
+    // its quantization, flipping tricks or incremental updates
+    fn set_bb_activations(&mut self, bb: u64, piece_num: u8, is_white: bool, regions: [usize; 2], ofs: [usize; 2]) {
+        let width = self.weights[0].rows / 2;
         let mut bb = bb;
         while bb != 0 {
             let idx = bb.trailing_zeros() as i32;
             bb &= bb - 1;
             let input_number = input_number(piece_num, is_white, idx);
-            for j in 0..self.activations[0].size() {
-                self.activations[0].data[j] += self.weights[0].get(j, input_number);
+            // black sees the board flipped, and only the first half of each
+            // row is used
+            let white_input = regions[0] + input_number;
+            let black_input = regions[1] + flip_input(input_number as i16) as usize;
+            for j in 0..width {
+                self.activations[0].data[ofs[0] + j] += self.weights[0].get(j, white_input);
+                self.activations[0].data[ofs[1] + j] += self.weights[0].get(j, black_input);
             }
         }
     }
 
+    // fills in the first layer's inputs, side to move's half first
     pub fn set_activations(&mut self, pos: &Bitboard) {
-        for i in 0..self.activations[0].data.len() {
-            self.activations[0].data[i] = self.biases[0].data[i];
+        let width = self.weights[0].rows / 2;
+        let buckets = self.weights[0].cols / 768;
+        let wkr = king_bucket(buckets, pos.king[1].trailing_zeros() as i8) * 768;
+        let bkr = king_bucket(buckets, (pos.king[0].trailing_zeros() ^ 56) as i8) * 768;
+        let ofs = if pos.side_to_move == Color::White { [0, width] } else { [width, 0] };
+        for j in 0..width {
+            self.activations[0].data[ofs[0] + j] = self.biases[0].data[j];
+            self.activations[0].data[ofs[1] + j] = self.biases[0].data[j];
         }
 
         for side in [Color::White, Color::Black] {
             let is_white = side == Color::White;
             let me = side as usize;
 
-            self.set_bb_activations(pos.pawn[me], PAWN, is_white);
-            self.set_bb_activations(pos.knight[me], KNIGHT, is_white);
-            self.set_bb_activations(pos.bishop[me], BISHOP, is_white);
-            self.set_bb_activations(pos.rook[me], ROOK, is_white);
-            self.set_bb_activations(pos.queen[me], QUEEN, is_white);
-            self.set_bb_activations(pos.king[me], KING, is_white);
-        }
-
-        if pos.side_to_move == Color::White {
-            for j in 0..self.activations[0].size() {
-                self.activations[0].data[j] += self.weights[0].get(j, 768);
-            }
-        }
-        for i in 0..self.activations[0].data.len() {
-            println!("{} {}", i, self.activations[0].data[i]);
-        }
-    }
-
-    #[inline]
-    pub fn activate(&mut self, piece: u8, color: Color, idx: i8) {
-        let feature_idx = input_number(piece, color == Color::White, idx as i32);
-        for j in 0..self.activations[0].size() {
-            self.activations[0].data[j] += self.weights[0].get(j, feature_idx);
-        }
-    }
-
-    #[inline]
-    pub fn deactivate(&mut self, piece: u8, color: Color, idx: i8) {
-        let feature_idx = input_number(piece, color == Color::White, idx as i32);
-        for j in 0..self.activations[0].size() {
-            self.activations[0].data[j] -= self.weights[0].get(j, feature_idx);
-        }
-    }
-
-    #[inline]
-    pub fn move_piece(&mut self, piece: u8, color: Color, start: i8, end: i8) {
-        self.deactivate(piece, color, start);
-        self.activate(piece, color, end);
-    }
-
-    pub fn black_turn(&mut self) {
-        for j in 0..self.activations[0].size() {
-            self.activations[0].data[j] -= self.weights[0].get(j, 768);
-        }
-    }
-
-    pub fn white_turn(&mut self) {
-        for j in 0..self.activations[0].size() {
-            self.activations[0].data[j] += self.weights[0].get(j, 768);
+            self.set_bb_activations(pos.pawn[me], PAWN, is_white, [wkr, bkr], ofs);
+            self.set_bb_activations(pos.knight[me], KNIGHT, is_white, [wkr, bkr], ofs);
+            self.set_bb_activations(pos.bishop[me], BISHOP, is_white, [wkr, bkr], ofs);
+            self.set_bb_activations(pos.rook[me], ROOK, is_white, [wkr, bkr], ofs);
+            self.set_bb_activations(pos.queen[me], QUEEN, is_white, [wkr, bkr], ofs);
+            self.set_bb_activations(pos.king[me], KING, is_white, [wkr, bkr], ofs);
         }
     }
 
-    pub fn nnue_eval(&mut self) -> i32 {
-        // assumes that inputs already updated and so on
-        let mut output = 0.0;
+    // assumes set_activations was called for pos
+    pub fn nnue_eval(&mut self, pos: &Bitboard) -> i32 {
         for layer in 1..self.activations.len() {
             for i in 0..self.activations[layer].size() {
-                self.activations[layer].data[i] = 0.0;
+                let mut total = self.biases[layer].data[i];
                 for j in 0..self.activations[layer-1].size() {
-                    self.activations[layer].data[i] += relu(self.activations[layer-1].data[j]) * self.weights[layer].get(i, j);
-                }
-
-                if layer != self.activations.len() - 1 {
-                    self.activations[layer].data[i] = self.activations[layer].data[i] + self.biases[layer].data[i];
-                } else {
-                    output = self.activations[layer].data[i] + self.biases[layer].data[i];
+                    total += relu(self.activations[layer-1].data[j]) * self.weights[layer].get(i, j);
                 }
+                self.activations[layer].data[i] = total;
             }
         }
-        return (output * 10.0).floor() as i32;
+
+        let last = &self.activations[self.activations.len() - 1];
+        let num_pieces = pos.composite[0].count_ones() + pos.composite[1].count_ones();
+        let mut output = last.data[output_bucket(num_pieces, last.size())];
+        if pos.side_to_move == Color::Black {output *= -1.0;}
+        return (output * 9.0).floor() as i32;
     }
 
     pub fn full_eval(&mut self, pos: &Bitboard) -> i32 {
         self.set_activations(pos);
-        return self.nnue_eval();
+        return self.nnue_eval(pos);
     }
 
     pub fn save_image(&self, file: &str) -> std::io::Result<()> {
@@ -398,290 +525,386 @@ impl SlowNetwork {
     }
 }
 
-#[repr(align(32))]
+// the accumulator is int16 neurons in chunks of 16, the first half from
+// white's point of view and the second from black's
+type Chunk = i16x16;
+
+// the first layer after the accumulator runs on int16 too.  its weights are
+// scaled so that no lane of the int32 sum in nnue_eval can overflow
+fn max_layer_weight(chunks: usize) -> f32 {
+    return (i32::MAX as f32 / (chunks as f32 * i16::MAX as f32)).floor();
+}
+
+// float weights as they come out of a file, before quantization.  feature
+// rows hold both perspectives, the second half being the flipped input
+struct FloatNet {
+    shape: NetShape,
+    features: Vec<f32>,
+    biases: Vec<f32>,
+    layers: Vec<(Vec<f32>, Vec<f32>)>   // weights by [neuron][input], biases
+}
+
+impl FloatNet {
+    fn new(shape: NetShape) -> FloatNet {
+        let width = shape.width;
+        let mut layers = Vec::new();
+        let mut inputs = 2 * width;
+        for &n in shape.layers.iter() {
+            layers.push((vec![0.0; n * inputs], vec![0.0; n]));
+            inputs = n;
+        }
+        return FloatNet {
+            features: vec![0.0; 768 * shape.buckets * 2 * width],
+            biases: vec![0.0; 2 * width],
+            layers: layers,
+            shape: shape
+        };
+    }
+
+    // a weight from input row (bucket, j) to neuron i, which the other
+    // perspective sees on the flipped input
+    fn set_feature(&mut self, bucket: usize, j: usize, i: usize, x: f32) {
+        let width = self.shape.width;
+        self.features[(bucket * 768 + j) * 2 * width + i] = x;
+        self.features[(bucket * 768 + flip_input(j as i16) as usize) * 2 * width + width + i] = x;
+    }
+
+    fn set_bias(&mut self, i: usize, x: f32) {
+        self.biases[i] = x;
+        self.biases[i + self.shape.width] = x;
+    }
+}
+
+// the largest value each neuron can reach in any position: the bias plus the
+// 32 largest weights of the bucket, since there are never more than 32 pieces
+fn accumulator_bound(net: &FloatNet) -> f32 {
+    let width = net.shape.width;
+    let mut bound: f32 = 0.0;
+    let mut column = vec![0.0; 768];
+    for i in 0..width {
+        for k in 0..net.shape.buckets {
+            for (j, c) in column.iter_mut().enumerate() {
+                *c = net.features[(k * 768 + j) * 2 * width + i].abs();
+            }
+            column.select_nth_unstable_by(31, |a: &f32, b: &f32| b.total_cmp(a));
+            let total = net.biases[i].abs() + column[..32].iter().sum::<f32>();
+            bound = bound.max(total);
+        }
+    }
+    return bound;
+}
+
+fn quantize_chunks(weights: &[f32], scale: f32) -> Vec<Chunk> {
+    return weights.chunks(16).map(|c| {
+        let mut q = [0; 16];
+        for j in 0..16 {
+            q[j] = (c[j] * scale).round() as i16;
+        }
+        Chunk::from_array(q)
+    }).collect();
+}
+
+fn max_abs(weights: &[f32]) -> f32 {
+    return weights.iter().fold(0.0, |m: f32, w| m.max(w.abs()));
+}
+
+// everything about a net that doesn't change as it's used, shared between
+// every board using it
+pub struct NetParams {
+    pub shape: NetShape,
+    feature_weights: Vec<Chunk>,    // a row of chunks per input
+    feature_biases: Vec<Chunk>,
+    l1_weights: Vec<Chunk>,         // a row of chunks per neuron
+    l1_biases: Vec<f32>,
+    l1_scale: f32,                  // undoes both quantization scales on the l1 sums
+    layers: Vec<(Vec<f32>, Vec<f32>)>
+}
+
+impl NetParams {
+    // converts float weights to int16.  the feature scale is as large as it
+    // can be without any accumulator overflowing, and the first layer's
+    // weights get whatever scale keeps its sums in range.  anything after
+    // that stays float
+    fn quantize(net: FloatNet) -> NetParams {
+        let bound = accumulator_bound(&net);
+        let feature_scale = if bound > 0.0 { i16::MAX as f32 / bound } else { 1.0 };
+        let mut layers = net.layers;
+        let (l1_weights, l1_biases) = layers.remove(0);
+        let chunks = 2 * net.shape.width / 16;
+        let max_weight = max_abs(&l1_weights);
+        let l1_scale = if max_weight > 0.0 { max_layer_weight(chunks) / max_weight } else { 1.0 };
+
+        return NetParams {
+            feature_weights: quantize_chunks(&net.features, feature_scale),
+            feature_biases: quantize_chunks(&net.biases, feature_scale),
+            l1_weights: quantize_chunks(&l1_weights, l1_scale),
+            l1_biases: l1_biases,
+            l1_scale: 1.0 / (feature_scale * l1_scale),
+            layers: layers,
+            shape: net.shape
+        };
+    }
+
+    fn empty() -> NetParams {
+        NetParams {
+            shape: NetShape::default_shape(),
+            feature_weights: Vec::new(),
+            feature_biases: Vec::new(),
+            l1_weights: Vec::new(),
+            l1_biases: Vec::new(),
+            l1_scale: 0.0,
+            layers: Vec::new()
+        }
+    }
+
+    fn chunks(&self) -> usize {
+        return self.feature_biases.len();
+    }
+}
+
+// the features a move added and removed, by input number without the king
+// bucket.  a move changes at most two of each (castling, capture promotions)
+#[derive(Clone, Copy)]
+struct Delta {
+    added: [usize; 2],
+    removed: [usize; 2],
+    num_added: usize,
+    num_removed: usize
+}
+
+impl Delta {
+    fn new() -> Delta {
+        Delta {
+            added: [0; 2],
+            removed: [0; 2],
+            num_added: 0,
+            num_removed: 0
+        }
+    }
+}
+
+// one ply of the accumulator stack.  the values of a perspective are only
+// filled in once an evaluation needs them, until then the entry is just the
+// delta from the ply before
+#[derive(Clone)]
+struct StackEntry {
+    values: Vec<Chunk>,
+    computed: [bool; 2],
+    regions: [usize; 2],    // king buckets as input offsets, white then black
+    delta: Delta
+}
+
+impl StackEntry {
+    fn new(chunks: usize) -> StackEntry {
+        StackEntry {
+            values: vec![all_zeros(); chunks],
+            computed: [false; 2],
+            regions: [0; 2],
+            delta: Delta::new()
+        }
+    }
+}
+
+// the last accumulator half computed for a perspective and king bucket,
+// along with the pieces it was computed from.  a king changing bucket
+// starts from here and only applies the pieces that differ
+#[derive(Clone)]
+struct CacheEntry {
+    values: Vec<Chunk>,
+    pieces: [[u64; 6]; 2]
+}
+
 pub struct Network {
-    pub feature_weights: Vec<[f32x8; 64]>,
-    pub hidden_weights: [f32x8; 64],
-    pub hidden_biases: [f32x8; 64],
-    pub hidden_activations: [f32x8; 64],
-    pub output_bias: f32,
-    pub flip: bool
+    pub params: Arc<NetParams>,
+    pub flip: bool,
+    stack: Vec<StackEntry>,
+    top: usize,
+    // filled in on the first refresh, copies start without one
+    cache: Vec<CacheEntry>
+}
+
+fn all_zeros() -> Chunk {
+    Chunk::splat(0)
+}
+
+#[inline]
+fn add_feature(values: &mut [Chunk], weights: &[Chunk]) {
+    for (v, w) in values.iter_mut().zip(weights.iter()) {
+        *v += *w;
+    }
 }
 
-fn all_zeros() -> f32x8 {
-    f32x8::from_array([0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0])
+#[inline]
+fn sub_feature(values: &mut [Chunk], weights: &[Chunk]) {
+    for (v, w) in values.iter_mut().zip(weights.iter()) {
+        *v -= *w;
+    }
 }
 
 impl Network {
-    pub fn empty_net() -> Network {
+    fn from_params(params: NetParams) -> Network {
+        let chunks = params.chunks();
         Network {
-            feature_weights: vec![[all_zeros(); 64]; 769],
-            hidden_weights: [all_zeros(); 64],
-            hidden_biases: [all_zeros(); 64],
-            hidden_activations: [all_zeros(); 64],
-            output_bias: 0.0,
-            flip: false
+            params: Arc::new(params),
+            flip: false,
+            stack: vec![StackEntry::new(chunks)],
+            top: 0,
+            cache: Vec::new()
         }
     }
 
+    pub fn empty_net() -> Network {
+        return Network::from_params(NetParams::empty());
+    }
+
     pub fn copy(&self) -> Network {
         Network {
-            feature_weights: self.feature_weights.to_vec(),
-            hidden_weights: self.hidden_weights,
-            hidden_biases: self.hidden_biases,
-            hidden_activations: self.hidden_activations,
-            output_bias: self.output_bias,
-            flip: self.flip
+            params: Arc::clone(&self.params),
+            flip: self.flip,
+            stack: self.stack[..=self.top].to_vec(),
+            top: self.top,
+            cache: Vec::new()
         }
     }
 
     pub fn is_valid(&self) -> bool {true}
 
+    pub fn shape(&self) -> &NetShape {
+        return &self.params.shape;
+    }
+
+    // the input offset of a king's bucket, from its own side of the board
+    pub fn king_region(&self, idx: i8) -> usize {
+        return king_bucket(self.params.shape.buckets, idx) * 768;
+    }
+
     pub unsafe fn load_default() -> Network {
-        // inputs
-        let mut feature_weights = vec![[all_zeros(); 64]; 3072];
-        let mut weights: Vec<[f32; 512]> = vec![[0.0; 512]; 3072];
+        let mut net = FloatNet::new(NetShape::default_shape());
+        // the first 128 neurons have weights for each region, the rest are
+        // the same in all of them
         for k in 0..4 {
             for j in 0..768 {
                 for i in 0..128 {
-                    let weight = DEFAULT_NNUE_FEATURE_WEIGHTS[(k * 768 + j)*256 + i] as f32;
-                    weights[k * 768 + j][i] = weight;
-                    weights[k * 768 + flip_input(j as i16) as usize][i + 256] = weight;
+                    net.set_feature(k, j, i, DEFAULT_NNUE_FEATURE_WEIGHTS[(k * 768 + j)*256 + i]);
                 }
                 for i in 128..256 {
-                    let weight = DEFAULT_NNUE_FEATURE_WEIGHTS[j * 256 + i] as f32;
-                    weights[k * 768 + j][i] = weight;
-                    weights[k * 768 + flip_input(j as i16) as usize][i + 256] = weight;
+                    net.set_feature(k, j, i, DEFAULT_NNUE_FEATURE_WEIGHTS[j * 256 + i]);
                 }
             }
         }
-
-        for i in 0..3072 {
-            for j in 0..64 {
-                feature_weights[i][j] = f32x8::from_slice(&weights[i][j*8..j*8+8]);
-            }
+        for (i, b) in DEFAULT_NNUE_HIDDEN_BIAS.iter().enumerate() {
+            net.set_bias(i, *b);
         }
+        net.layers[0].0.copy_from_slice(&DEFAULT_NNUE_HIDDEN_WEIGHTS);
+        net.layers[0].1[0] = DEFAULT_NNUE_OUTPUT_BIAS;
 
-        let mut hidden_biases = [all_zeros(); 64];
-        let mut biases = [0.0; 512];
-        for i in 0..512 {
-            biases[i] = DEFAULT_NNUE_HIDDEN_BIAS[i % 256];
-        }
-        for i in 0..64 {
-            hidden_biases[i] = f32x8::from_slice(&biases[i*8..i*8+8]);
-        }
+        return Network::from_params(NetParams::quantize(net));
+    }
 
-        let mut hidden_weights = [all_zeros(); 64];
-        let mut weights = [0.0; 512];
-        for i in 0..512 {
-            weights[i] = DEFAULT_NNUE_HIDDEN_WEIGHTS[i];
-        }
-        for i in 0..64 {
-            hidden_weights[i] = f32x8::from_slice(&weights[i*8..i*8+8]);
+    pub fn load(fname: &str) -> Result<Network, String> {
+        let data = match std::fs::read(fname) {
+            Ok(d) => d,
+            Err(e) => { return Err(format!("unable to read {}: {}", fname, e)); }
+        };
+        let header = match read_bz_header(&mut &data[..]) {
+            Ok(h) => h,
+            Err(e) => { return Err(format!("{}: {}", fname, e)); }
+        };
+        let shape = match NetShape::from_header(&header) {
+            Ok(s) => s,
+            Err(e) => { return Err(format!("{}: {}", fname, e)); }
+        };
+        let expected = header.size() + 4 * shape.num_params();
+        if data.len() != expected {
+            return Err(format!("{}: expected {} bytes for a {} network, found {}", fname, expected, shape.describe(), data.len()));
         }
-
-        let output_bias = DEFAULT_NNUE_OUTPUT_BIAS;
-
-        let network = Network {
-            feature_weights: feature_weights,
-            hidden_weights: hidden_weights,
-            hidden_biases: hidden_biases,
-            hidden_activations: [all_zeros(); 64],
-            output_bias: output_bias,
-            flip: false
+        let mut words = data[header.size()..].chunks_exact(4).map(|w| f32::from_le_bytes([w[0], w[1], w[2], w[3]]));
+        let mut next = || -> Result<f32, String> {
+            return match words.next() {
+                Some(x) if x.is_finite() => Ok(x),
+                _ => Err(format!("{}: bad weight", fname))
+            };
         };
 
-        return network;
-    }
-
-    pub unsafe fn load(fname: &str) -> std::io::Result<Network> {
-        let mut file = File::open(fname)?;
-
-        // We'll read one 4-byte "word" at a time
-        let mut buf: [u8; 4] = [0; 4];
-
-        // magic number
-        file.read(&mut buf)?;
-        if buf[0..2] != [66, 90] { panic!("Magic word does not match expected 'BZ'"); }
-        if buf[2..4] != [2, 0] { panic!("SlowNetwork binary format version is not supported."); }
-
-        // network id
-        file.read(&mut buf)?;
-        let _id = u32::from_le_bytes(buf);
-        // println!("loading network with id {}", id);
-
-        // topology information
-        file.read(&mut buf)?;
-        let _inputs = u32::from_le_bytes(buf);
-        file.read(&mut buf)?;
-        let _outputs = u32::from_le_bytes(buf);
-        file.read(&mut buf)?;
-        let layers = u32::from_le_bytes(buf);
-
-        // hidden neuron counts in layers
-        let mut hidden: Vec<u32> = Vec::new();
-        for _ in 0..layers {
-            file.read(&mut buf)?;
-            hidden.push(u32::from_le_bytes(buf));
-        }
-
-        // inputs
-        let mut feature_weights = vec![[all_zeros(); 64]; 3072];
-        let mut weights = vec![[0.0; 512]; 3072];
-        for k in 0..4 {
+        // only the first perspective is read, the second is its mirror image
+        let mut net = FloatNet::new(shape);
+        let width = net.shape.width;
+        for k in 0..net.shape.buckets {
             for j in 0..768 {
-                for i in 0..512 {
-                    file.read(&mut buf)?;
-                    if i < 256 {
-                        weights[k * 768 + j][i] = f32::from_le_bytes(buf);
-                        weights[k * 768 + flip_input(j as i16) as usize][i + 256] = f32::from_le_bytes(buf);
+                for i in 0..2 * width {
+                    let x = next()?;
+                    if i < width {
+                        net.set_feature(k, j, i, x);
                     }
                 }
             }
         }
-
-        for i in 0..3072 {
-            for j in 0..64 {
-                feature_weights[i][j] = f32x8::from_slice(&weights[i][j*8..j*8+8]);
+        for i in 0..2 * width {
+            let x = next()?;
+            if i < width {
+                net.set_bias(i, x);
             }
         }
 
-        let mut hidden_biases = [all_zeros(); 64];
-        let mut biases = [0.0; 512];
-        for i in 0..512 {
-            file.read(&mut buf)?;
-            if i < 256 {
-                biases[i] = f32::from_le_bytes(buf);
-                biases[i+256] = f32::from_le_bytes(buf);
+        // the rest are stored input by input
+        let mut inputs = 2 * width;
+        for (weights, biases) in net.layers.iter_mut() {
+            let neurons = biases.len();
+            for j in 0..inputs {
+                for i in 0..neurons {
+                    weights[i * inputs + j] = next()?;
+                }
             }
+            for b in biases.iter_mut() {
+                *b = next()?;
+            }
+            inputs = neurons;
         }
-        for i in 0..64 {
-            hidden_biases[i] = f32x8::from_slice(&biases[i*8..i*8+8]);
-        }
-
-        let mut hidden_weights = [all_zeros(); 64];
-        let mut weights = [0.0; 512];
-        for i in 0..512 {
-            file.read(&mut buf)?;
-            weights[i] = f32::from_le_bytes(buf);
-        }
-        for i in 0..64 {
-            hidden_weights[i] = f32x8::from_slice(&weights[i*8..i*8+8]);
-        }
-
-        file.read(&mut buf)?;
-        let output_bias = f32::from_le_bytes(buf);
-
-        let network = Network {
-            feature_weights: feature_weights,
-            hidden_weights: hidden_weights,
-            hidden_biases: hidden_biases,
-            hidden_activations: [all_zeros(); 64],
-            output_bias: output_bias,
-            flip: false
-        };
 
-        return Ok(network);
+        return Ok(Network::from_params(NetParams::quantize(net)));
     }
 
-    #[inline]
-    pub fn activate(&mut self, piece: u8, color: Color, idx: i8, wkr: usize, bkr: usize) {
-        let inp = input_number(piece, color == Color::White, idx as i32);
-        // TODO note to self: make sure that flipping happens _within_ each kr
-        let feature_idx = inp + wkr as usize;
-        let flipped_idx = inp + bkr as usize;
-        for j in 0..8 {
-            let j = j * 4;
-            self.hidden_activations[j] += self.feature_weights[feature_idx][j];
-            self.hidden_activations[j+1] += self.feature_weights[feature_idx][j+1];
-            self.hidden_activations[j+2] += self.feature_weights[feature_idx][j+2];
-            self.hidden_activations[j+3] += self.feature_weights[feature_idx][j+3];
-            self.hidden_activations[j+32] += self.feature_weights[flipped_idx][j+32];
-            self.hidden_activations[j+33] += self.feature_weights[flipped_idx][j+33];
-            self.hidden_activations[j+34] += self.feature_weights[flipped_idx][j+34];
-            self.hidden_activations[j+35] += self.feature_weights[flipped_idx][j+35];
+    // a new ply on the accumulator stack, starting out as an empty delta
+    // from the ply before with the same king buckets
+    pub fn push(&mut self) {
+        let regions = self.stack[self.top].regions;
+        self.top += 1;
+        if self.top == self.stack.len() {
+            self.stack.push(StackEntry::new(self.params.chunks()));
         }
+        let entry = &mut self.stack[self.top];
+        entry.computed = [false; 2];
+        entry.regions = regions;
+        entry.delta = Delta::new();
     }
 
-    #[inline]
-    pub fn deactivate(&mut self, piece: u8, color: Color, idx: i8, wkr: usize, bkr: usize) {
-        let inp = input_number(piece, color == Color::White, idx as i32);
-        // TODO note to self: make sure that flipping happens _within_ each kr
-        let feature_idx = inp + wkr as usize;
-        let flipped_idx = inp + bkr as usize;
-        for j in 0..8 {
-            let j = j * 4;
-            self.hidden_activations[j] -= self.feature_weights[feature_idx][j];
-            self.hidden_activations[j+1] -= self.feature_weights[feature_idx][j+1];
-            self.hidden_activations[j+2] -= self.feature_weights[feature_idx][j+2];
-            self.hidden_activations[j+3] -= self.feature_weights[feature_idx][j+3];
-            self.hidden_activations[j+32] -= self.feature_weights[flipped_idx][j+32];
-            self.hidden_activations[j+33] -= self.feature_weights[flipped_idx][j+33];
-            self.hidden_activations[j+34] -= self.feature_weights[flipped_idx][j+34];
-            self.hidden_activations[j+35] -= self.feature_weights[flipped_idx][j+35];
-        }
+    pub fn pop(&mut self) {
+        if self.top == 0 { panic!("empty activation stack!"); }
+        self.top -= 1;
     }
 
-    pub fn clear_kr(&mut self) {
-        for i in 0..16 {
-            self.hidden_activations[i] = self.hidden_biases[i];
-        }
-        for i in 32..48 {
-            self.hidden_activations[i] = self.hidden_biases[i];
-        }
-    }
-
-    pub fn set_kr_bb_activations(&mut self, bb: u64, piece_num: u8, is_white: bool, wkr: usize, bkr: usize) {
-        let mut bb = bb;
-        while bb != 0 {
-            let idx = bb.trailing_zeros() as i8;
-            bb &= bb - 1;
-            self.activate_kr(piece_num, if is_white {Color::White} else {Color::Black}, idx, wkr, bkr);
-        }
+    pub fn set_regions(&mut self, wkr: usize, bkr: usize) {
+        self.stack[self.top].regions = [wkr, bkr];
     }
 
     #[inline]
-    pub fn activate_kr(&mut self, piece: u8, color: Color, idx: i8, wkr: usize, bkr: usize) {
-        let inp = input_number(piece, color == Color::White, idx as i32);
-        // TODO note to self: make sure that flipping happens _within_ each kr
-        let feature_idx = inp + wkr as usize;
-        let flipped_idx = inp + bkr as usize;
-        for j in 0..4 {
-            let j = j * 4;
-            self.hidden_activations[j] += self.feature_weights[feature_idx][j];
-            self.hidden_activations[j+1] += self.feature_weights[feature_idx][j+1];
-            self.hidden_activations[j+2] += self.feature_weights[feature_idx][j+2];
-            self.hidden_activations[j+3] += self.feature_weights[feature_idx][j+3];
-            self.hidden_activations[j+32] += self.feature_weights[flipped_idx][j+32];
-            self.hidden_activations[j+33] += self.feature_weights[flipped_idx][j+33];
-            self.hidden_activations[j+34] += self.feature_weights[flipped_idx][j+34];
-            self.hidden_activations[j+35] += self.feature_weights[flipped_idx][j+35];
-        }
+    pub fn activate(&mut self, piece: u8, color: Color, idx: i8) {
+        let delta = &mut self.stack[self.top].delta;
+        delta.added[delta.num_added] = input_number(piece, color == Color::White, idx as i32);
+        delta.num_added += 1;
     }
 
     #[inline]
-    pub fn deactivate_kr(&mut self, piece: u8, color: Color, idx: i8, wkr: usize, bkr: usize) {
-        let inp = input_number(piece, color == Color::White, idx as i32);
-        // TODO note to self: make sure that flipping happens _within_ each kr
-        let feature_idx = inp + wkr as usize;
-        let flipped_idx = inp + bkr as usize;
-        for j in 0..4 {
-            let j = j * 4;
-            self.hidden_activations[j] -= self.feature_weights[feature_idx][j];
-            self.hidden_activations[j+1] -= self.feature_weights[feature_idx][j+1];
-            self.hidden_activations[j+2] -= self.feature_weights[feature_idx][j+2];
-            self.hidden_activations[j+3] -= self.feature_weights[feature_idx][j+3];
-            self.hidden_activations[j+32] -= self.feature_weights[flipped_idx][j+32];
-            self.hidden_activations[j+33] -= self.feature_weights[flipped_idx][j+33];
-            self.hidden_activations[j+34] -= self.feature_weights[flipped_idx][j+34];
-            self.hidden_activations[j+35] -= self.feature_weights[flipped_idx][j+35];
-        }
+    pub fn deactivate(&mut self, piece: u8, color: Color, idx: i8) {
+        let delta = &mut self.stack[self.top].delta;
+        delta.removed[delta.num_removed] = input_number(piece, color == Color::White, idx as i32);
+        delta.num_removed += 1;
     }
 
     #[inline]
-    pub fn move_piece(&mut self, piece: u8, color: Color, start: i8, end: i8, wkr: usize, bkr: usize) {
-        self.deactivate(piece, color, start, wkr, bkr);
-        self.activate(piece, color, end, wkr, bkr);
+    pub fn move_piece(&mut self, piece: u8, color: Color, start: i8, end: i8) {
+        self.deactivate(piece, color, start);
+        self.activate(piece, color, end);
     }
 
     pub fn black_turn(&mut self) {
@@ -692,64 +915,188 @@ impl Network {
         self.flip = false;
     }
 
-    pub fn set_bb_activations(&mut self, bb: u64, piece_num: u8, is_white: bool, wkr: usize, bkr: usize) {
-        let mut bb = bb;
-        while bb != 0 {
-            let idx = bb.trailing_zeros() as i8;
-            bb &= bb - 1;
-            self.activate(piece_num, if is_white {Color::White} else {Color::Black}, idx, wkr, bkr);
+    // brings one perspective of the top of the stack up to date, applying the
+    // deltas since the last computed ply.  if the king changed bucket since
+    // then the deltas are no use and the half is refreshed from the cache
+    fn update(&mut self, p: usize, pieces: &[[u64; 6]; 2]) {
+        let top = self.top;
+        if self.stack[top].computed[p] { return; }
+        let region = self.stack[top].regions[p];
+        let mut i = top;
+        loop {
+            if i == 0 || self.stack[i - 1].regions[p] != region {
+                self.refresh(p, pieces);
+                return;
+            }
+            if self.stack[i - 1].computed[p] { break; }
+            i -= 1;
         }
-    }
 
-    pub fn set_activations(&mut self, pos: &Bitboard) {
-        for i in 0..64 {
-            self.hidden_activations[i] = self.hidden_biases[i];
+        let params = &*self.params;
+        let chunks = params.chunks();
+        let half = p * chunks / 2..(p + 1) * chunks / 2;
+        for j in i..=top {
+            let (before, after) = self.stack.split_at_mut(j);
+            let prev = &before[j - 1];
+            let entry = &mut after[0];
+            entry.values[half.clone()].copy_from_slice(&prev.values[half.clone()]);
+            let delta = entry.delta;
+            for &inp in &delta.added[..delta.num_added] {
+                let row = (inp + region) * chunks;
+                add_feature(&mut entry.values[half.clone()], &params.feature_weights[row + half.start..row + half.end]);
+            }
+            for &inp in &delta.removed[..delta.num_removed] {
+                let row = (inp + region) * chunks;
+                sub_feature(&mut entry.values[half.clone()], &params.feature_weights[row + half.start..row + half.end]);
+            }
+            entry.computed[p] = true;
         }
+    }
 
-        let wkr = region(pos.king[1].trailing_zeros() as i8) as usize * 768;
-        let bkr = region((pos.king[0].trailing_zeros() ^ 56) as i8) as usize * 768;
-        for side in [Color::White, Color::Black] {
-            let is_white = side == Color::White;
-            let me = side as usize;
+    fn refresh(&mut self, p: usize, pieces: &[[u64; 6]; 2]) {
+        let params = &*self.params;
+        let chunks = params.chunks();
+        let half = p * chunks / 2..(p + 1) * chunks / 2;
+        if self.cache.is_empty() {
+            // every entry starts out as an empty board
+            for side in 0..2 {
+                let values = params.feature_biases[side * chunks / 2..(side + 1) * chunks / 2].to_vec();
+                for _ in 0..params.shape.buckets {
+                    self.cache.push(CacheEntry { values: values.clone(), pieces: [[0; 6]; 2] });
+                }
+            }
+        }
 
-            self.set_bb_activations(pos.pawn[me], PAWN, is_white, wkr, bkr);
-            self.set_bb_activations(pos.knight[me], KNIGHT, is_white, wkr, bkr);
-            self.set_bb_activations(pos.bishop[me], BISHOP, is_white, wkr, bkr);
-            self.set_bb_activations(pos.rook[me], ROOK, is_white, wkr, bkr);
-            self.set_bb_activations(pos.queen[me], QUEEN, is_white, wkr, bkr);
-            self.set_bb_activations(pos.king[me], KING, is_white, wkr, bkr);
+        let region = self.stack[self.top].regions[p];
+        let cached = &mut self.cache[p * params.shape.buckets + region / 768];
+        for (color, boards) in pieces.iter().enumerate() {
+            for (piece, &now) in boards.iter().enumerate() {
+                let before = cached.pieces[color][piece];
+                let mut added = now & !before;
+                while added != 0 {
+                    let idx = added.trailing_zeros() as i32;
+                    added &= added - 1;
+                    let row = (input_number(piece as u8, color == Color::White as usize, idx) + region) * chunks;
+                    add_feature(&mut cached.values, &params.feature_weights[row + half.start..row + half.end]);
+                }
+                let mut removed = before & !now;
+                while removed != 0 {
+                    let idx = removed.trailing_zeros() as i32;
+                    removed &= removed - 1;
+                    let row = (input_number(piece as u8, color == Color::White as usize, idx) + region) * chunks;
+                    sub_feature(&mut cached.values, &params.feature_weights[row + half.start..row + half.end]);
+                }
+            }
         }
+        cached.pieces = *pieces;
+        let entry = &mut self.stack[self.top];
+        entry.values[half].copy_from_slice(&cached.values);
+        entry.computed[p] = true;
+    }
+
+    // starts the stack over from a position
+    pub fn reset(&mut self, pieces: &[[u64; 6]; 2], wkr: usize, bkr: usize, side_to_move: Color) {
+        self.top = 0;
+        self.stack[0].regions = [wkr, bkr];
+        self.stack[0].computed = [false; 2];
+        self.refresh(0, pieces);
+        self.refresh(1, pieces);
 
-        if pos.side_to_move == Color::White {
+        if side_to_move == Color::White {
             self.white_turn();
         } else {
             self.black_turn();
         }
     }
 
-    pub fn nnue_eval(&mut self) -> i32 {
-        // assumes that inputs already updated and so on
-        let mut output = self.output_bias;
-        let mut total = all_zeros();
-        let relu_zeros = all_zeros();
-        for i in 0..64 {
-            let idx = if self.flip { i ^ 32 } else { i };
-            let relud = self.hidden_activations[idx].simd_max(relu_zeros);
+    pub fn set_activations(&mut self, pos: &Bitboard) {
+        let wkr = self.king_region(pos.king[1].trailing_zeros() as i8);
+        let bkr = self.king_region((pos.king[0].trailing_zeros() ^ 56) as i8);
+        self.reset(&pos.piece_boards(), wkr, bkr, pos.side_to_move);
+    }
 
-            total += relud * self.hidden_weights[i];
+    // one neuron of the first layer, side to move's half first
+    #[inline]
+    fn l1_neuron(&self, accumulator: &[Chunk], neuron: usize) -> f32 {
+        let params = &*self.params;
+        let half = accumulator.len() / 2;
+        let (us, them) = if self.flip { (half, 0) } else { (0, half) };
+        let weights = &params.l1_weights[neuron * 2 * half..(neuron + 1) * 2 * half];
+        let relu_zeros = all_zeros();
+        let mut total = i32x16::splat(0);
+        for i in 0..half {
+            total += accumulator[us + i].simd_max(relu_zeros).cast::<i32>() * weights[i].cast::<i32>();
+            total += accumulator[them + i].simd_max(relu_zeros).cast::<i32>() * weights[half + i].cast::<i32>();
         }
-        output += total.reduce_sum();
+        return params.l1_biases[neuron] + total.cast::<i64>().reduce_sum() as f32 * params.l1_scale;
+    }
+
+    pub fn nnue_eval(&mut self, pieces: &[[u64; 6]; 2]) -> i32 {
+        self.update(0, pieces);
+        self.update(1, pieces);
+
+        let params = &*self.params;
+        let accumulator = &self.stack[self.top].values;
+        let num_pieces = pieces.iter().flatten().map(|bb| bb.count_ones()).sum();
+        let bucket = output_bucket(num_pieces, params.shape.outputs());
+        let mut output = if params.layers.is_empty() {
+            self.l1_neuron(accumulator, bucket)
+        } else {
+            let mut values = [0.0; MAX_LAYER_SIZE];
+            let mut inputs = params.l1_biases.len();
+            for (i, v) in values[..inputs].iter_mut().enumerate() {
+                *v = self.l1_neuron(accumulator, i);
+            }
+            let last = params.layers.len() - 1;
+            for (weights, biases) in params.layers[..last].iter() {
+                let mut next = [0.0; MAX_LAYER_SIZE];
+                for (i, n) in next[..biases.len()].iter_mut().enumerate() {
+                    let row = &weights[i * inputs..(i + 1) * inputs];
+                    *n = biases[i] + row.iter().zip(values.iter()).map(|(w, v)| w * relu(*v)).sum::<f32>();
+                }
+                values = next;
+                inputs = biases.len();
+            }
+            // only the output for this position's bucket
+            let (weights, biases) = &params.layers[last];
+            let row = &weights[bucket * inputs..(bucket + 1) * inputs];
+            biases[bucket] + row.iter().zip(values.iter()).map(|(w, v)| w * relu(*v)).sum::<f32>()
+        };
 
         if self.flip {output *= -1.0;}
         return (output * 9.0).floor() as i32;
     }
 
+    // what each piece other than the kings adds to the eval, found by
+    // evaluating without it.  this runs on a copy so the stack is left alone
+    pub fn piece_contributions(&self, board: &Bitboard) -> Vec<(i8, i32)> {
+        let pieces = board.piece_boards();
+        let (wkr, bkr) = board.king_regions();
+        let mut scratch = self.copy();
+        scratch.reset(&pieces, wkr, bkr, board.side_to_move);
+        let base_score = scratch.nnue_eval(&pieces);
+        let mut contributions = Vec::new();
+        for color in 0..2 {
+            for piece in PAWN..KING {
+                let mut bb = pieces[color][piece as usize];
+                while bb != 0 {
+                    let idx = bb.trailing_zeros() as i8;
+                    bb &= bb - 1;
+                    let mut without = pieces;
+                    without[color][piece as usize] ^= 1u64 << idx;
+                    scratch.reset(&without, wkr, bkr, board.side_to_move);
+                    contributions.push((idx, base_score - scratch.nnue_eval(&without)));
+                }
+            }
+        }
+        contributions.sort_by_key(|c| c.0);
+        return contributions;
+    }
+
     pub fn print_eval(&mut self, board: &Bitboard) {
-        let base_score = self.nnue_eval() as f32 / 1000.0;
-        let wkr = region(board.king[1].trailing_zeros() as i8) as usize * 768;
-        let bkr = region((board.king[0].trailing_zeros() ^ 56) as i8) as usize * 768;
-        // let wkr = region(board.king[1].trailing_zeros() as i8);
-        // let bkr = region((board.king[0].trailing_zeros() ^ 56) as i8);
+        let pieces = board.piece_boards();
+        let base_score = self.nnue_eval(&pieces) as f32 / 1000.0;
+        let contributions = self.piece_contributions(board);
         eprint!("\x1B[0m");
         for _ in 0..24 { eprint!("\n"); }
         eprint!("\x1B[24A");
@@ -783,18 +1130,10 @@ impl Network {
                 if piece == b'k' {
                     eprint!("       \x1B[7D");
                 } else {
-                    let piece_num = match piece {
-                        b'p' => PAWN,
-                        b'n' => KNIGHT,
-                        b'b' => BISHOP,
-                        b'r' => ROOK,
-                        b'q' => QUEEN,
-                        _ => {panic!("there is no piece here")}
+                    let ofs = match contributions.iter().find(|c| c.0 == idx) {
+                        Some(c) => c.1 as f32 / 1000.0,
+                        None => {panic!("there is no piece here")}
                     };
-                    self.deactivate(piece_num, color, idx, wkr, bkr);
-                    let hypothetical_score = self.nnue_eval() as f32 / 1000.0;
-                    let ofs = base_score - hypothetical_score;
-                    self.activate(piece_num, color, idx, wkr, bkr);
                     if ofs.abs() >= 10.0 {
                         eprint!(" {:+5.1} \x1B[7D", ofs);
                     }
@@ -0,0 +1,123 @@
+use crate::bitboard::*;
+use crate::movegen::*;
+use crate::moveutil::*;
+use crate::nnue::*;
+use crate::pgn::*;
+use crate::rand::*;
+use crate::util::*;
+
+// plays random games with a net loaded both as a Network and as the float
+// SlowNetwork, checking at every node that the incrementally updated eval
+// matches one computed from scratch and that both match the reference
+
+pub struct NnueCheckOptions {
+    pub games: u32,
+    pub plies: u32,
+    pub tolerance: i32,     // allowed difference from the float reference, plus 1% of it
+    pub seed: u64
+}
+
+impl NnueCheckOptions {
+    pub fn default() -> NnueCheckOptions {
+        NnueCheckOptions {
+            games: 100,
+            plies: 200,
+            tolerance: 10,
+            seed: 0
+        }
+    }
+}
+
+struct CheckStats {
+    nodes: u64,
+    worst: i32
+}
+
+// a step of the random walk, so it can be taken back
+enum Step {
+    Move(Move),
+    Null
+}
+
+fn check_node(pos: &mut Bitboard, net: &Network, slow: &mut SlowNetwork, options: &NnueCheckOptions, stats: &mut CheckStats) -> Result<(), String> {
+    let incremental = pos.nnue_eval();
+    // a fresh copy has no refresh cache, so this is built up from nothing
+    let mut fresh = net.copy();
+    fresh.set_activations(pos);
+    let scratch = fresh.nnue_eval(&pos.piece_boards());
+    let reference = slow.full_eval(pos);
+
+    stats.nodes += 1;
+    if incremental != scratch {
+        return Err(format!("incremental eval {} but {} from scratch in {}", incremental, scratch, pos.fen()));
+    }
+    let diff = (incremental - reference).abs();
+    stats.worst = stats.worst.max(diff);
+    // quantization error grows with the size of the eval
+    if diff > options.tolerance + reference.abs() / 100 {
+        return Err(format!("eval {} but the reference gives {} in {}", incremental, reference, pos.fen()));
+    }
+    return Ok(());
+}
+
+fn undo(pos: &mut Bitboard, step: Step) {
+    match step {
+        Step::Move(mv) => pos.undo_move(&mv),
+        Step::Null => pos.undo_null_move()
+    }
+}
+
+// one random walk from the start position.  moves are sometimes taken back
+// or answered with a null move, and at the end everything is unwound, so
+// every way the accumulator stack moves gets exercised
+fn check_game(net: &Network, slow: &mut SlowNetwork, options: &NnueCheckOptions, rng: &mut u64, stats: &mut CheckStats) -> Result<(), String> {
+    let mut pos = Bitboard::from_position(START_FEN.to_string());
+    pos.net = net.copy();
+    pos.set_activations();
+    let mut steps: Vec<Step> = Vec::new();
+
+    check_node(&mut pos, net, slow, options, stats)?;
+    for _ in 0..options.plies {
+        let r = u64::rand_with(rng) % 16;
+        let last_null = matches!(steps.last(), Some(Step::Null));
+        if r < 3 && !steps.is_empty() {
+            let step = steps.pop().unwrap();
+            undo(&mut pos, step);
+        } else if r == 3 && !last_null && !pos.is_check(pos.side_to_move) {
+            pos.do_null_move();
+            steps.push(Step::Null);
+        } else {
+            let legal = legal_moves(&mut pos);
+            if legal.is_empty() { break; }
+            let mv = legal[(u64::rand_with(rng) % legal.len() as u64) as usize];
+            pos.do_move(&mv);
+            steps.push(Step::Move(mv));
+        }
+        check_node(&mut pos, net, slow, options, stats)?;
+    }
+
+    while let Some(step) = steps.pop() {
+        undo(&mut pos, step);
+        check_node(&mut pos, net, slow, options, stats)?;
+    }
+    return Ok(());
+}
+
+pub fn check_nnue(fname: &str, options: &NnueCheckOptions) -> Result<(), String> {
+    let net = Network::load(fname)?;
+    let mut slow = match SlowNetwork::load(fname) {
+        Ok(n) => n,
+        Err(e) => { return Err(format!("unable to load {}: {}", fname, e)); }
+    };
+
+    let mut rng = options.seed;
+    for _ in 0..64 { u64::rand_with(&mut rng); }
+    let mut stats = CheckStats { nodes: 0, worst: 0 };
+    for game in 0..options.games {
+        if let Err(e) = check_game(&net, &mut slow, options, &mut rng, &mut stats) {
+            return Err(format!("game {}: {}", game + 1, e));
+        }
+    }
+    println!("checked {} nodes in {} games of {}, largest difference from the reference {}", stats.nodes, options.games, net.shape().describe(), stats.worst);
+    return Ok(());
+}
@@ -0,0 +1,549 @@
+use std::fs::{File, OpenOptions};
+use std::convert::TryInto;
+use std::io::{BufReader, BufRead, BufWriter, Read, Write, ErrorKind};
+
+use crate::bitboard::*;
+use crate::util::*;
+
+// fixed-size binary training records, 32 bytes each, all little endian:
+//   0..8    occupancy bitboard
+//   8..24   one nibble per occupied square in ascending square order, low
+//           nibble first.  the piece (PAWN..KING) is in the low three bits
+//           and the top bit is set for white
+//   24      castling rights (KQkq bits, same as the board) with bit 7 set
+//           when black is to move
+//   25      en passant file, 0xff for none
+//   26      halfmove clock
+//   27      result: 0 black win, 1 draw, 2 white win
+//   28..30  search score in centipawns from white's point of view
+//   30..32  fullmove number
+// files holding these records end in .bin, anything else is treated as the
+// `fen;score:x;outcome:y` text lines
+
+pub const PACKED_SIZE: usize = 32;
+
+const BLACK_TO_MOVE: u8 = 0x80;
+const WHITE_PIECE: u8 = 0x8;
+const NO_EP: u8 = 0xff;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PackedPosition {
+    pub occupancy: u64,
+    pub pieces: [u8; 16],
+    pub flags: u8,
+    pub ep: u8,
+    pub halfmove: u8,
+    pub result: u8,
+    pub score: i16,
+    pub fullmove: u16
+}
+
+pub fn is_packed_file(fname: &str) -> bool {
+    return fname.ends_with(".bin");
+}
+
+fn outcome_to_result(outcome: f64) -> u8 {
+    return (outcome * 2.0).round().clamp(0.0, 2.0) as u8;
+}
+
+impl PackedPosition {
+    // the board part of a record, with the score and result left empty
+    fn from_parts(pieces: &[[u64; 6]; 2], side_to_move: Color, castling_rights: u8, ep_file: i32, halfmove: u8, fullmove: u16) -> PackedPosition {
+        let mut occupancy = 0;
+        for side in pieces.iter() {
+            for bb in side.iter() {
+                occupancy |= bb;
+            }
+        }
+
+        let mut packed = [0; 16];
+        let mut occ = occupancy;
+        let mut i = 0;
+        while occ != 0 && i < 32 {
+            let sq = occ.trailing_zeros();
+            let bb = 1 << sq;
+            let mut nibble = 0;
+            for (color, side) in pieces.iter().enumerate() {
+                for (piece, piece_bb) in side.iter().enumerate() {
+                    if piece_bb & bb != 0 {
+                        nibble = piece as u8 | if color == Color::White as usize { WHITE_PIECE } else { 0 };
+                    }
+                }
+            }
+            packed[i / 2] |= nibble << (4 * (i % 2));
+            occ &= occ - 1;
+            i += 1;
+        }
+
+        return PackedPosition {
+            occupancy: occupancy,
+            pieces: packed,
+            flags: (castling_rights & 0xf) | if side_to_move == Color::Black { BLACK_TO_MOVE } else { 0 },
+            ep: if ep_file >= 0 { ep_file as u8 } else { NO_EP },
+            halfmove: halfmove,
+            result: 0,
+            score: 0,
+            fullmove: fullmove
+        };
+    }
+
+    pub fn from_board(pos: &Bitboard, score: i32, outcome: f64) -> PackedPosition {
+        let mut pieces = [[0; 6]; 2];
+        for (color, side) in pieces.iter_mut().enumerate() {
+            *side = [pos.pawn[color], pos.knight[color], pos.bishop[color], pos.rook[color], pos.queen[color], pos.king[color]];
+        }
+        // matching the move number Bitboard::fen gives
+        let fullmove = 1 + pos.history.len() / 2;
+        let mut p = PackedPosition::from_parts(&pieces, pos.side_to_move, pos.castling_rights, pos.ep_file, pos.halfmove, fullmove as u16);
+        p.set_score(score);
+        p.set_outcome(outcome);
+        return p;
+    }
+
+    // packs a FEN without going through a Bitboard, which would also have to
+    // set up hashes and the network
+    pub fn from_fen(fen: &str, score: i32, outcome: f64) -> Result<PackedPosition, String> {
+        Bitboard::validate_fen(fen)?;
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+
+        let mut pieces = [[0; 6]; 2];
+        let mut rank = 7;
+        let mut file = 0;
+        for c in fields[0].bytes() {
+            match c {
+                b'/' => { rank -= 1; file = 0; },
+                b'1'..=b'8' => { file += (c - b'0') as i32; },
+                _ => {
+                    let color = if c.is_ascii_uppercase() { Color::White } else { Color::Black } as usize;
+                    let piece = match c.to_ascii_lowercase() {
+                        b'p' => PAWN,
+                        b'n' => KNIGHT,
+                        b'b' => BISHOP,
+                        b'r' => ROOK,
+                        b'q' => QUEEN,
+                        _ => KING
+                    };
+                    pieces[color][piece as usize] |= coord_to_bb((file, rank));
+                    file += 1;
+                }
+            }
+        }
+
+        let side_to_move = if fields[1] == "b" { Color::Black } else { Color::White };
+        let mut castling_rights = 0;
+        for c in fields[2].bytes() {
+            castling_rights |= match c {
+                b'K' => 0b1000,
+                b'Q' => 0b0100,
+                b'k' => 0b0010,
+                b'q' => 0b0001,
+                _ => 0
+            };
+        }
+        let ep_file = if fields[3] == "-" { -1 } else { (fields[3].as_bytes()[0] - b'a') as i32 };
+        let halfmove = fields[4].parse().unwrap_or(0);
+        let fullmove = match fields.get(5) {
+            Some(f) => f.parse().unwrap_or(1),
+            None => 1
+        };
+        let mut p = PackedPosition::from_parts(&pieces, side_to_move, castling_rights, ep_file, halfmove, fullmove);
+        p.set_score(score);
+        p.set_outcome(outcome);
+        return Ok(p);
+    }
+
+    // parses a `fen;score:x;...;outcome:y` line.  other fields are dropped
+    // and a missing score is stored as 0
+    pub fn from_text(line: &str) -> Result<PackedPosition, String> {
+        let mut fields = line.split(';');
+        let fen = fields.next().unwrap_or("").trim();
+        let mut score = 0;
+        let mut outcome = None;
+        for field in fields {
+            let field = field.trim();
+            if let Some(s) = field.strip_prefix("score:") {
+                score = match s.parse::<f64>() {
+                    Ok(s) => s.round() as i32,
+                    Err(_) => { return Err(format!("bad score '{}'", s)); }
+                };
+            } else if let Some(o) = field.strip_prefix("outcome:") {
+                outcome = match o.parse::<f64>() {
+                    Ok(o) if (0.0..=1.0).contains(&o) => Some(o),
+                    _ => { return Err(format!("bad outcome '{}'", o)); }
+                };
+            }
+        }
+        return match outcome {
+            Some(o) => PackedPosition::from_fen(fen, score, o),
+            None => Err("missing outcome".to_string())
+        };
+    }
+
+    pub fn to_text(self) -> String {
+        return format!("{};score:{};outcome:{:.1}", self.fen(), self.score, self.outcome());
+    }
+
+    pub fn from_bytes(b: &[u8; PACKED_SIZE]) -> Result<PackedPosition, String> {
+        let p = PackedPosition {
+            occupancy: u64::from_le_bytes(b[0..8].try_into().unwrap()),
+            pieces: b[8..24].try_into().unwrap(),
+            flags: b[24],
+            ep: b[25],
+            halfmove: b[26],
+            result: b[27],
+            score: i16::from_le_bytes([b[28], b[29]]),
+            fullmove: u16::from_le_bytes([b[30], b[31]])
+        };
+
+        // enough checking that a misaligned or foreign file doesn't turn
+        // into nonsense boards
+        if p.occupancy.count_ones() > 32 || p.result > 2 || (p.ep != NO_EP && p.ep > 7) {
+            return Err("corrupt record".to_string());
+        }
+        let mut kings = [0, 0];
+        for i in 0..p.occupancy.count_ones() as usize {
+            let nibble = p.nibble(i);
+            if nibble & 0x7 > KING {
+                return Err("corrupt record".to_string());
+            }
+            if nibble & 0x7 == KING {
+                kings[(nibble >> 3) as usize] += 1;
+            }
+        }
+        if kings != [1, 1] {
+            return Err("corrupt record".to_string());
+        }
+        return Ok(p);
+    }
+
+    pub fn to_bytes(self) -> [u8; PACKED_SIZE] {
+        let mut b = [0; PACKED_SIZE];
+        b[0..8].copy_from_slice(&self.occupancy.to_le_bytes());
+        b[8..24].copy_from_slice(&self.pieces);
+        b[24] = self.flags;
+        b[25] = self.ep;
+        b[26] = self.halfmove;
+        b[27] = self.result;
+        b[28..30].copy_from_slice(&self.score.to_le_bytes());
+        b[30..32].copy_from_slice(&self.fullmove.to_le_bytes());
+        return b;
+    }
+
+    fn nibble(&self, i: usize) -> u8 {
+        return (self.pieces[i / 2] >> (4 * (i % 2))) & 0xf;
+    }
+
+    // piece bitboards indexed [color][piece], as Bitboard::from_pieces takes them
+    pub fn piece_boards(&self) -> [[u64; 6]; 2] {
+        let mut pieces = [[0; 6]; 2];
+        let mut occ = self.occupancy;
+        let mut i = 0;
+        while occ != 0 {
+            let nibble = self.nibble(i);
+            let color = (nibble >> 3) as usize;
+            pieces[color][(nibble & 0x7) as usize] |= occ & occ.wrapping_neg();
+            occ &= occ - 1;
+            i += 1;
+        }
+        return pieces;
+    }
+
+    pub fn side_to_move(&self) -> Color {
+        return if self.flags & BLACK_TO_MOVE != 0 { Color::Black } else { Color::White };
+    }
+
+    pub fn castling_rights(&self) -> u8 {
+        return self.flags & 0xf;
+    }
+
+    pub fn ep_file(&self) -> i32 {
+        return if self.ep == NO_EP { -1 } else { self.ep as i32 };
+    }
+
+    // game result from white's point of view, as in the text format
+    pub fn outcome(&self) -> f64 {
+        return self.result as f64 / 2.0;
+    }
+
+    pub fn set_score(&mut self, score: i32) {
+        self.score = score.clamp(i16::MIN as i32, i16::MAX as i32) as i16;
+    }
+
+    pub fn set_outcome(&mut self, outcome: f64) {
+        self.result = outcome_to_result(outcome);
+    }
+
+    pub fn to_board(self) -> Bitboard {
+        return Bitboard::from_pieces(self.piece_boards(), self.side_to_move(), self.castling_rights(), self.ep_file(), self.halfmove);
+    }
+
+    pub fn fen(&self) -> String {
+        let mut squares = [0u8; 64];
+        let mut occ = self.occupancy;
+        let mut i = 0;
+        while occ != 0 {
+            let nibble = self.nibble(i);
+            let c = b"pnbrqk"[(nibble & 0x7) as usize];
+            squares[occ.trailing_zeros() as usize] = if nibble & WHITE_PIECE != 0 { c.to_ascii_uppercase() } else { c };
+            occ &= occ - 1;
+            i += 1;
+        }
+
+        let mut fen = String::new();
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+            for file in 0..8 {
+                let c = squares[rank * 8 + file];
+                if c == 0 {
+                    empty += 1;
+                    continue;
+                }
+                if empty > 0 {
+                    fen.push((b'0' + empty) as char);
+                    empty = 0;
+                }
+                fen.push(c as char);
+            }
+            if empty > 0 {
+                fen.push((b'0' + empty) as char);
+            }
+            if rank > 0 {
+                fen.push('/');
+            }
+        }
+
+        fen.push_str(if self.side_to_move() == Color::White { " w " } else { " b " });
+        let castling_rights = self.castling_rights();
+        if castling_rights == 0 {
+            fen.push('-');
+        }
+        for (mask, c) in [(0b1000, 'K'), (0b0100, 'Q'), (0b0010, 'k'), (0b0001, 'q')] {
+            if castling_rights & mask != 0 {
+                fen.push(c);
+            }
+        }
+        fen.push(' ');
+        if self.ep == NO_EP {
+            fen.push('-');
+        } else {
+            fen.push((b'a' + self.ep) as char);
+            fen.push(if self.side_to_move() == Color::White { '6' } else { '3' });
+        }
+        fen.push_str(&format!(" {} {}", self.halfmove, self.fullmove));
+        return fen;
+    }
+}
+
+pub struct PackedReader {
+    reader: BufReader<File>,
+    fname: String
+}
+
+impl PackedReader {
+    pub fn open(fname: &str) -> Result<PackedReader, String> {
+        return match File::open(fname) {
+            Ok(f) => Ok(PackedReader { reader: BufReader::with_capacity(1 << 20, f), fname: fname.to_string() }),
+            Err(e) => Err(format!("unable to open file {}: {}", fname, e))
+        };
+    }
+
+    // reads up to max records, returning an empty vector at the end of the file
+    pub fn read_chunk(&mut self, max: usize) -> Result<Vec<PackedPosition>, String> {
+        let mut chunk = Vec::with_capacity(max);
+        while chunk.len() < max {
+            match self.next() {
+                Some(r) => { chunk.push(r?); },
+                None => { break; }
+            }
+        }
+        return Ok(chunk);
+    }
+}
+
+impl Iterator for PackedReader {
+    type Item = Result<PackedPosition, String>;
+
+    fn next(&mut self) -> Option<Result<PackedPosition, String>> {
+        let mut buf = [0; PACKED_SIZE];
+        return match self.reader.read_exact(&mut buf) {
+            Ok(()) => Some(PackedPosition::from_bytes(&buf).map_err(|e| format!("{} in {}", e, self.fname))),
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => None,
+            Err(e) => Some(Err(format!("unable to read {}: {}", self.fname, e)))
+        };
+    }
+}
+
+pub struct PackedWriter {
+    writer: BufWriter<File>
+}
+
+impl PackedWriter {
+    pub fn create(fname: &str, append: bool) -> Result<PackedWriter, String> {
+        let f = OpenOptions::new().create(true).write(true).append(append).truncate(!append).open(fname);
+        return match f {
+            Ok(f) => Ok(PackedWriter { writer: BufWriter::with_capacity(1 << 20, f) }),
+            Err(e) => Err(format!("unable to open {}: {}", fname, e))
+        };
+    }
+
+    pub fn write(&mut self, p: &PackedPosition) -> Result<(), String> {
+        return self.writer.write_all(&p.to_bytes()).map_err(|e| e.to_string());
+    }
+
+    pub fn flush(&mut self) -> Result<(), String> {
+        return self.writer.flush().map_err(|e| e.to_string());
+    }
+}
+
+// reads training data in either format, decided by the extension.  text
+// lines that don't parse come back as errors so callers can skip them
+pub enum DataReader {
+    Text(std::io::Lines<BufReader<File>>),
+    Packed(PackedReader)
+}
+
+impl DataReader {
+    pub fn open(fname: &str) -> Result<DataReader, String> {
+        if is_packed_file(fname) {
+            return Ok(DataReader::Packed(PackedReader::open(fname)?));
+        }
+        return match File::open(fname) {
+            Ok(f) => Ok(DataReader::Text(BufReader::with_capacity(1 << 20, f).lines())),
+            Err(e) => Err(format!("unable to open file {}: {}", fname, e))
+        };
+    }
+}
+
+impl Iterator for DataReader {
+    type Item = Result<PackedPosition, String>;
+
+    fn next(&mut self) -> Option<Result<PackedPosition, String>> {
+        return match self {
+            DataReader::Packed(r) => r.next(),
+            DataReader::Text(lines) => loop {
+                match lines.next()? {
+                    Ok(l) if l.trim().is_empty() => { continue; },
+                    Ok(l) => { break Some(PackedPosition::from_text(&l)); },
+                    Err(e) => { break Some(Err(e.to_string())); }
+                }
+            }
+        };
+    }
+}
+
+// writes training data in either format, decided by the extension
+pub enum DataWriter {
+    Text(BufWriter<File>),
+    Packed(PackedWriter)
+}
+
+impl DataWriter {
+    pub fn create(fname: &str) -> Result<DataWriter, String> {
+        if is_packed_file(fname) {
+            return Ok(DataWriter::Packed(PackedWriter::create(fname, false)?));
+        }
+        return match File::create(fname) {
+            Ok(f) => Ok(DataWriter::Text(BufWriter::with_capacity(1 << 20, f))),
+            Err(e) => Err(format!("unable to open {}: {}", fname, e))
+        };
+    }
+
+    pub fn write(&mut self, p: &PackedPosition) -> Result<(), String> {
+        return match self {
+            DataWriter::Packed(w) => w.write(p),
+            DataWriter::Text(w) => writeln!(w, "{}", p.to_text()).map_err(|e| e.to_string())
+        };
+    }
+
+    pub fn flush(&mut self) -> Result<(), String> {
+        return match self {
+            DataWriter::Packed(w) => w.flush(),
+            DataWriter::Text(w) => w.flush().map_err(|e| e.to_string())
+        };
+    }
+}
+
+// text to binary or back, decided by the file extensions.  returns the
+// number of records written and the number of input lines skipped
+pub fn convert_data(input: &str, output: &str) -> Result<(u64, u64), String> {
+    if is_packed_file(input) == is_packed_file(output) {
+        return Err("exactly one of the files should be a .bin file".to_string());
+    }
+    let reader = DataReader::open(input)?;
+    let mut writer = DataWriter::create(output)?;
+    let mut written = 0;
+    let mut skipped = 0;
+    for p in reader {
+        match p {
+            Ok(p) => {
+                writer.write(&p)?;
+                written += 1;
+            },
+            // a bad binary record means the rest of the file can't be trusted
+            Err(e) if is_packed_file(input) => { return Err(e); },
+            Err(e) => {
+                if skipped < 10 {
+                    eprintln!("ERR: skipping position {}: {}", written + skipped + 1, e);
+                }
+                skipped += 1;
+            }
+        }
+    }
+    writer.flush()?;
+    return Ok((written, skipped));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FENS: [&str; 4] = [
+        "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 3",
+        "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+        "r3k2r/8/8/8/8/8/8/R3K2R w Kq - 12 40",
+        "8/8/8/8/8/8/k7/K7 b - - 99 300",
+    ];
+
+    #[test]
+    fn bytes_round_trip() {
+        for fen in FENS {
+            let p = PackedPosition::from_fen(fen, -123, 0.5).unwrap();
+            let q = PackedPosition::from_bytes(&p.to_bytes()).unwrap();
+            assert_eq!(p, q);
+            assert_eq!(q.fen(), fen);
+            assert_eq!(q.score, -123);
+            assert_eq!(q.outcome(), 0.5);
+        }
+    }
+
+    #[test]
+    fn text_round_trip() {
+        for fen in FENS {
+            let line = format!("{};score:42;outcome:1.0", fen);
+            let p = PackedPosition::from_text(&line).unwrap();
+            assert_eq!(p.to_text(), line);
+        }
+    }
+
+    // the board doesn't keep a fullmove number, so these start at 1
+    #[test]
+    fn board_round_trip() {
+        crate::test_init();
+        for fen in FENS {
+            let fields: Vec<&str> = fen.split(' ').collect();
+            let fen = format!("{} {} 1", fields[..4].join(" "), fields[4]);
+            let board = Bitboard::from_position(fen.clone());
+            let p = PackedPosition::from_board(&board, 0, 1.0);
+            assert_eq!(p, PackedPosition::from_fen(&fen, 0, 1.0).unwrap());
+            assert_eq!(p.to_board().fen(), fen);
+        }
+    }
+
+    #[test]
+    fn corrupt_records() {
+        let mut b = PackedPosition::from_fen(FENS[0], 0, 0.0).unwrap().to_bytes();
+        b[25] = 8;
+        assert!(PackedPosition::from_bytes(&b).is_err());
+        assert!(PackedPosition::from_bytes(&[0; PACKED_SIZE]).is_err());
+    }
+}
@@ -2,6 +2,7 @@ use crate::bitboard::*;
 use crate::movegen::*;
 use crate::moveorder::*;
 use crate::moveutil::*;
+use crate::search::get_time_millis;
 
 pub static mut PERFT_NODES : [usize; 16] = [0; 16];
 
@@ -18,3 +19,17 @@ pub fn perft(pos: &mut Bitboard, depth: i8, ply: usize) {
         pos.undo_move(&mv);
     }
 }
+
+pub fn run_perft(pos: &mut Bitboard, depth: i8) {
+    unsafe { PERFT_NODES = [0; 16]; }
+    let start = get_time_millis();
+    perft(pos, depth - 1, 0);
+    let elapsed = (get_time_millis() - start).max(1);
+    unsafe {
+        for ply in 1..=(depth as usize) {
+            println!("depth {:>2}: {:>12}", ply, PERFT_NODES[ply]);
+        }
+        let nodes = PERFT_NODES[depth as usize];
+        println!("{} nodes in {}ms ({} nps)", nodes, elapsed, (nodes as u128 * 1000) / elapsed);
+    }
+}
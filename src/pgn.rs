@@ -10,243 +10,1034 @@ use crate::searchutil::*;
 use crate::uci::*;
 use crate::util::*;
 
-pub fn san_to_move(pos: &mut Bitboard, san: String) -> Move {
-    let mut san_bytes = san.trim_end_matches(|x| x == '+' || x == '#').as_bytes();
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
 
-    if san_bytes == "0-0".as_bytes() || san_bytes == "O-O".as_bytes() {
-        return if pos.side_to_move == Color::White {
-            Move::piece_move(coord_to_idx((4, 0)), coord_to_idx((6, 0)), b'k')
-        } else {
-            Move::piece_move(coord_to_idx((4, 7)), coord_to_idx((6, 7)), b'k')
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GameResult {
+    WhiteWin,
+    BlackWin,
+    Draw,
+    Unknown
+}
+
+impl GameResult {
+    pub fn from_str(s: &str) -> Option<GameResult> {
+        return match s {
+            "1-0" => Some(GameResult::WhiteWin),
+            "0-1" => Some(GameResult::BlackWin),
+            "1/2-1/2" => Some(GameResult::Draw),
+            "*" => Some(GameResult::Unknown),
+            _ => None
+        };
+    }
+
+    pub fn to_str(self) -> &'static str {
+        return match self {
+            GameResult::WhiteWin => "1-0",
+            GameResult::BlackWin => "0-1",
+            GameResult::Draw => "1/2-1/2",
+            GameResult::Unknown => "*"
+        };
+    }
+
+    // game outcome from white's point of view, as used in the training data
+    pub fn outcome(&self) -> Option<f64> {
+        return match self {
+            GameResult::WhiteWin => Some(1.0),
+            GameResult::BlackWin => Some(0.0),
+            GameResult::Draw => Some(0.5),
+            GameResult::Unknown => None
+        };
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct PgnMove {
+    pub mv: Move,
+    pub san: String,
+    pub nags: Vec<u8>,
+    // comments appearing before the move (only really happens at the start
+    // of the game or of a variation) and after it
+    pub comments_before: Vec<String>,
+    pub comments: Vec<String>,
+    // alternatives to this move, each played from the position before it
+    pub variations: Vec<Vec<PgnMove>>
+}
+
+impl PgnMove {
+    pub fn new(pos: &mut Bitboard, mv: Move) -> PgnMove {
+        PgnMove {
+            mv: mv,
+            san: move_to_san(pos, &mv),
+            nags: Vec::new(),
+            comments_before: Vec::new(),
+            comments: Vec::new(),
+            variations: Vec::new()
         }
     }
+}
 
-    if san_bytes == "0-0-0".as_bytes() || san_bytes == "O-O-O".as_bytes() {
-        return if pos.side_to_move == Color::White {
-            Move::piece_move(coord_to_idx((4, 0)), coord_to_idx((2, 0)), b'k')
-        } else {
-            Move::piece_move(coord_to_idx((4, 7)), coord_to_idx((2, 7)), b'k')
+#[derive(Clone, Debug)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<PgnMove>,
+    pub result: GameResult
+}
+
+impl PgnGame {
+    pub fn new() -> PgnGame {
+        PgnGame {
+            tags: Vec::new(),
+            moves: Vec::new(),
+            result: GameResult::Unknown
+        }
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        for (k, v) in self.tags.iter_mut() {
+            if k == name {
+                *v = value.to_string();
+                return;
+            }
+        }
+        self.tags.push((name.to_string(), value.to_string()));
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        for (k, v) in &self.tags {
+            if k == name {
+                return Some(v.as_str());
+            }
+        }
+        return None;
+    }
+
+    pub fn starting_fen(&self) -> String {
+        return match self.tag("FEN") {
+            Some(fen) => fen.to_string(),
+            None => START_FEN.to_string()
+        };
+    }
+
+    pub fn starting_board(&self) -> Result<Bitboard, String> {
+        let fen = self.starting_fen();
+        Bitboard::validate_fen(&fen)?;
+        return Ok(Bitboard::from_position(fen));
+    }
+
+    // walk the mainline, handing the callback the position before each move
+    // along with the move about to be played.  returns the final position.
+    pub fn replay<F: FnMut(&mut Bitboard, &PgnMove)>(&self, mut f: F) -> Result<Bitboard, String> {
+        let mut board = self.starting_board()?;
+        for m in &self.moves {
+            f(&mut board, m);
+            do_pgn_move(&mut board, m);
         }
+        return Ok(board);
     }
 
-    let pseudolegal_moves = moves(pos);
-    let mut moves = Vec::new();
-    for mv in pseudolegal_moves {
+    pub fn to_pgn(&self) -> Result<String, String> {
+        let mut board = self.starting_board()?;
+        let mut out = String::new();
+
+        // the seven tag roster always comes first and in this order
+        for name in SEVEN_TAG_ROSTER.iter() {
+            let value = match (*name, self.tag(name)) {
+                ("Result", _) => self.result.to_str(),
+                (_, Some(v)) => v,
+                ("Date", None) => "????.??.??",
+                (_, None) => "?"
+            };
+            out.push_str(&format!("[{} \"{}\"]\n", name, escape_tag_value(value)));
+        }
+        for (name, value) in &self.tags {
+            if !SEVEN_TAG_ROSTER.contains(&name.as_str()) {
+                out.push_str(&format!("[{} \"{}\"]\n", name, escape_tag_value(value)));
+            }
+        }
+        out.push('\n');
+
+        let mut tokens: Vec<String> = Vec::new();
+        let fullmove = match self.starting_fen().split_whitespace().nth(5) {
+            Some(n) => n.parse::<usize>().unwrap_or(1),
+            None => 1
+        };
+        let start_ply = 2 * (fullmove.max(1) - 1) + if board.side_to_move == Color::White { 0 } else { 1 };
+        movetext_tokens(&mut board, &self.moves, start_ply, &mut tokens);
+        tokens.push(self.result.to_str().to_string());
+
+        // export format keeps lines under 80 columns
+        let mut line_len = 0;
+        for token in tokens {
+            if line_len > 0 && line_len + 1 + token.len() >= PGN_LINE_WIDTH {
+                out.push('\n');
+                line_len = 0;
+            }
+            if line_len > 0 {
+                out.push(' ');
+                line_len += 1;
+            }
+            line_len += token.len();
+            out.push_str(&token);
+        }
+        out.push_str("\n\n");
+        return Ok(out);
+    }
+}
+
+const SEVEN_TAG_ROSTER: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+const PGN_LINE_WIDTH: usize = 80;
+
+fn escape_tag_value(value: &str) -> String {
+    return value.replace('\\', "\\\\").replace('"', "\\\"");
+}
+
+fn comment_token(comment: &str) -> String {
+    // there's no escape for a closing brace inside a comment
+    return format!("{{{}}}", comment.replace('}', ")").split_whitespace().collect::<Vec<&str>>().join(" "));
+}
+
+fn movetext_tokens(pos: &mut Bitboard, line: &[PgnMove], start_ply: usize, tokens: &mut Vec<String>) {
+    // a move number is needed for white moves and for black moves that
+    // don't directly follow white's move
+    let mut need_number = true;
+    for (i, m) in line.iter().enumerate() {
+        let ply = start_ply + i;
+        for c in &m.comments_before {
+            tokens.push(comment_token(c));
+            need_number = true;
+        }
+        if ply % 2 == 0 {
+            tokens.push(format!("{}.", ply / 2 + 1));
+        } else if need_number {
+            tokens.push(format!("{}...", ply / 2 + 1));
+        }
+        need_number = false;
+
+        tokens.push(move_to_san(pos, &m.mv));
+        for nag in &m.nags {
+            tokens.push(format!("${}", nag));
+        }
+        for c in &m.comments {
+            tokens.push(comment_token(c));
+            need_number = true;
+        }
+        for variation in &m.variations {
+            let mut variation_tokens = Vec::new();
+            movetext_tokens(pos, variation, ply, &mut variation_tokens);
+            if let Some(first) = variation_tokens.first_mut() {
+                first.insert(0, '(');
+            }
+            if let Some(last) = variation_tokens.last_mut() {
+                last.push(')');
+            }
+            tokens.append(&mut variation_tokens);
+            need_number = true;
+        }
+        do_pgn_move(pos, m);
+    }
+    for m in line.iter().rev() {
+        undo_pgn_move(pos, m);
+    }
+}
+
+pub fn write_pgn<W: Write>(out: &mut W, game: &PgnGame) -> Result<(), String> {
+    let pgn = game.to_pgn()?;
+    return match out.write_all(pgn.as_bytes()) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(format!("unable to write game: {}", e))
+    };
+}
+
+pub fn legal_moves(pos: &mut Bitboard) -> Vec<Move> {
+    let mut legal = Vec::new();
+    for mv in moves(pos) {
         pos.do_move(&mv);
         if !pos.is_check(!pos.side_to_move) {
-            // legal
-            moves.push(mv);
+            legal.push(mv);
         }
         pos.undo_move(&mv);
     }
+    return legal;
+}
+
+pub fn move_to_san(pos: &mut Bitboard, mv: &Move) -> String {
+    if mv.is_null() {
+        return "--".to_string();
+    }
+
+    let mut san = String::new();
+    if mv.piece == b'k' && (mv.end - mv.start).abs() == 2 {
+        san.push_str(if mv.end > mv.start { "O-O" } else { "O-O-O" });
+    } else {
+        let is_capture = pos.piece_at_square(mv.end, !pos.side_to_move) != 0 || mv.is_pawn_cap();
+        if mv.piece == b'p' {
+            if is_capture {
+                san.push((b'a' + (mv.start % 8) as u8) as char);
+            }
+        } else {
+            san.push((mv.piece - 32) as char);
+            // disambiguate against other pieces of the same type that can
+            // reach the same square
+            let others: Vec<Move> = legal_moves(pos).into_iter()
+                .filter(|m| m.piece == mv.piece && m.end == mv.end && m.start != mv.start)
+                .collect();
+            if !others.is_empty() {
+                let file_unique = others.iter().all(|m| m.start % 8 != mv.start % 8);
+                let rank_unique = others.iter().all(|m| m.start / 8 != mv.start / 8);
+                let start = idx_to_str(mv.start);
+                if file_unique {
+                    san.push_str(&start[..1]);
+                } else if rank_unique {
+                    san.push_str(&start[1..]);
+                } else {
+                    san.push_str(&start);
+                }
+            }
+        }
+        if is_capture {
+            san.push('x');
+        }
+        san.push_str(&idx_to_str(mv.end));
+        if mv.promote_to != 0 {
+            san.push('=');
+            san.push((mv.promote_to - 32) as char);
+        }
+    }
 
-    let promote_to = if san_bytes[san_bytes.len() - 2] == b'=' {
-        let promote = san_bytes[san_bytes.len() - 1] + 32; // lowercase
+    pos.do_move(mv);
+    if pos.is_check(pos.side_to_move) {
+        san.push(if legal_moves(pos).is_empty() { '#' } else { '+' });
+    }
+    pos.undo_move(mv);
+    return san;
+}
+
+pub fn san_to_move(pos: &mut Bitboard, san: String) -> Move {
+    // strip check marks and annotations, neither of which matter for finding
+    // the move
+    let trimmed = san.trim_end_matches(['+', '#', '!', '?']).trim_end_matches("e.p.");
+
+    let mut moves = legal_moves(pos);
+
+    if trimmed == "0-0" || trimmed == "O-O" || trimmed == "0-0-0" || trimmed == "O-O-O" {
+        let rank = if pos.side_to_move == Color::White { 0 } else { 7 };
+        let file = if trimmed.len() == 3 { 6 } else { 2 };
+        let start = coord_to_idx((4, rank));
+        let end = coord_to_idx((file, rank));
+        moves.retain(|&m| m.piece == b'k' && m.start == start && m.end == end);
+        return if moves.len() == 1 { moves[0] } else { Move::null_move() };
+    }
+
+    // capture markers don't tell us anything the destination doesn't
+    let cleaned: String = trimmed.chars().filter(|&c| c != 'x' && c != ':' && c != '-').collect();
+    let mut san_bytes = cleaned.as_bytes();
+
+    if san_bytes.len() < 2 {
+        return Move::null_move();
+    }
+
+    // promotions, with or without the '='
+    let last = san_bytes[san_bytes.len() - 1];
+    let promote_to = if b"QRBN".contains(&last) && san_bytes[san_bytes.len() - 2] == b'=' {
         san_bytes = &san_bytes[..san_bytes.len() - 2];
-        promote
+        last + 32 // lowercase
+    } else if b"QRBN".contains(&last) && san_bytes.len() >= 3 && san_bytes[san_bytes.len() - 2].is_ascii_digit() {
+        san_bytes = &san_bytes[..san_bytes.len() - 1];
+        last + 32
     } else {
         0
     };
 
+    if san_bytes.len() < 2 {
+        return Move::null_move();
+    }
+
     // start by filtering by destination tile
-    let dest = str_to_idx(format!("{}{}", san_bytes[san_bytes.len() - 2] as char, san_bytes[san_bytes.len() - 1] as char));
+    let dest_file = san_bytes[san_bytes.len() - 2];
+    let dest_rank = san_bytes[san_bytes.len() - 1];
+    if !(b'a'..=b'h').contains(&dest_file) || !(b'1'..=b'8').contains(&dest_rank) {
+        return Move::null_move();
+    }
+    let dest = bytes_to_idx(dest_file, dest_rank);
+    san_bytes = &san_bytes[..san_bytes.len() - 2];
     moves.retain(|&m| m.end == dest && m.promote_to == promote_to);
 
-    if moves.len() == 1 { return moves[0]; }
-
-    let piece_type = if san_bytes[0] >= b'A' && san_bytes[0] <= b'Z' {
+    let piece_type = if !san_bytes.is_empty() && san_bytes[0].is_ascii_uppercase() {
         let piece = san_bytes[0] + 32; // + 32 to lowercase the ascii
         san_bytes = &san_bytes[1..];
         piece
     } else {
         b'p'
     };
-
     moves.retain(|&m| m.piece == piece_type);
+
+    // whatever is left between the piece and the destination disambiguates
+    for &c in san_bytes {
+        if (b'a'..=b'h').contains(&c) {
+            moves.retain(|&m| m.start % 8 == (c - b'a') as i8);
+        } else if (b'1'..=b'8').contains(&c) {
+            moves.retain(|&m| m.start / 8 == (c - b'1') as i8);
+        } else {
+            return Move::null_move();
+        }
+    }
+
     if moves.len() == 1 { return moves[0]; }
+    return Move::null_move();
+}
 
-    let file = if san_bytes[0] >= b'a' && san_bytes[0] <= b'z' {
-        let f = san_bytes[0] - b'a';
-        san_bytes = &san_bytes[1..];
-        f as i8
-    } else {
-        -1
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Symbol(String),
+    Nag(u8),
+    Comment(String),
+    VariationStart,
+    VariationEnd,
+    Result(GameResult)
+}
+
+fn is_symbol_char(c: char) -> bool {
+    return c.is_ascii_alphanumeric() || "_+#=:-/".contains(c);
+}
+
+fn suffix_nag(s: &str) -> Option<u8> {
+    return match s {
+        "!" => Some(1),
+        "?" => Some(2),
+        "!!" => Some(3),
+        "??" => Some(4),
+        "!?" => Some(5),
+        "?!" => Some(6),
+        _ => None
     };
+}
 
-    if file != -1 {
-        moves.retain(|&m| m.start % 8 == file);
-        if moves.len() == 1 { return moves[0]; }
+// splits movetext into tokens.  brace comments can span lines, so the lexer
+// keeps its state between calls.
+struct PgnLexer {
+    in_comment: bool,
+    comment: String,
+    depth: i32
+}
+
+impl PgnLexer {
+    fn new() -> PgnLexer {
+        PgnLexer {
+            in_comment: false,
+            comment: String::new(),
+            depth: 0
+        }
     }
 
-    let rank = if san_bytes[0] >= b'1' && san_bytes[0] <= b'9' {
-        let r = san_bytes[0] - b'1';
-        r as i8
-    } else {
-        -1
-    };
+    // returns true once the game termination marker has been seen
+    fn lex_line(&mut self, line: &str, tokens: &mut Vec<Token>) -> bool {
+        let chars: Vec<char> = line.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            if self.in_comment {
+                if c == '}' {
+                    self.in_comment = false;
+                    tokens.push(Token::Comment(self.comment.trim().to_string()));
+                    self.comment.clear();
+                } else {
+                    self.comment.push(c);
+                }
+                i += 1;
+                continue;
+            }
 
-    if rank != -1 {
-        moves.retain(|&m| m.start / 8 == rank);
-        if moves.len() == 1 { return moves[0]; }
+            if c == '{' {
+                self.in_comment = true;
+                i += 1;
+            } else if c == ';' {
+                // rest of line comment
+                let comment: String = chars[i + 1..].iter().collect();
+                tokens.push(Token::Comment(comment.trim().to_string()));
+                break;
+            } else if c == '(' {
+                self.depth += 1;
+                tokens.push(Token::VariationStart);
+                i += 1;
+            } else if c == ')' {
+                self.depth -= 1;
+                tokens.push(Token::VariationEnd);
+                i += 1;
+            } else if c == '*' {
+                tokens.push(Token::Result(GameResult::Unknown));
+                i += 1;
+                if self.depth <= 0 { return true; }
+            } else if c == '$' {
+                let mut j = i + 1;
+                while j < chars.len() && chars[j].is_ascii_digit() { j += 1; }
+                let num: String = chars[i + 1..j].iter().collect();
+                if let Ok(n) = num.parse::<u8>() {
+                    tokens.push(Token::Nag(n));
+                }
+                i = j;
+            } else if c == '!' || c == '?' {
+                let mut j = i;
+                while j < chars.len() && (chars[j] == '!' || chars[j] == '?') { j += 1; }
+                let s: String = chars[i..j].iter().collect();
+                if let Some(n) = suffix_nag(&s) {
+                    tokens.push(Token::Nag(n));
+                }
+                i = j;
+            } else if is_symbol_char(c) {
+                let mut j = i;
+                while j < chars.len() && is_symbol_char(chars[j]) { j += 1; }
+                let s: String = chars[i..j].iter().collect();
+                i = j;
+                if s.bytes().all(|b| b.is_ascii_digit()) {
+                    // move number indication, with any number of periods
+                    while i < chars.len() && chars[i] == '.' { i += 1; }
+                    continue;
+                }
+                match GameResult::from_str(&s) {
+                    Some(r) => {
+                        tokens.push(Token::Result(r));
+                        if self.depth <= 0 { return true; }
+                    },
+                    None => { tokens.push(Token::Symbol(s)); }
+                }
+            } else {
+                // whitespace, stray periods and anything else we don't understand
+                i += 1;
+            }
+        }
+        if self.in_comment {
+            self.comment.push(' ');
+        }
+        return false;
     }
+}
 
-    return Move::null_move();
+fn parse_tag_line(line: &str, tags: &mut Vec<(String, String)>) -> Result<(), String> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] != '[' {
+            if chars[i].is_whitespace() { i += 1; continue; }
+            return Err(format!("malformed tag line: {}", line.trim()));
+        }
+        i += 1;
+        while i < chars.len() && chars[i].is_whitespace() { i += 1; }
+        let mut name = String::new();
+        while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+            name.push(chars[i]);
+            i += 1;
+        }
+        while i < chars.len() && chars[i].is_whitespace() { i += 1; }
+        if name.is_empty() || i >= chars.len() || chars[i] != '"' {
+            return Err(format!("malformed tag line: {}", line.trim()));
+        }
+        i += 1;
+        let mut value = String::new();
+        loop {
+            if i >= chars.len() {
+                return Err(format!("unterminated tag value: {}", line.trim()));
+            }
+            let c = chars[i];
+            i += 1;
+            if c == '\\' && i < chars.len() {
+                // escaped quote or backslash
+                value.push(chars[i]);
+                i += 1;
+            } else if c == '"' {
+                break;
+            } else {
+                value.push(c);
+            }
+        }
+        while i < chars.len() && chars[i].is_whitespace() { i += 1; }
+        if i >= chars.len() || chars[i] != ']' {
+            return Err(format!("malformed tag line: {}", line.trim()));
+        }
+        i += 1;
+        tags.push((name, value));
+    }
+    return Ok(());
 }
 
+fn do_pgn_move(pos: &mut Bitboard, m: &PgnMove) {
+    if m.mv.is_null() {
+        pos.do_null_move();
+    } else {
+        pos.do_move(&m.mv);
+    }
+}
 
-fn extract_positions(pos: &mut Bitboard, buf: &String) {
-    let mut in_annotation = false;
-    let mut cur_fen = pos.fen();
-    let mut record_pos = false;
-    let mut pos_stm = Color::White;
-    let mut pos_eval = 0;
-    let mut pos_qeval = 0;
-    let mut lines: Vec<String> = Vec::new();
+fn undo_pgn_move(pos: &mut Bitboard, m: &PgnMove) {
+    if m.mv.is_null() {
+        pos.undo_null_move();
+    } else {
+        pos.undo_move(&m.mv);
+    }
+}
 
-    let tokens = buf.split_ascii_whitespace();
-    for token in tokens {
-        if in_annotation {
-            if token.ends_with("}") {
-                in_annotation = false;
-            }
-            continue;
-        }
+// parses a line of play (the mainline or a variation) starting at tokens[*i].
+// the position is restored before returning.
+fn parse_line(pos: &mut Bitboard, tokens: &[Token], i: &mut usize, depth: i32, result: &mut Option<GameResult>) -> Result<Vec<PgnMove>, String> {
+    let mut line: Vec<PgnMove> = Vec::new();
+    let mut pending_comments: Vec<String> = Vec::new();
+    let mut error: Option<String> = None;
+    let mut closed = false;
 
-        if token == "1-0" || token == "0-1" || token == "1/2-1/2" || token == "*" {
-            let outcome = {
-                if token == "1-0" {
-                    "1.0"
-                } else if token == "1/2-1/2" {
-                    "0.5"
-                } else if token == "0-1" {
-                    "0.0"
+    while *i < tokens.len() {
+        let token = &tokens[*i];
+        *i += 1;
+        match token {
+            Token::Symbol(san) => {
+                let mv = if san == "--" || san == "Z0" {
+                    Move::null_move()
+                } else {
+                    let mv = san_to_move(pos, san.clone());
+                    if mv.is_null() {
+                        error = Some(format!("illegal or ambiguous move '{}' in position {}", san, pos.fen()));
+                        break;
+                    }
+                    mv
+                };
+                if mv.is_null() {
+                    pos.do_null_move();
                 } else {
-                    return;
+                    pos.do_move(&mv);
                 }
-            };
-            for line in lines {
-                println!("{};outcome:{}", line, outcome);
-            }
-            return;
-        }
-
-        match &token[..1] {
-            "{" => {
-                // entering annotation
-                in_annotation = true;
-                if record_pos {
-                    let end_idx = token.find("/").unwrap();
-                    let score: f64 = match token[1..end_idx].parse() {
-                        Ok(s) => s,
-                        Err(_e) => {
-                            // generally a mate score. ignore
-                            record_pos = false;
-                            continue;
-                        }
-                    };
-                    let mul = if pos_stm == Color::White { 1 } else { -1 };
-                    let output_score = mul * ((score * 100.0) as i32);
-
-                    lines.push(format!("{};score:{};eval:{};qs:{}", cur_fen, output_score, pos_eval, pos_qeval));
-                    record_pos = false;
+                line.push(PgnMove {
+                    mv: mv,
+                    san: san.clone(),
+                    nags: Vec::new(),
+                    comments_before: pending_comments.split_off(0),
+                    comments: Vec::new(),
+                    variations: Vec::new()
+                });
+            },
+            Token::Nag(n) => {
+                if let Some(m) = line.last_mut() {
+                    m.nags.push(*n);
                 }
             },
-            "1" | "2" | "3" | "4" | "5" | "6" | "7" | "8" | "9" => {},
-            _ => {
-                // for well-formatted file, by process of elinimation, this
-                // should be a move
-                let mv = san_to_move(pos, token.to_string());
-                let mul = if pos.side_to_move == Color::White { 1 } else { -1 };
-                let qeval = mul * qsearch(pos, -1000000, 1000000, 0) / 10;
-                let eval = mul * static_eval(pos, unsafe {&mut TI[0].pht}) / 10;
+            Token::Comment(c) => {
+                match line.last_mut() {
+                    Some(m) => { m.comments.push(c.clone()); },
+                    None => { pending_comments.push(c.clone()); }
+                }
+            },
+            Token::VariationStart => {
+                let last = match line.pop() {
+                    Some(m) => m,
+                    None => { error = Some("variation with no move to replace".to_string()); break; }
+                };
+                undo_pgn_move(pos, &last);
+                let variation = parse_line(pos, tokens, i, depth + 1, result);
+                do_pgn_move(pos, &last);
+                line.push(last);
+                match variation {
+                    Ok(v) => { if !v.is_empty() { line.last_mut().unwrap().variations.push(v); } },
+                    Err(e) => { error = Some(e); break; }
+                }
+            },
+            Token::VariationEnd => {
+                if depth == 0 {
+                    error = Some("unmatched ')'".to_string());
+                }
+                closed = true;
+                break;
+            },
+            Token::Result(r) => {
+                if depth == 0 {
+                    *result = Some(*r);
+                    break;
+                }
+            }
+        }
+    }
+
+    if depth > 0 && error.is_none() && !closed {
+        error = Some("unterminated variation".to_string());
+    }
+
+    for m in line.iter().rev() {
+        undo_pgn_move(pos, m);
+    }
+    return match error {
+        Some(e) => Err(e),
+        None => Ok(line)
+    };
+}
+
+// streaming reader over a PGN database, yielding one game at a time.  a game
+// that fails to parse is reported as an error without stopping the iteration.
+pub struct PgnReader<R: BufRead> {
+    reader: R,
+    pending: Option<String>,
+    line_num: usize,
+    done: bool
+}
+
+impl<R: BufRead> PgnReader<R> {
+    pub fn new(reader: R) -> PgnReader<R> {
+        PgnReader {
+            reader: reader,
+            pending: None,
+            line_num: 0,
+            done: false
+        }
+    }
+
+    fn read_line(&mut self) -> Result<Option<String>, String> {
+        if let Some(line) = self.pending.take() {
+            return Ok(Some(line));
+        }
+        let mut buf = String::new();
+        return match self.reader.read_line(&mut buf) {
+            Ok(0) => Ok(None),
+            Ok(_) => { self.line_num += 1; Ok(Some(buf)) },
+            Err(e) => Err(format!("unable to read line {}: {}", self.line_num + 1, e))
+        };
+    }
 
-                if qeval == eval {
-                    cur_fen = pos.fen();
-                    pos_stm = pos.side_to_move;
-                    pos_eval = eval;
-                    pos_qeval = qeval;
-                    record_pos = true;
+    fn build_game(tags: Vec<(String, String)>, tokens: Vec<Token>, start_line: usize) -> Result<PgnGame, String> {
+        let mut game = PgnGame {
+            tags: tags,
+            moves: Vec::new(),
+            result: GameResult::Unknown
+        };
+        let mut board = match game.starting_board() {
+            Ok(b) => b,
+            Err(e) => { return Err(format!("game at line {}: bad FEN tag: {}", start_line, e)); }
+        };
+
+        let mut result = None;
+        let mut i = 0;
+        game.moves = match parse_line(&mut board, &tokens, &mut i, 0, &mut result) {
+            Ok(m) => m,
+            Err(e) => { return Err(format!("game at line {}: {}", start_line, e)); }
+        };
+        game.result = match result {
+            Some(r) => r,
+            None => match game.tag("Result") {
+                Some(r) => GameResult::from_str(r).unwrap_or(GameResult::Unknown),
+                None => GameResult::Unknown
+            }
+        };
+        return Ok(game);
+    }
+}
+
+impl PgnReader<BufReader<File>> {
+    pub fn open(fname: &str) -> Result<PgnReader<BufReader<File>>, String> {
+        return match File::open(fname) {
+            Ok(f) => Ok(PgnReader::new(BufReader::new(f))),
+            Err(e) => Err(format!("unable to open file {}: {}", fname, e))
+        };
+    }
+}
+
+impl<R: BufRead> Iterator for PgnReader<R> {
+    type Item = Result<PgnGame, String>;
+
+    fn next(&mut self) -> Option<Result<PgnGame, String>> {
+        if self.done { return None; }
+
+        let mut tags: Vec<(String, String)> = Vec::new();
+        let mut tokens: Vec<Token> = Vec::new();
+        let mut lexer = PgnLexer::new();
+        let mut tag_error: Option<String> = None;
+        let mut start_line = 0;
+        let mut in_movetext = false;
+
+        loop {
+            let line = match self.read_line() {
+                Ok(Some(l)) => l,
+                Ok(None) => { self.done = true; break; },
+                Err(e) => { self.done = true; return Some(Err(e)); }
+            };
+
+            if !lexer.in_comment {
+                if line.starts_with('%') {
+                    // escape mechanism, the rest of the line is ignored
+                    continue;
                 }
+                let trimmed = line.trim();
+                if trimmed.starts_with('[') {
+                    if in_movetext {
+                        // a new game started without a termination marker
+                        self.pending = Some(line);
+                        break;
+                    }
+                    if start_line == 0 { start_line = self.line_num; }
+                    if let Err(e) = parse_tag_line(trimmed, &mut tags) {
+                        tag_error = Some(e);
+                    }
+                    continue;
+                }
+                if trimmed.is_empty() {
+                    continue;
+                }
+            }
 
-                pos.do_move(&mv);
+            if start_line == 0 { start_line = self.line_num; }
+            in_movetext = true;
+            if lexer.lex_line(line.trim_end(), &mut tokens) {
+                break;
             }
         }
+
+        if start_line == 0 {
+            // nothing but whitespace left
+            return None;
+        }
+        if let Some(e) = tag_error {
+            return Some(Err(format!("game at line {}: {}", start_line, e)));
+        }
+        return Some(PgnReader::<R>::build_game(tags, tokens, start_line));
     }
 }
 
+fn comment_score(comments: &[String]) -> Option<f64> {
+    // engine annotations in the form {score/depth time ...}
+    let comment = comments.first()?;
+    let token = comment.split_whitespace().next()?;
+    let end_idx = token.find('/')?;
+    // mate scores fail to parse and are ignored
+    return token[..end_idx].parse().ok();
+}
+
+fn extract_positions(game: &PgnGame) -> Result<Vec<String>, String> {
+    let outcome = match game.result.outcome() {
+        Some(o) => o,
+        None => { return Ok(Vec::new()); }
+    };
+
+    let mut lines: Vec<String> = Vec::new();
+    game.replay(|pos, m| {
+        let mul = if pos.side_to_move == Color::White { 1 } else { -1 };
+        let qeval = mul * qsearch(pos, -1000000, 1000000, 0) / 10;
+        let eval = mul * static_eval(pos, unsafe {&mut TI[0].pht}) / 10;
+
+        // only quiet positions with a usable engine score
+        if qeval != eval { return; }
+        if let Some(score) = comment_score(&m.comments) {
+            let output_score = mul * ((score * 100.0) as i32);
+            lines.push(format!("{};score:{};eval:{};qs:{};outcome:{:.1}", pos.fen(), output_score, eval, qeval, outcome));
+        }
+    })?;
+    return Ok(lines);
+}
+
 pub fn convert_pgn(fname: &str) {
     unsafe {
         TI = vec![ThreadInfo::new(UCIOptions::default())];
     }
 
-    let f = match File::open(fname) {
-        Ok(f) => f,
-        Err(e) => panic!("unable to open file {}.  err: {}", fname, e)
+    let reader = match PgnReader::open(fname) {
+        Ok(r) => r,
+        Err(e) => { eprintln!("ERR: {}", e); return; }
     };
 
-    let mut r = BufReader::new(f);
-
-    let mut buf = String::new();
-
-    const GET_STARTING_POSITION: i32 = 0;
-    const FIND_MOVES: i32 = 1;
-    const GATHER_MOVES: i32 = 2;
-    const EXTRACT_POSITIONS: i32 = 3;
-
-    let mut state = GET_STARTING_POSITION;
-    let mut board = Bitboard::default_board();
-    loop {
-        match state {
-            GET_STARTING_POSITION => {
-                loop {
-                    buf.clear();
-                    let num_bytes = match r.read_line(&mut buf) {
-                        Ok(n) => n, Err(e) => panic!("unable to read line. err: {}", e)
-                    };
-
-                    if num_bytes == 0 { return; }
-
-                    match buf.get(..4) {
-                        Some("[FEN") => {
-                            board = Bitboard::from_position(format!("{}", buf.get(6..(buf.len() - 2)).unwrap()));
-                            state = FIND_MOVES;
-                            break;
-                        },
-                        _ => {}
-                    }
-                }
-            },
-            FIND_MOVES => {
-                loop {
-                    buf.clear();
-                    let num_bytes = match r.read_line(&mut buf) {
-                        Ok(n) => n, Err(e) => panic!("unable to read line. err: {}", e)
-                    };
-                    if num_bytes == 0 { return; }
-
-                    if buf.trim().len() == 0 {
-                        // was an empty line
-                        state = GATHER_MOVES;
-                        break;
-                    }
-                }
-            },
-            GATHER_MOVES => {
-                buf.clear();
-                // gathering loop
-                loop {
-                    let num_bytes = match r.read_line(&mut buf) {
-                        Ok(n) => n, Err(e) => panic!("unable to read line. err: {}", e)
-                    };
-                    if num_bytes <= 1 { state = EXTRACT_POSITIONS; break; }
+    for game in reader {
+        let game = match game {
+            Ok(g) => g,
+            Err(e) => { eprintln!("ERR: skipping {}", e); continue; }
+        };
+        match extract_positions(&game) {
+            Ok(lines) => {
+                for line in lines {
+                    println!("{}", line);
                 }
             },
-            EXTRACT_POSITIONS => {
-                // the buffer should contain the whole moveslist at this point, with annotations.
-                extract_positions(&mut board, &buf);
-                state = GET_STARTING_POSITION;
-            },
-            _ => {return;}
+            Err(e) => { eprintln!("ERR: skipping game: {}", e); }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // every legal move should come back from its own SAN
+    fn round_trip_all(fen: &str) -> Vec<String> {
+        crate::test_init();
+        let mut pos = Bitboard::from_position(fen.to_string());
+        let mut sans = Vec::new();
+        for mv in legal_moves(&mut pos) {
+            let san = move_to_san(&mut pos, &mv);
+            assert_eq!(san_to_move(&mut pos, san.clone()), mv, "{} in {}", san, fen);
+            sans.push(san);
+        }
+        return sans;
+    }
+
+    #[test]
+    fn castling() {
+        let sans = round_trip_all("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        assert!(sans.contains(&"O-O".to_string()));
+        assert!(sans.contains(&"O-O-O".to_string()));
+        let sans = round_trip_all("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1");
+        assert!(sans.contains(&"O-O".to_string()));
+        assert!(sans.contains(&"O-O-O".to_string()));
+    }
+
+    #[test]
+    fn promotion() {
+        let sans = round_trip_all("1n6/P6k/8/8/8/8/8/K7 w - - 0 1");
+        for san in ["a8=Q", "a8=N", "axb8=Q", "axb8=R", "axb8=B"] {
+            assert!(sans.contains(&san.to_string()), "missing {}", san);
         }
     }
+
+    #[test]
+    fn disambiguation() {
+        let sans = round_trip_all("8/7k/8/8/8/8/8/1N3NK1 w - - 0 1");
+        assert!(sans.contains(&"Nbd2".to_string()));
+        assert!(sans.contains(&"Nfd2".to_string()));
+        let sans = round_trip_all("8/7k/8/R7/8/8/8/R5K1 w - - 0 1");
+        assert!(sans.contains(&"R1a3".to_string()));
+        assert!(sans.contains(&"R5a3".to_string()));
+        let sans = round_trip_all("8/7k/8/8/8/Q7/8/Q1Q3K1 w - - 0 1");
+        assert!(sans.contains(&"Qa1b2".to_string()));
+    }
+
+    // validate_fen and from_position have to agree on where fields split
+    #[test]
+    fn fen_tag_spacing() {
+        crate::test_init();
+        let text = "[FEN \"8/8/8/8/8/8/8/K6k  w - - 0 1\"]\n\n1. Kb1 *\n";
+        let games: Vec<Result<PgnGame, String>> = PgnReader::new(text.as_bytes()).collect();
+        assert_eq!(games.len(), 1);
+        let end = games[0].as_ref().unwrap().replay(|_, _| {}).unwrap();
+        assert_eq!(end.fen(), "8/8/8/8/8/8/8/1K5k b - - 1 1");
+    }
+
+    fn read_games(text: &str) -> Vec<Result<PgnGame, String>> {
+        crate::test_init();
+        return PgnReader::new(text.as_bytes()).collect();
+    }
+
+    fn sans(line: &[PgnMove]) -> Vec<&str> {
+        return line.iter().map(|m| m.san.as_str()).collect();
+    }
+
+    #[test]
+    fn tag_section() {
+        let text = "[Event \"Casual \\\"blitz\\\"\"]\n[White \"A\"] [Black \"B\"]\n[Result \"1-0\"]\n\n1. e4 e5 1-0\n";
+        let games = read_games(text);
+        assert_eq!(games.len(), 1);
+        let game = games[0].as_ref().unwrap();
+        assert_eq!(game.tags.len(), 4);
+        assert_eq!(game.tag("Event"), Some("Casual \"blitz\""));
+        assert_eq!(game.tag("White"), Some("A"));
+        assert_eq!(game.tag("Black"), Some("B"));
+        assert_eq!(game.tag("Site"), None);
+        assert_eq!(sans(&game.moves), ["e4", "e5"]);
+        assert_eq!(game.result, GameResult::WhiteWin);
+
+        let games = read_games("[Event \"unterminated]\n\n1. e4 *\n");
+        assert!(games[0].is_err());
+    }
+
+    #[test]
+    fn comments() {
+        let text = "{opening\ncomment} 1. e4 {best by test} e5 ; rest of line\n2. Nf3 {a} {b} *\n";
+        let games = read_games(text);
+        let game = games[0].as_ref().unwrap();
+        assert_eq!(sans(&game.moves), ["e4", "e5", "Nf3"]);
+        assert_eq!(game.moves[0].comments_before, ["opening comment"]);
+        assert_eq!(game.moves[0].comments, ["best by test"]);
+        assert_eq!(game.moves[1].comments, ["rest of line"]);
+        assert_eq!(game.moves[2].comments, ["a", "b"]);
+    }
+
+    #[test]
+    fn variations() {
+        let text = "1. e4 (1. d4 d5 (1... Nf6 2. c4) 2. c4) (1. c4) 1... e5 2. Nf3 *\n";
+        let games = read_games(text);
+        let game = games[0].as_ref().unwrap();
+        assert_eq!(sans(&game.moves), ["e4", "e5", "Nf3"]);
+        let variations = &game.moves[0].variations;
+        assert_eq!(variations.len(), 2);
+        assert_eq!(sans(&variations[0]), ["d4", "d5", "c4"]);
+        assert_eq!(sans(&variations[1]), ["c4"]);
+        assert_eq!(variations[0][1].variations.len(), 1);
+        assert_eq!(sans(&variations[0][1].variations[0]), ["Nf6", "c4"]);
+
+        // the moves in a variation are played from the position before the move it replaces
+        let games = read_games("1. e4 e5 (1... e4) *\n");
+        assert!(games[0].is_err());
+        let games = read_games("1. e4 (1. d4 *\n");
+        assert!(games[0].is_err());
+        let games = read_games("1. e4 ) e5 *\n");
+        assert!(games[0].is_err());
+    }
+
+    #[test]
+    fn nags() {
+        let games = read_games("1. e4! e5?! 2. Nf3 $14 $1 Nc6?? 3. Bb5!? $255 *\n");
+        let game = games[0].as_ref().unwrap();
+        let nags: Vec<&[u8]> = game.moves.iter().map(|m| m.nags.as_slice()).collect();
+        assert_eq!(nags, [&[1][..], &[6], &[14, 1], &[4], &[5, 255]]);
+        assert_eq!(sans(&game.moves), ["e4", "e5", "Nf3", "Nc6", "Bb5"]);
+    }
+
+    #[test]
+    fn escape_lines() {
+        let text = "% produced by some tool\n[Event \"x\"]\n%[White \"hidden\"]\n\n1. e4\n% 1... c5\ne5 *\n";
+        let games = read_games(text);
+        assert_eq!(games.len(), 1);
+        let game = games[0].as_ref().unwrap();
+        assert_eq!(game.tags.len(), 1);
+        assert_eq!(game.tag("White"), None);
+        assert_eq!(sans(&game.moves), ["e4", "e5"]);
+    }
+
+    #[test]
+    fn results() {
+        let results = [
+            ("1-0", GameResult::WhiteWin),
+            ("0-1", GameResult::BlackWin),
+            ("1/2-1/2", GameResult::Draw),
+            ("*", GameResult::Unknown)
+        ];
+        for (token, result) in results {
+            let games = read_games(&format!("1. e4 e5 {}\n", token));
+            assert_eq!(games.len(), 1);
+            let game = games[0].as_ref().unwrap();
+            assert_eq!(game.result, result, "{}", token);
+            assert_eq!(sans(&game.moves), ["e4", "e5"]);
+        }
+
+        // the Result tag is the fallback for movetext with no termination marker
+        let games = read_games("[Result \"0-1\"]\n\n1. e4 e5\n");
+        assert_eq!(games[0].as_ref().unwrap().result, GameResult::BlackWin);
+    }
+
+    #[test]
+    fn multiple_games() {
+        let text = "[Event \"one\"]\n\n1. e4 e5 1-0\n\n\
+                    [Event \"two\"]\n\n1. e4 e4 0-1\n\n\
+                    [Event \"three\"]\n\n1. d4\n\
+                    [Event \"four\"]\n\n1. c4 1/2-1/2\n\n";
+        let games = read_games(text);
+        assert_eq!(games.len(), 4);
+        assert_eq!(games[0].as_ref().unwrap().tag("Event"), Some("one"));
+        // a game that fails to parse doesn't stop the ones after it
+        assert!(games[1].as_ref().unwrap_err().contains("game at line 5"));
+        // a new tag section ends a game with no termination marker
+        let game = games[2].as_ref().unwrap();
+        assert_eq!(game.tag("Event"), Some("three"));
+        assert_eq!(sans(&game.moves), ["d4"]);
+        let game = games[3].as_ref().unwrap();
+        assert_eq!(game.tag("Event"), Some("four"));
+        assert_eq!(game.result, GameResult::Draw);
+    }
+
+    #[test]
+    fn check_marks() {
+        let sans = round_trip_all("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        assert!(sans.contains(&"Ra8#".to_string()));
+        let sans = round_trip_all("6k1/8/8/8/8/8/8/R5K1 w - - 0 1");
+        assert!(sans.contains(&"Ra8+".to_string()));
+    }
 }
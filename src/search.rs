@@ -3,17 +3,21 @@ use std::time;
 use std::time::SystemTime;
 use std::thread;
 
+use crate::advisor::*;
 use crate::bitboard::*;
 use crate::eval::*;
+use crate::handbrain::*;
 use crate::moveorder::*;
 use crate::moveutil::*;
 use crate::searchparams::*;
 use crate::searchutil::*;
 use crate::syzygy::*;
 use crate::see::*;
+use crate::skill::*;
 use crate::tt::*;
 use crate::uci::*;
 use crate::util::*;
+use crate::wdl::*;
 
 static mut SEARCH_IN_PROGRESS: bool = false;
 static mut ABORT: bool = false;
@@ -21,6 +25,7 @@ static mut STOP_THREAD: bool = false;
 static mut START_TIME: u128 = 0;
 static mut SEARCH_LIMITS: SearchLimits = SearchLimits::infinite();
 static mut LAST_BESTMOVE: Move = Move::null_move();
+static mut SILENT: bool = false;
 
 const LB: i32 = -10000000;
 const UB: i32 = 10000000;
@@ -73,24 +78,50 @@ fn get_pv_str(pv: &Vec<Move>) -> String {
     return s;
 }
 
-fn get_val_str(val: i32) -> String {
-    if val.abs() < MATE_SCORE - 100000 { return format!("cp {}", val / 10); }
+// material is the root position's, for the win/draw/loss model
+fn get_val_str(val: i32, material: i32, options: &UCIOptions) -> String {
+    if val.abs() < MATE_SCORE - 100000 {
+        let cp = if options.normalize_score { normalized_cp(val, material) } else { val / 10 };
+        if options.show_wdl {
+            let (w, d, l) = wdl(val, material);
+            return format!("cp {} wdl {} {} {}", cp, w, d, l);
+        }
+        return format!("cp {}", cp);
+    }
     let mut mate_score = (((MATE_SCORE - val.abs()) as f64) / 2.).ceil() as i32;
     if val < 0 {
         mate_score = -1 * mate_score;
     }
+    if options.show_wdl {
+        let (w, d, l) = wdl(val, material);
+        return format!("mate {} wdl {} {} {}", mate_score, w, d, l);
+    }
     return format!("mate {}", mate_score);
 }
 
-fn print_info(depth: i32, seldepth: i32, pv: &Vec<Move>, val: i32, time: u128, nodes: u64, tb_hits: u64) {
+fn print_info(depth: i32, seldepth: i32, pv: &Vec<Move>, val: i32, time: u128, nodes: u64, tb_hits: u64, material: i32, options: &UCIOptions) {
     let pv_str = get_pv_str(pv);
-    let val_str = get_val_str(val);
+    let val_str = get_val_str(val, material, options);
     let nps = (nodes * 1000) as u128 / time;
     println!("info depth {} seldepth {} score {} time {} nodes {} nps {} tbhits {} multipv 1 pv {}",
              depth, seldepth, val_str, time, nodes, nps, tb_hits, pv_str
     );
 }
 
+pub fn set_silent(silent: bool) {
+    // suppress info and bestmove output for searches run by tools
+    // rather than a GUI
+    unsafe {
+        SILENT = silent;
+    }
+}
+
+fn silent() -> bool {
+    unsafe {
+        SILENT
+    }
+}
+
 pub fn abort_search() {
     // kill a search altogether
     unsafe {
@@ -129,7 +160,25 @@ pub fn thread_killed() -> bool {
     }
 }
 
+fn thread_stopped(thread_num: usize) -> bool {
+    // like thread_killed, but also honors a stop for this thread alone
+    unsafe {
+        ABORT || STOP_THREAD || TI[thread_num].stopped
+    }
+}
+
 fn check_time(search_limits: &SearchLimits) {
+    if search_limits.nodes > 0 {
+        let mut nodes = 0;
+        let threads = unsafe { &*std::ptr::addr_of!(TI) };
+        for t in threads.iter() {
+            nodes += t.nodes_searched;
+        }
+        if nodes >= search_limits.nodes {
+            abort_search();
+            return;
+        }
+    }
     if search_limits.infinite { return; }
     if search_limits.movetime == 0 && !search_limits.use_variable_time {
         return;
@@ -178,13 +227,34 @@ fn thread_handler(mut node: Bitboard, thread_depth: i32, max_depth: i32, thread_
     }
 }
 
-const OFF: u8 = 0;
-const BRAIN: u8 = 1;
-const HAND: u8 = 2;
+fn has_legal_move(node: &mut Bitboard, hand_piece: HandPiece) -> bool {
+    let mut root_movepicker = MovePicker::perft_new();
+    loop {
+        let mv = root_movepicker.next(node).0;
+        if mv.is_null() {
+            return false;
+        }
+        if hand_piece.allows(&mv) {
+            node.do_move(&mv);
+            let is_legal = !node.is_check(!node.side_to_move);
+            node.undo_move(&mv);
+            if is_legal {
+                return true;
+            }
+        }
+    }
+}
 
-pub fn best_move(node: &mut Bitboard, num_threads: u16, search_limits: SearchLimits, options: UCIOptions, bh_piece: i8) {
+pub fn best_move(node: &mut Bitboard, num_threads: u16, search_limits: SearchLimits, options: UCIOptions) -> SearchResult {
     let start_time = get_time_millis();
+    let mut result = SearchResult::new();
     let mut search_limits = search_limits;
+    // a weakened engine searches alone so its node counts mean the same thing
+    let skill = Skill::from_options(&options);
+    let num_threads = if skill.active() {1} else {num_threads};
+    if skill.active() {
+        search_limits.depth = cmp::min(search_limits.depth, skill.depth());
+    }
     let max_time = search_limits.maximum_time;
     search_limits.maximum_time = 10000;
 
@@ -210,46 +280,39 @@ pub fn best_move(node: &mut Bitboard, num_threads: u16, search_limits: SearchLim
             SS.push(new_searchstats());
             TI.push(ThreadInfo::new(options.clone()));
             // TI[i as usize].root_moves = root_moves.clone();
-            TI[i as usize].bh_piece = bh_piece;
+            TI[i as usize].set_contempt(node.side_to_move, options.contempt());
+            TI[i as usize].eval_noise = skill.eval_noise();
+            TI[i as usize].noise_seed = skill.noise_seed();
         }
     }
 
-    if options.bh_mode == HAND {
-        if bh_piece == -1 {
-            eprintln!("You didn't tell me which piece to move!");
-            unsafe {
-                SEARCH_IN_PROGRESS = false;
-            }
-            return;
+    // the hand plays whatever it can if it's given a piece that can't move,
+    // so a gui always gets a move back
+    let mut hand_piece = if options.bh_mode == HAND {options.hand_piece} else {HandPiece::Any};
+    if !has_legal_move(node, hand_piece) {
+        if !silent() {
+            println!("info string {} has no legal moves, playing any piece", hand_piece.name());
         }
-        let mut root_movepicker = MovePicker::perft_new();
-        loop {
-            let mv = root_movepicker.next(node).0;
-            if mv.is_null() {
-                unsafe {
-                    SEARCH_IN_PROGRESS = false;
-                }
-                eprintln!("There are no moves for that piece!");
-                return;
-            }
-            if mv.start == bh_piece {
-                node.do_move(&mv);
-                let is_legal = !node.is_check(!node.side_to_move);
-                node.undo_move(&mv);
-                if is_legal {
-                    break;
-                }
-            }
+        hand_piece = HandPiece::Any;
+    }
+    unsafe {
+        for i in 0..num_threads {
+            TI[i as usize].hand_piece = hand_piece;
         }
     }
 
-    if tb_active() && node.num_pieces() <= max_tb_pieces() && max_time > 0 {
+    if tb_active() && node.num_pieces() <= max_tb_pieces() && max_time > 0 && !skill.active() && options.bh_mode == OFF {
         if let Some((mv, score)) = probe_root(&node) {
             abort_search();
-            print_info(1, 1, &vec![mv], score, 1, 1, 1);
-            println!("bestmove {}", mv);
+            if !silent() {
+                print_info(1, 1, &vec![mv], score, 1, 1, 1, wdl_material(node), &options);
+                println!("bestmove {}", mv);
+            }
             unsafe {SEARCH_IN_PROGRESS = false;}
-            return;
+            result.best_move = mv;
+            result.score = score;
+            result.iterations.push(SearchIteration {depth: 1, best_move: mv, score: score, time: 0, nodes: 0});
+            return result;
         }
     }
 
@@ -264,6 +327,8 @@ pub fn best_move(node: &mut Bitboard, num_threads: u16, search_limits: SearchLim
     let mut pv: &Vec<Move>;
     let mut nodes_searched;
     let mut tb_hits;
+    let mut candidates: Vec<(Move, i32)> = Vec::new();
+    let mut piece_lines: Vec<(u8, Move, i32)> = Vec::new();
 
 
     allow_threads();
@@ -294,7 +359,7 @@ pub fn best_move(node: &mut Bitboard, num_threads: u16, search_limits: SearchLim
                 SS[0][0].pv = vec![best_move];
             }
             val = search(node, alpha, beta, depth, 0, true, 0);
-            if search_aborted() {break;}
+            if thread_stopped(0) {break;}
 
             if val > alpha && val < beta {
                 break;
@@ -304,9 +369,29 @@ pub fn best_move(node: &mut Bitboard, num_threads: u16, search_limits: SearchLim
                 aspiration_delta_low *= 2;
             }
         }
-        if search_aborted() { break; }
+        if thread_stopped(0) { break; }
         best_val = val;
 
+        unsafe {
+            // the extra lines overwrite the main pv
+            let main_pv = SS[0][0].pv.clone();
+            let mv = main_pv[0];
+            let lines = search_lines(node, depth, mv, best_val, skill.candidates());
+            // an unfinished set would favor the best move, so keep the last full one
+            if !thread_stopped(0) || candidates.is_empty() {
+                candidates = lines;
+            }
+            if options.bh_mode == BRAIN {
+                // pieces an unfinished depth didn't get to keep their last score
+                for line in search_pieces(node, depth, mv, best_val) {
+                    piece_lines.retain(|l| l.0 != line.0);
+                    piece_lines.push(line);
+                }
+                piece_lines.sort_by(|a, b| b.2.cmp(&a.2).then((b.1 == mv).cmp(&(a.1 == mv))));
+            }
+            SS[0][0].pv = main_pv;
+        }
+
         let elapsed_time;
         nodes_searched = 0;
         tb_hits = 0;
@@ -333,12 +418,17 @@ pub fn best_move(node: &mut Bitboard, num_threads: u16, search_limits: SearchLim
                 tb_hits += TI[t_num as usize].tb_hits;
             }
 
-            if options.bh_mode == OFF {
-                print_info(depth, TI[0].seldepth, &pv, best_val, current_time - start_time, nodes_searched, tb_hits);
-            } else {
-                println!("Thinking... depth {}", depth);
+            if !silent() {
+                print_info(depth, TI[0].seldepth, &pv, best_val, current_time - start_time, nodes_searched, tb_hits, wdl_material(node), &options);
             }
         }
+        result.iterations.push(SearchIteration {
+            depth: depth,
+            best_move: best_move,
+            score: best_val,
+            time: elapsed_time,
+            nodes: nodes_searched
+        });
 
         // we've obviously run out of time
         if search_limits.movetime > 0 && elapsed_time > search_limits.movetime {
@@ -371,6 +461,13 @@ pub fn best_move(node: &mut Bitboard, num_threads: u16, search_limits: SearchLim
             }
         }
 
+        // the skill node budget is checked at every node, since check_time
+        // only looks every 1024 and the weakest levels get fewer than that.
+        // like in fixed_search it only kicks in once there's a move to play
+        if skill.active() {
+            unsafe { TI[0].node_limit = skill.nodes(); }
+        }
+
         depth += 1;
     }
     stop_threads();
@@ -381,27 +478,14 @@ pub fn best_move(node: &mut Bitboard, num_threads: u16, search_limits: SearchLim
         }
     }
 
-    let emoji = if best_val <= -MIN_MATE_SCORE {
-        "XO"
-    } else if best_val <= -10000 {
-        "X("
-    } else if best_val <= -4000 {
-        ">:C"
-    } else if best_val <= -1000 {
-        ":("
-    } else if best_val < 0 {
-        ":I"
-    } else if best_val < 1000 {
-        ":|"
-    } else if best_val < 4000 {
-        ":)"
-    } else if best_val < 10000 {
-        ":D"
-    } else if best_val < MIN_MATE_SCORE {
-        ":3"
-    } else {
-        ">:3"
-    };
+    if skill.active() && candidates.len() > 1 {
+        best_move = skill.pick(&candidates);
+        for &(mv, score) in &candidates {
+            if mv == best_move {
+                best_val = score;
+            }
+        }
+    }
 
     if search_limits.infinite {
         unsafe {
@@ -410,35 +494,147 @@ pub fn best_move(node: &mut Bitboard, num_threads: u16, search_limits: SearchLim
             }
         }
     }
-    if options.bh_mode == BRAIN {
-        let piece = match best_move.piece {
-            b'k' => {"king"},
-            b'q' => {"queen"},
-            b'r' => {"rook"},
-            b'b' => {"bishop"},
-            b'n' => {"knight"},
-            b'p' => {"pawn"},
-            _ => {"???"}
-        };
-        println!("bestpiece {}", idx_to_str(best_move.start));
-        // >:C :( :| :) :D
-        // -4  -1  0  1  4
-        eprintln!("Hey, you should move the {} on {} {}", piece, idx_to_str(best_move.start), emoji);
-    } else {
-        if options.bh_mode != OFF {
-            println!("bestmove {} {}", best_move, emoji);
-        } else {
-            println!("bestmove {}", best_move);
+    if !silent() {
+        record_score(node, best_val);
+        if options.show_advice {
+            println!("info string {}", advise(node, &options));
+        }
+        if options.bh_mode == BRAIN {
+            let material = wdl_material(node);
+            for (rank, (piece, mv, score)) in piece_lines.iter().enumerate() {
+                println!("info string piece {} {} score {} move {}", rank + 1, piece_name(*piece), get_val_str(*score, material, &options), mv);
+            }
         }
+        println!("bestmove {}", best_move);
     }
     unsafe {
         LAST_BESTMOVE = best_move;
         SEARCH_IN_PROGRESS = false;
     }
+    result.best_move = best_move;
+    result.score = best_val;
+    return result;
+}
+
+// gives every independent searcher (e.g. a datagen game) its own slot in
+// TI and SS. fixed_search only touches its own slot, so these can run in parallel
+pub fn setup_search_slots(slots: usize, options: &UCIOptions) {
+    let ss = unsafe { &mut *std::ptr::addr_of_mut!(SS) };
+    let ti = unsafe { &mut *std::ptr::addr_of_mut!(TI) };
+    *ss = Vec::new();
+    *ti = Vec::new();
+    for _ in 0..slots {
+        ss.push(new_searchstats());
+        ti.push(ThreadInfo::new(options.clone()));
+    }
+}
+
+// iterative deepening to a fixed depth and/or node count, no time control
+// and no output. returns the best move and its score for the side to move
+pub fn fixed_search(node: &mut Bitboard, max_depth: i32, nodes: u64, thread_num: usize) -> (Move, i32) {
+    unsafe {
+        TI[thread_num].nodes_searched = 0;
+        TI[thread_num].seldepth = 0;
+        TI[thread_num].stopped = false;
+        TI[thread_num].node_limit = 0;
+    }
+
+    let mut best_move = Move::null_move();
+    let mut best_val = LB;
+    let mut val;
+    for depth in 1..=max_depth {
+        let mut aspiration_delta_low = 250;
+        let mut aspiration_delta_high = 250;
+        loop {
+            let mut alpha = LB;
+            let mut beta = UB;
+            if depth > 1 {
+                alpha = best_val - aspiration_delta_low;
+                beta = best_val + aspiration_delta_high;
+            }
+            unsafe {
+                SS[thread_num][0].pv = vec![best_move];
+            }
+            val = search(node, alpha, beta, depth, 0, true, thread_num);
+            if thread_stopped(thread_num) { break; }
+
+            if val > alpha && val < beta {
+                break;
+            } else if val >= beta {
+                aspiration_delta_high *= 2;
+            } else {
+                aspiration_delta_low *= 2;
+            }
+        }
+        if thread_stopped(thread_num) { break; }
+
+        best_val = val;
+        unsafe {
+            best_move = SS[thread_num][0].pv[0];
+            // the node limit only kicks in once there's a move to play
+            if nodes > 0 {
+                TI[thread_num].node_limit = nodes;
+                if TI[thread_num].nodes_searched >= nodes { break; }
+            }
+        }
+    }
+
+    unsafe {
+        TI[thread_num].node_limit = 0;
+        TI[thread_num].stopped = false;
+    }
+    return (best_move, best_val);
+}
+
+// the best score each type of piece can get.  types that can't move are
+// left out
+fn search_pieces(node: &mut Bitboard, depth: i32, best_move: Move, best_val: i32) -> Vec<(u8, Move, i32)> {
+    let mut lines = vec![(best_move.piece, best_move, best_val)];
+    for &piece in PIECE_KINDS.iter() {
+        if piece == best_move.piece || !has_legal_move(node, HandPiece::Kind(piece)) {
+            continue;
+        }
+        unsafe {
+            TI[0].hand_piece = HandPiece::Kind(piece);
+            SS[0][0].pv = Vec::new();
+            let val = search(node, LB, UB, depth, 0, true, 0);
+            if thread_stopped(0) || SS[0][0].pv.is_empty() {
+                break;
+            }
+            lines.push((piece, SS[0][0].pv[0], val));
+        }
+    }
+    unsafe {
+        TI[0].hand_piece = HandPiece::Any;
+    }
+    return lines;
+}
+
+// the best move plus the best of the rest, one at a time, up to `count`
+// lines sorted best first.  only the main thread is used
+fn search_lines(node: &mut Bitboard, depth: i32, best_move: Move, best_val: i32, count: usize) -> Vec<(Move, i32)> {
+    let mut lines = vec![(best_move, best_val)];
+    while lines.len() < count {
+        let val;
+        unsafe {
+            TI[0].root_excluded = lines.iter().map(|l| l.0).collect();
+            SS[0][0].pv = Vec::new();
+            val = search(node, LB, UB, depth, 0, true, 0);
+            if thread_stopped(0) || SS[0][0].pv.is_empty() {
+                break;
+            }
+            lines.push((SS[0][0].pv[0], val));
+        }
+    }
+    unsafe {
+        TI[0].root_excluded.clear();
+    }
+    lines.sort_by(|a, b| b.1.cmp(&a.1));
+    return lines;
 }
 
 fn search(node: &mut Bitboard, alpha: i32, beta: i32, depth: i32, ply: i32, is_pv: bool, thread_num: usize) -> i32 {
-    if thread_killed() {
+    if thread_stopped(thread_num) {
         return 0;
     }
 
@@ -454,6 +650,10 @@ fn search(node: &mut Bitboard, alpha: i32, beta: i32, depth: i32, ply: i32, is_p
             // main thread
             check_time(&SEARCH_LIMITS);
         }
+        if ti.node_limit > 0 && ti.nodes_searched >= ti.node_limit {
+            ti.stopped = true;
+            return 0;
+        }
     }
 
     let init_node = ply == 0;
@@ -472,7 +672,7 @@ fn search(node: &mut Bitboard, alpha: i32, beta: i32, depth: i32, ply: i32, is_p
     if !init_node {
         if node.is_repetition() || node.is_fifty_move() || node.insufficient_material() {
             ti.nodes_searched += 1;
-            return DRAW_SCORE;
+            return ti.draw_score[node.side_to_move as usize];
         }
 
         // mate distance pruning
@@ -550,7 +750,7 @@ fn search(node: &mut Bitboard, alpha: i32, beta: i32, depth: i32, ply: i32, is_p
                 if let Some(score) = probe_wdl(&node) {
                     ti.tb_hits += 1;
                     let (node_score, node_type) = if score == 0 {
-                        (0, PV_NODE)
+                        (ti.draw_score[node.side_to_move as usize], PV_NODE)
                     } else if score > 0 {
                         (score - ply, CUT_NODE)
                     } else {
@@ -582,7 +782,7 @@ fn search(node: &mut Bitboard, alpha: i32, beta: i32, depth: i32, ply: i32, is_p
         // so the improving flag for subsequent move is false
         sse.static_eval = MATE_SCORE + 1;
     } else if sse.excluded_move.is_null() {
-        sse.static_eval = static_eval(node, &mut ti.pht);
+        sse.static_eval = static_eval(node, &mut ti.pht) + ti.noise(node.hash);
         unsafe {STATIC_EVALS += 1; TT_VALID += if sse.tt_hit {1} else {0};}
     }
     let eval = sse.static_eval;
@@ -758,16 +958,18 @@ fn search(node: &mut Bitboard, alpha: i32, beta: i32, depth: i32, ply: i32, is_p
             break;
         }
 
-        if init_node && ti.bh_piece >= 0 {
-            if mv.start != ti.bh_piece {
-                continue;
-            }
+        if init_node && !ti.hand_piece.allows(&mv) {
+            continue;
         }
 
         if mv == sse.excluded_move {
             continue;
         }
 
+        if init_node && ti.root_excluded.contains(&mv) {
+            continue;
+        }
+
         let is_tactical = is_tactical_move(&mv, node);
         let is_quiet = is_quiet_move(&mv, node);
 
@@ -893,7 +1095,7 @@ fn search(node: &mut Bitboard, alpha: i32, beta: i32, depth: i32, ply: i32, is_p
             }
         }
         node.undo_move(&mv);
-        if thread_killed() { return 0; }
+        if thread_stopped(thread_num) { return 0; }
 
         if val > best_val {
             best_move = mv;
@@ -905,7 +1107,7 @@ fn search(node: &mut Bitboard, alpha: i32, beta: i32, depth: i32, ply: i32, is_p
         }
         if alpha >= beta {
             // fail-high
-            if !thread_killed() {
+            if !thread_stopped(thread_num) {
                 if sse.excluded_move.is_null() {
                     if is_quiet {
                         // update heuristics
@@ -934,7 +1136,7 @@ fn search(node: &mut Bitboard, alpha: i32, beta: i32, depth: i32, ply: i32, is_p
             if is_check {
                 return -MATE_SCORE + ply;
             } else {
-                return DRAW_SCORE;
+                return ti.draw_score[node.side_to_move as usize];
             }
         } else {
             return alpha;
@@ -944,7 +1146,8 @@ fn search(node: &mut Bitboard, alpha: i32, beta: i32, depth: i32, ply: i32, is_p
     best_val = cmp::min(best_val, max_val);
 
     unsafe {
-        if sse.excluded_move.is_null() && !thread_killed() {
+        // a root searched without some moves isn't the real position
+        if sse.excluded_move.is_null() && !thread_stopped(thread_num) && !(init_node && (!ti.root_excluded.is_empty() || ti.hand_piece != HandPiece::Any)) {
             TT.set(node.hash, best_move, TTEntry::make_tt_score(best_val, ply), if raised_alpha {PV_NODE} else {ALL_NODE}, depth, node.history.len() as i32);
         }
     }
@@ -959,6 +1162,10 @@ pub fn qsearch(node: &mut Bitboard, alpha: i32, beta: i32, thread_num: usize) ->
         ti = &mut TI[thread_num];
     }
     ti.nodes_searched += 1;
+    // captures can leave nothing to mate with
+    if node.insufficient_material() {
+        return ti.draw_score[node.side_to_move as usize];
+    }
     // unsafe {STATIC_EVALS += 1;}
     // if !is_check && node.is_quiet() { return static_eval(node, &mut ti.pht); }
     // unsafe {
@@ -979,7 +1186,7 @@ pub fn qsearch(node: &mut Bitboard, alpha: i32, beta: i32, thread_num: usize) ->
     // let mut raised_alpha = false;
     let mut alpha = alpha;
 
-    let stand_pat = static_eval(node, &mut ti.pht);
+    let stand_pat = static_eval(node, &mut ti.pht) + ti.noise(node.hash);
 
     let mut best_val = alpha - 1;
 
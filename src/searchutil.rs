@@ -1,5 +1,6 @@
 use std::cmp;
 
+use crate::handbrain::*;
 use crate::moveutil::*;
 use crate::searchparams::*;
 use crate::pht::*;
@@ -81,6 +82,7 @@ pub struct SearchLimits {
     pub optimum_time: u128,
     pub maximum_time: u128,
     pub depth: i32,
+    pub nodes: u64,     // 0 for no node limit
 }
 
 impl SearchLimits {
@@ -92,7 +94,8 @@ impl SearchLimits {
             movetime: movetime,
             optimum_time: 0,
             maximum_time: 0,
-            depth: MAX_DEPTH as i32
+            depth: MAX_DEPTH as i32,
+            nodes: 0
         }
     }
 
@@ -104,7 +107,8 @@ impl SearchLimits {
             movetime: 0,
             optimum_time: time_info.0,
             maximum_time: time_info.1,
-            depth: MAX_DEPTH as i32
+            depth: MAX_DEPTH as i32,
+            nodes: 0
         }
     }
 
@@ -116,7 +120,8 @@ impl SearchLimits {
             movetime: 0,
             optimum_time: time_info.0,
             maximum_time: time_info.1,
-            depth: MAX_DEPTH as i32
+            depth: MAX_DEPTH as i32,
+            nodes: 0
         }
     }
 
@@ -127,7 +132,20 @@ impl SearchLimits {
             movetime: 0,
             optimum_time: 0,
             maximum_time: 0,
-            depth: depth
+            depth: depth,
+            nodes: 0
+        }
+    }
+
+    pub fn nodes(nodes: u64) -> SearchLimits {
+        SearchLimits {
+            infinite: false,
+            use_variable_time: false,
+            movetime: 0,
+            optimum_time: 0,
+            maximum_time: 0,
+            depth: MAX_DEPTH as i32,
+            nodes: nodes
         }
     }
     pub const fn infinite() -> SearchLimits {
@@ -137,7 +155,34 @@ impl SearchLimits {
             movetime: 0,
             optimum_time: 0,
             maximum_time: 0,
-            depth: MAX_DEPTH as i32
+            depth: MAX_DEPTH as i32,
+            nodes: 0
+        }
+    }
+}
+
+// what the main thread knew at the end of each completed iteration
+#[derive(Copy, Clone)]
+pub struct SearchIteration {
+    pub depth: i32,
+    pub best_move: Move,
+    pub score: i32,
+    pub time: u128,
+    pub nodes: u64
+}
+
+pub struct SearchResult {
+    pub best_move: Move,
+    pub score: i32,
+    pub iterations: Vec<SearchIteration>
+}
+
+impl SearchResult {
+    pub fn new() -> SearchResult {
+        SearchResult {
+            best_move: Move::null_move(),
+            score: 0,
+            iterations: Vec::new()
         }
     }
 }
@@ -155,7 +200,13 @@ pub struct ThreadInfo {
     pub pht: PHT,
     pub probe_depth: i32,
     pub root_moves: Vec<Move>,
-    pub bh_piece: i8
+    pub hand_piece: HandPiece,  // the root moves the hand may play
+    pub node_limit: u64,    // 0 for none, only used by fixed_search
+    pub draw_score: [i32; 2],   // what a draw is worth to each color
+    pub root_excluded: Vec<Move>,   // root moves left out, for multiple lines
+    pub eval_noise: i32,    // the most the eval is off by when strength is limited
+    pub noise_seed: u64,
+    pub stopped: bool       // stops this thread alone
 }
 
 impl ThreadInfo {
@@ -180,8 +231,31 @@ impl ThreadInfo {
             probe_depth: options.probe_depth,
             pht: pht,
             root_moves: Vec::new(),
-            bh_piece: -1
+            hand_piece: HandPiece::Any,
+            node_limit: 0,
+            draw_score: [DRAW_SCORE; 2],
+            root_excluded: Vec::new(),
+            eval_noise: 0,
+            noise_seed: 0,
+            stopped: false
+        }
+    }
+
+    // with contempt a draw counts against the side to move at the root and
+    // for its opponent
+    pub fn set_contempt(&mut self, root_side: Color, contempt: i32) {
+        self.draw_score[root_side as usize] = DRAW_SCORE - contempt;
+        self.draw_score[!root_side as usize] = DRAW_SCORE + contempt;
+    }
+
+    // the same position always gets the same noise within a search, so
+    // the tt stays consistent
+    pub fn noise(&self, hash: u64) -> i32 {
+        if self.eval_noise == 0 {
+            return 0;
         }
+        let h = (hash ^ self.noise_seed).wrapping_mul(0x9E3779B97F4A7C15) >> 32;
+        return (h % (2 * self.eval_noise as u64 + 1)) as i32 - self.eval_noise;
     }
 
     pub fn clear_history(&mut self) {
@@ -201,7 +275,7 @@ impl ThreadInfo {
         self.followup_history = vec![[[[0; 64]; 12]; 64]; 12];
         self.countermove_history = vec![[[[0; 64]; 12]; 64]; 12];
         self.root_moves = Vec::new();
-        self.bh_piece = -1;
+        self.hand_piece = HandPiece::Any;
     }
 
     pub fn update_move_history(&mut self, mv: Move, side: Color, depth: i32, searched_moves: &Vec<Move>) {
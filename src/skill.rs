@@ -0,0 +1,112 @@
+use crate::moveutil::*;
+use crate::rand::*;
+use crate::search::get_time_millis;
+use crate::uci::*;
+
+// strength limiting.  Skill Level runs from 0 to 20, 20 being full strength,
+// and UCI_Elo picks a (fractional) level from the rating table below.
+// weaker levels search fewer nodes and less deeply, see the eval through
+// some noise, and choose among the best few root moves with a bias toward
+// the worse ones that grows as the level drops
+
+pub const MAX_SKILL_LEVEL: i32 = 20;
+
+// where full strength sits, within the 2300-2550 lichess range the README
+// gives for Mantissa
+pub const FULL_STRENGTH_ELO: i32 = 2450;
+
+// ratings of each level.  these are uncalibrated estimates, taken from short
+// depth limited matches with the handcrafted eval rather than the engine as
+// it ships, and could be off by a few hundred Elo at the low end.  until
+// calibrate.py has been run against the shipped net and its table pasted in
+// here, UCI_Elo isn't listed among the uci options
+pub const LEVEL_ELO: [i32; 21] = [
+    1380, 1407, 1434, 1460, 1487, 1562, 1637, 1713, 1788, 1836,
+    1884, 1931, 1979, 2039, 2100, 2160, 2220, 2278, 2335, 2393,
+    FULL_STRENGTH_ELO
+];
+
+const SKILL_CANDIDATES: usize = 4;
+const MAX_NOISE: f64 = 1500.0;          // internal units, at level 0
+const MAX_PICK_DELTA: i32 = 1000;       // a pawn
+
+#[derive(Copy, Clone)]
+pub struct Skill {
+    level: f64,
+    seed: u64
+}
+
+impl Skill {
+    pub fn from_options(options: &UCIOptions) -> Skill {
+        let level = if options.limit_strength {
+            elo_to_level(options.elo)
+        } else {
+            options.skill_level as f64
+        };
+        return Skill {
+            level: level,
+            seed: get_time_millis() as u64
+        };
+    }
+
+    pub fn active(&self) -> bool {
+        return self.level < MAX_SKILL_LEVEL as f64;
+    }
+
+    pub fn depth(&self) -> i32 {
+        return 1 + self.level.round() as i32;
+    }
+
+    // 64 nodes at level 0, doubling every level and a half
+    pub fn nodes(&self) -> u64 {
+        return f64::powf(2.0, 6.0 + self.level * 0.65) as u64;
+    }
+
+    pub fn eval_noise(&self) -> i32 {
+        let missing = MAX_SKILL_LEVEL as f64 - self.level;
+        return (MAX_NOISE * missing / MAX_SKILL_LEVEL as f64).round() as i32;
+    }
+
+    pub fn noise_seed(&self) -> u64 {
+        return self.seed;
+    }
+
+    pub fn candidates(&self) -> usize {
+        return if self.active() {SKILL_CANDIDATES} else {1};
+    }
+
+    // candidates are (move, score) sorted best first.  each gets a push up
+    // from how far it is behind the best move and a random share of the
+    // spread, both scaled by the weakness, and the highest pushed score wins
+    pub fn pick(&self, candidates: &[(Move, i32)]) -> Move {
+        let top = candidates[0].1;
+        let delta = (top - candidates[candidates.len() - 1].1).min(MAX_PICK_DELTA);
+        let weakness = (120.0 - 2.0 * self.level).round() as i32;
+        let mut rng = self.seed;
+        for _ in 0..16 { u64::rand_with(&mut rng); }
+
+        let mut best = candidates[0].0;
+        let mut best_push = i32::MIN;
+        for &(mv, score) in candidates {
+            let noise = (u64::rand_with(&mut rng) >> 32) % weakness as u64;
+            let push = (weakness * (top - score) + delta * noise as i32) / 128;
+            if score + push > best_push {
+                best_push = score + push;
+                best = mv;
+            }
+        }
+        return best;
+    }
+}
+
+fn elo_to_level(elo: i32) -> f64 {
+    if elo <= LEVEL_ELO[0] {
+        return 0.0;
+    }
+    for (level, pair) in LEVEL_ELO.windows(2).enumerate() {
+        if elo < pair[1] {
+            return level as f64 + (elo - pair[0]) as f64 / (pair[1] - pair[0]) as f64;
+        }
+    }
+    return MAX_SKILL_LEVEL as f64;
+}
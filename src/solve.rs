@@ -0,0 +1,110 @@
+use crate::bitboard::*;
+use crate::epd::*;
+use crate::moveutil::*;
+use crate::pgn::*;
+use crate::search::*;
+use crate::searchutil::*;
+use crate::tt::*;
+use crate::uci::*;
+
+struct SolveOutcome {
+    solved: bool,
+    // first iteration from which the engine stuck with a correct move
+    solved_depth: i32,
+    solved_time: u128,
+    found: Move,
+    nodes: u64
+}
+
+fn is_correct(mv: Move, bm: &[Move], am: &[Move]) -> bool {
+    if mv.is_null() { return false; }
+    if !bm.is_empty() && !bm.contains(&mv) { return false; }
+    return !am.contains(&mv);
+}
+
+fn solve_position(board: &mut Bitboard, bm: &[Move], am: &[Move], limits: SearchLimits, options: &UCIOptions) -> SolveOutcome {
+    clear_tt();
+    let result = best_move(board, options.num_threads, limits, options.clone());
+
+    let mut outcome = SolveOutcome {
+        solved: is_correct(result.best_move, bm, am),
+        solved_depth: 0,
+        solved_time: 0,
+        found: result.best_move,
+        nodes: 0
+    };
+    if let Some(last) = result.iterations.last() {
+        outcome.nodes = last.nodes;
+    }
+    if outcome.solved {
+        for it in result.iterations.iter().rev() {
+            if !is_correct(it.best_move, bm, am) { break; }
+            outcome.solved_depth = it.depth;
+            outcome.solved_time = it.time;
+        }
+    }
+    return outcome;
+}
+
+pub fn solve_suite(fname: &str, limits: SearchLimits, options: UCIOptions) {
+    let records = match read_epd_file(fname) {
+        Ok(r) => r,
+        Err(e) => { eprintln!("ERR: {}", e); return; }
+    };
+
+    allocate_tt(options.hash as usize);
+    set_silent(true);
+
+    let mut total = 0;
+    let mut solved = 0;
+    let mut total_time = 0;
+    let mut total_nodes = 0;
+    let mut failed: Vec<String> = Vec::new();
+    for (i, record) in records.iter().enumerate() {
+        let id = match record.id() {
+            Some(id) => id.to_string(),
+            None => format!("#{}", i + 1)
+        };
+        let (bm, am) = match (record.best_moves(), record.avoid_moves()) {
+            (Ok(bm), Ok(am)) => (bm, am),
+            (Err(e), _) | (_, Err(e)) => { eprintln!("ERR: skipping {}: {}", id, e); continue; }
+        };
+        if bm.is_empty() && am.is_empty() {
+            eprintln!("ERR: skipping {}: no bm or am operation", id);
+            continue;
+        }
+        let mut board = match record.to_board() {
+            Ok(b) => b,
+            Err(e) => { eprintln!("ERR: skipping {}: {}", id, e); continue; }
+        };
+
+        let start = get_time_millis();
+        let outcome = solve_position(&mut board, &bm, &am, limits, &options);
+        let elapsed = get_time_millis() - start;
+        total += 1;
+        total_time += elapsed;
+        total_nodes += outcome.nodes;
+
+        let found = move_to_san(&mut board, &outcome.found);
+        let expected = if !bm.is_empty() {
+            format!("bm {}", record.op("bm").unwrap().join(" "))
+        } else {
+            format!("am {}", record.op("am").unwrap().join(" "))
+        };
+        if outcome.solved {
+            solved += 1;
+            println!("{:<20} solved   {:<8} depth {:>3} time {:>7}ms  ({})",
+                     id, found, outcome.solved_depth, outcome.solved_time, expected);
+        } else {
+            println!("{:<20} FAILED   {:<8}                         ({})", id, found, expected);
+            failed.push(id);
+        }
+    }
+
+    set_silent(false);
+    println!();
+    println!("solved {}/{} in {}ms, {} nodes", solved, total, total_time, total_nodes);
+    if !failed.is_empty() {
+        println!("failed: {}", failed.join(" "));
+    }
+}
@@ -0,0 +1,534 @@
+use std::cmp;
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use crate::nnue::*;
+use crate::packed::*;
+use crate::rand::*;
+use crate::search::get_time_millis;
+use crate::util::*;
+
+// trains the 768x4 king region -> 256x2 -> 1 network on the CPU.
+//
+// every board is seen from both sides, white's features as they are and
+// black's flipped, each with the region of its own king.  the first 128
+// hidden neurons have weights for each king region and the other 128 share
+// one set across regions, which is what Network::load_default expects of
+// the embedded net.  the output is from the side to move's point of view
+// and the engine multiplies it by 9 to get internal units.  with more than
+// one output bucket there is an output head per bucket, picked by piece
+// count the way the engine does
+
+const FEATURES: usize = 768;
+const REGIONS: usize = 4;
+const HIDDEN: usize = 256;
+const REGIONAL: usize = 128;
+const SHARED: usize = HIDDEN - REGIONAL;
+const OUTPUT_SCALE: f32 = 9.0;
+
+// all parameters live in one flat vector so the optimizer and the gradient
+// reduction don't have to care about layers
+const REGIONAL_OFS: usize = 0;
+const SHARED_OFS: usize = REGIONAL_OFS + REGIONS * FEATURES * REGIONAL;
+const HIDDEN_BIAS_OFS: usize = SHARED_OFS + FEATURES * SHARED;
+// each output head is its weights followed by its bias
+const OUTPUT_OFS: usize = HIDDEN_BIAS_OFS + HIDDEN;
+const HEAD_SIZE: usize = 2 * HIDDEN + 1;
+
+fn num_params(output_buckets: usize) -> usize {
+    return OUTPUT_OFS + output_buckets * HEAD_SIZE;
+}
+
+fn output_buckets(w: &[f32]) -> usize {
+    return (w.len() - OUTPUT_OFS) / HEAD_SIZE;
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum LrSchedule {
+    Constant,
+    Step,       // multiply by gamma every step epochs
+    Cosine      // decay to lr * gamma over the run
+}
+
+impl LrSchedule {
+    pub fn from_str(s: &str) -> Option<LrSchedule> {
+        return match s {
+            "constant" => Some(LrSchedule::Constant),
+            "step" => Some(LrSchedule::Step),
+            "cosine" => Some(LrSchedule::Cosine),
+            _ => None
+        };
+    }
+}
+
+pub struct TrainOptions {
+    pub epochs: usize,
+    pub start_epoch: usize,     // for picking the schedule back up after a resume
+    pub batch_size: usize,
+    pub chunk_size: usize,      // positions read and shuffled together
+    pub learning_rate: f32,
+    pub schedule: LrSchedule,
+    pub lr_step: usize,
+    pub lr_gamma: f32,
+    pub lambda: f32,            // weight of the score in the target, the rest is the result
+    pub k: f32,                 // sigmoid scale, per internal unit like tune's K
+    pub output_buckets: usize,
+    pub threads: usize,
+    pub resume: Option<String>,
+    pub out: String,
+    pub save_every: usize,
+    pub tolerance: f32,         // allowed engine/trainer difference in the final check
+    pub seed: u64
+}
+
+impl TrainOptions {
+    pub fn default() -> TrainOptions {
+        TrainOptions {
+            epochs: 10,
+            start_epoch: 1,
+            batch_size: 16384,
+            chunk_size: 1 << 22,
+            learning_rate: 0.001,
+            schedule: LrSchedule::Step,
+            lr_step: 4,
+            lr_gamma: 0.3,
+            lambda: 0.5,
+            k: 0.000388,
+            output_buckets: 1,
+            threads: 1,
+            resume: None,
+            out: "net.nnue".to_string(),
+            save_every: 1,
+            tolerance: 10.0,
+            seed: 0
+        }
+    }
+
+    fn lr(&self, epoch: usize) -> f32 {
+        return match self.schedule {
+            LrSchedule::Constant => self.learning_rate,
+            LrSchedule::Step => self.learning_rate * self.lr_gamma.powi(((epoch - 1) / cmp::max(self.lr_step, 1)) as i32),
+            LrSchedule::Cosine => {
+                let progress = (epoch - 1) as f32 / cmp::max(self.epochs - 1, 1) as f32;
+                let end = self.learning_rate * self.lr_gamma;
+                end + 0.5 * (self.learning_rate - end) * (1.0 + (std::f32::consts::PI * progress).cos())
+            }
+        };
+    }
+}
+
+// a position as the trainer sees it: the active features for each
+// perspective, side to move's first, and the blended target
+struct Sample {
+    features: [[u16; 32]; 2],
+    count: usize,
+    bucket: usize,
+    target: f32
+}
+
+fn sigmoid(x: f32) -> f32 {
+    return 1.0 / (1.0 + (-x).exp());
+}
+
+fn sample(p: &PackedPosition, options: &TrainOptions) -> Sample {
+    let pieces = p.piece_boards();
+    let white = Color::White as usize;
+    let black = Color::Black as usize;
+    let wkr = region(pieces[white][KING as usize].trailing_zeros() as i8) as usize * FEATURES;
+    let bkr = region((pieces[black][KING as usize].trailing_zeros() ^ 56) as i8) as usize * FEATURES;
+
+    let mut white_features = [0; 32];
+    let mut black_features = [0; 32];
+    let mut count = 0;
+    for (color, boards) in pieces.iter().enumerate() {
+        for (piece, bb) in boards.iter().enumerate() {
+            let mut bb = *bb;
+            while bb != 0 && count < 32 {
+                let input = input_number(piece as u8, color == white, bb.trailing_zeros() as i32);
+                white_features[count] = (wkr + input) as u16;
+                black_features[count] = (bkr + flip_input(input as i16) as usize) as u16;
+                count += 1;
+                bb &= bb - 1;
+            }
+        }
+    }
+
+    let stm_white = p.side_to_move() == Color::White;
+    let (result, score) = if stm_white {
+        (p.outcome() as f32, p.score as f32)
+    } else {
+        (1.0 - p.outcome() as f32, -p.score as f32)
+    };
+    // scores are stored in centipawns, K is per internal unit
+    let target = options.lambda * sigmoid(options.k * score * 10.0) + (1.0 - options.lambda) * result;
+    let features = if stm_white { [white_features, black_features] } else { [black_features, white_features] };
+    return Sample {
+        features: features,
+        count: count,
+        bucket: output_bucket(count as u32, options.output_buckets),
+        target: target
+    };
+}
+
+fn accumulate(w: &[f32], features: &[u16]) -> [f32; HIDDEN] {
+    let mut acc = [0.0; HIDDEN];
+    acc.copy_from_slice(&w[HIDDEN_BIAS_OFS..HIDDEN_BIAS_OFS + HIDDEN]);
+    for f in features {
+        let f = *f as usize;
+        let regional = &w[REGIONAL_OFS + f * REGIONAL..][..REGIONAL];
+        let shared = &w[SHARED_OFS + (f % FEATURES) * SHARED..][..SHARED];
+        for i in 0..REGIONAL {
+            acc[i] += regional[i];
+        }
+        for i in 0..SHARED {
+            acc[REGIONAL + i] += shared[i];
+        }
+    }
+    return acc;
+}
+
+// the raw network output for the side to move
+fn forward(w: &[f32], s: &Sample) -> f32 {
+    let head = OUTPUT_OFS + s.bucket * HEAD_SIZE;
+    let mut out = w[head + 2 * HIDDEN];
+    for (p, features) in s.features.iter().enumerate() {
+        let acc = accumulate(w, &features[..s.count]);
+        let weights = &w[head + p * HIDDEN..][..HIDDEN];
+        for i in 0..HIDDEN {
+            out += acc[i].max(0.0) * weights[i];
+        }
+    }
+    return out;
+}
+
+// adds this sample's gradient into grad, returning its loss
+fn backward(w: &[f32], s: &Sample, k: f32, grad: &mut [f32]) -> f32 {
+    let accs = [accumulate(w, &s.features[0][..s.count]), accumulate(w, &s.features[1][..s.count])];
+    let head = OUTPUT_OFS + s.bucket * HEAD_SIZE;
+    let mut out = w[head + 2 * HIDDEN];
+    for (p, acc) in accs.iter().enumerate() {
+        let weights = &w[head + p * HIDDEN..][..HIDDEN];
+        for i in 0..HIDDEN {
+            out += acc[i].max(0.0) * weights[i];
+        }
+    }
+
+    let scale = k * OUTPUT_SCALE;
+    let prediction = sigmoid(scale * out);
+    let err = prediction - s.target;
+    let g = 2.0 * err * prediction * (1.0 - prediction) * scale;
+
+    grad[head + 2 * HIDDEN] += g;
+    for (p, acc) in accs.iter().enumerate() {
+        let mut d_acc = [0.0; HIDDEN];
+        for i in 0..HIDDEN {
+            if acc[i] > 0.0 {
+                grad[head + p * HIDDEN + i] += g * acc[i];
+                d_acc[i] = g * w[head + p * HIDDEN + i];
+            }
+        }
+        for i in 0..HIDDEN {
+            grad[HIDDEN_BIAS_OFS + i] += d_acc[i];
+        }
+        for f in &s.features[p][..s.count] {
+            let f = *f as usize;
+            let regional = &mut grad[REGIONAL_OFS + f * REGIONAL..][..REGIONAL];
+            for i in 0..REGIONAL {
+                regional[i] += d_acc[i];
+            }
+            let shared = &mut grad[SHARED_OFS + (f % FEATURES) * SHARED..][..SHARED];
+            for i in 0..SHARED {
+                shared[i] += d_acc[REGIONAL + i];
+            }
+        }
+    }
+    return err * err;
+}
+
+fn uniform(rng: &mut u64, limit: f32) -> f32 {
+    let x = (u64::rand_with(rng) >> 11) as f32 / (1u64 << 53) as f32;
+    return (2.0 * x - 1.0) * limit;
+}
+
+fn init_params(rng: &mut u64, output_buckets: usize) -> Vec<f32> {
+    let mut w = vec![0.0; num_params(output_buckets)];
+    for x in w[REGIONAL_OFS..HIDDEN_BIAS_OFS].iter_mut() {
+        *x = uniform(rng, 1.0 / (32.0f32).sqrt());
+    }
+    for head in w[OUTPUT_OFS..].chunks_mut(HEAD_SIZE) {
+        for x in head[..2 * HIDDEN].iter_mut() {
+            *x = uniform(rng, 1.0 / ((2 * HIDDEN) as f32).sqrt());
+        }
+    }
+    return w;
+}
+
+// the feature weights for one input row of the file, which is laid out as
+// the engine sees it: 3072 inputs by 512 hidden, the second half being the
+// flipped perspective's copy
+fn feature_weights(w: &[f32], row: usize, out: &mut Vec<f32>) {
+    let kr = row / FEATURES;
+    let input = row % FEATURES;
+    for mirrored in [input, flip_input(input as i16) as usize] {
+        out.extend_from_slice(&w[REGIONAL_OFS + (kr * FEATURES + mirrored) * REGIONAL..][..REGIONAL]);
+        out.extend_from_slice(&w[SHARED_OFS + mirrored * SHARED..][..SHARED]);
+    }
+}
+
+// writes the BZ version 2 format SlowNetwork::load and Network::load read
+pub fn write_net(w: &[f32], fname: &str) -> Result<(), String> {
+    let write = || -> std::io::Result<()> {
+        let mut f = BufWriter::new(File::create(fname)?);
+        f.write_all(&[b'B', b'Z', 2, 0])?;
+        let buckets = output_buckets(w);
+        let header: [u32; 5] = [0, (FEATURES * REGIONS) as u32, buckets as u32, 1, 2 * HIDDEN as u32];
+        for x in header.iter() {
+            f.write_all(&x.to_le_bytes())?;
+        }
+
+        let mut values = Vec::with_capacity(2 * HIDDEN);
+        for row in 0..(FEATURES * REGIONS) {
+            values.clear();
+            feature_weights(w, row, &mut values);
+            for x in values.iter() {
+                f.write_all(&x.to_le_bytes())?;
+            }
+        }
+        for _ in 0..2 {
+            for x in w[HIDDEN_BIAS_OFS..HIDDEN_BIAS_OFS + HIDDEN].iter() {
+                f.write_all(&x.to_le_bytes())?;
+            }
+        }
+        // the output layer is stored input by input, then the biases
+        for i in 0..2 * HIDDEN {
+            for b in 0..buckets {
+                f.write_all(&w[OUTPUT_OFS + b * HEAD_SIZE + i].to_le_bytes())?;
+            }
+        }
+        for b in 0..buckets {
+            f.write_all(&w[OUTPUT_OFS + b * HEAD_SIZE + 2 * HIDDEN].to_le_bytes())?;
+        }
+        return f.flush();
+    };
+    return write().map_err(|e| format!("unable to write {}: {}", fname, e));
+}
+
+// picks up the weights of a network written by write_net.  the shared
+// neurons come from the first king region
+fn read_net(fname: &str) -> Result<Vec<f32>, String> {
+    let net = match SlowNetwork::load(fname) {
+        Ok(n) => n,
+        Err(e) => { return Err(format!("unable to load {}: {}", fname, e)); }
+    };
+    if net.weights.len() != 2 || net.weights[0].rows != 2 * HIDDEN || net.weights[0].cols != FEATURES * REGIONS {
+        return Err(format!("{} isn't a 768x4 -> 256x2 -> n network", fname));
+    }
+
+    let buckets = net.weights[1].rows;
+    let mut w = vec![0.0; num_params(buckets)];
+    for row in 0..(FEATURES * REGIONS) {
+        for i in 0..REGIONAL {
+            w[REGIONAL_OFS + row * REGIONAL + i] = net.weights[0].get(i, row);
+        }
+    }
+    for row in 0..FEATURES {
+        for i in 0..SHARED {
+            w[SHARED_OFS + row * SHARED + i] = net.weights[0].get(REGIONAL + i, row);
+        }
+    }
+    w[HIDDEN_BIAS_OFS..OUTPUT_OFS].copy_from_slice(&net.biases[0].data[..HIDDEN]);
+    for b in 0..buckets {
+        let head = OUTPUT_OFS + b * HEAD_SIZE;
+        for i in 0..2 * HIDDEN {
+            w[head + i] = net.weights[1].get(b, i);
+        }
+        w[head + 2 * HIDDEN] = net.biases[1].data[b];
+    }
+    return Ok(w);
+}
+
+// Adam's step count and moments, written next to every network so that a
+// resumed run carries on with the optimizer where it stopped.  little
+// endian, the step as a u64 and then m and v as f32s
+fn optimizer_name(net: &str) -> String {
+    return format!("{}.adam", net);
+}
+
+fn write_optimizer(net: &str, step: i32, m: &[f32], v: &[f32]) -> Result<(), String> {
+    let fname = optimizer_name(net);
+    let write = || -> std::io::Result<()> {
+        let mut f = BufWriter::new(File::create(&fname)?);
+        f.write_all(&(step as u64).to_le_bytes())?;
+        for x in m.iter().chain(v.iter()) {
+            f.write_all(&x.to_le_bytes())?;
+        }
+        return f.flush();
+    };
+    return write().map_err(|e| format!("unable to write {}: {}", fname, e));
+}
+
+// None if the network has no optimizer state next to it
+fn read_optimizer(net: &str, num_params: usize) -> Result<Option<(i32, Vec<f32>, Vec<f32>)>, String> {
+    let fname = optimizer_name(net);
+    let bytes = match std::fs::read(&fname) {
+        Ok(b) => b,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => { return Ok(None); },
+        Err(e) => { return Err(format!("unable to read {}: {}", fname, e)); }
+    };
+    if bytes.len() != 8 + 8 * num_params {
+        return Err(format!("{} doesn't match the network's {} parameters", fname, num_params));
+    }
+    let step = u64::from_le_bytes(bytes[..8].try_into().unwrap()) as i32;
+    let floats: Vec<f32> = bytes[8..].chunks_exact(4).map(|b| f32::from_le_bytes(b.try_into().unwrap())).collect();
+    return Ok(Some((step, floats[..num_params].to_vec(), floats[num_params..].to_vec())));
+}
+
+fn checkpoint_name(out: &str, epoch: usize) -> String {
+    return match out.rfind('.') {
+        Some(i) if !out[i..].contains('/') => format!("{}-e{}{}", &out[..i], epoch, &out[i..]),
+        _ => format!("{}-e{}", out, epoch)
+    };
+}
+
+// gradient of a batch summed over threads, and the summed loss
+fn batch_gradient(w: &[f32], batch: &[Sample], k: f32, grads: &mut [Vec<f32>]) -> f32 {
+    let chunk_size = cmp::max(1, batch.len().div_ceil(grads.len()));
+    let losses: Vec<f32> = std::thread::scope(|s| {
+        let handles: Vec<_> = grads.iter_mut().zip(batch.chunks(chunk_size)).map(|(grad, chunk)| {
+            s.spawn(move || {
+                grad.iter_mut().for_each(|g| *g = 0.0);
+                chunk.iter().map(|sample| backward(w, sample, k, grad)).sum::<f32>()
+            })
+        }).collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+
+    let used = cmp::min(grads.len(), batch.len().div_ceil(chunk_size));
+    let (first, rest) = grads.split_at_mut(1);
+    for other in rest[..used - 1].iter() {
+        for (a, b) in first[0].iter_mut().zip(other.iter()) {
+            *a += b;
+        }
+    }
+    return losses.iter().sum();
+}
+
+// makes sure what was written is what the engine will evaluate.  the engine
+// runs quantized, so it's allowed to be off by the tolerance plus 1%
+fn check_net(w: &[f32], fname: &str, positions: &[PackedPosition], tolerance: f32) -> Result<(), String> {
+    let net = Network::load(fname)?;
+    let options = TrainOptions { output_buckets: output_buckets(w), ..TrainOptions::default() };
+    let mut worst: f32 = 0.0;
+    let mut failed = 0;
+    for p in positions {
+        let mut pos = p.to_board();
+        pos.net = net.copy();
+        pos.set_activations();
+        let engine = pos.nnue_eval() as f32;
+        let trainer = forward(w, &sample(p, &options)) * OUTPUT_SCALE;
+        let trainer = if p.side_to_move() == Color::White { trainer } else { -trainer };
+        let diff = (engine - trainer).abs();
+        worst = worst.max(diff);
+        if diff > tolerance + trainer.abs() / 100.0 {
+            failed += 1;
+        }
+    }
+    println!("checked {} positions against the engine, largest difference {:.2}", positions.len(), worst);
+    if failed > 0 {
+        return Err(format!("the engine disagrees with the trainer on {} of {} positions, the network wasn't written or loaded correctly", failed, positions.len()));
+    }
+    return Ok(());
+}
+
+pub fn train(fname: &str, options: &TrainOptions) -> Result<(), String> {
+    let mut rng = options.seed ^ 0x9e3779b97f4a7c15;
+    for _ in 0..64 { u64::rand_with(&mut rng); }
+    let mut w = match &options.resume {
+        Some(net) => read_net(net)?,
+        None => init_params(&mut rng, options.output_buckets)
+    };
+
+    let threads = cmp::max(options.threads, 1);
+    if output_buckets(&w) != options.output_buckets {
+        return Err(format!("the resumed network has {} output buckets, not {}", output_buckets(&w), options.output_buckets));
+    }
+    let num_params = w.len();
+    let mut grads = vec![vec![0.0; num_params]; threads];
+    let (beta1, beta2, epsilon): (f32, f32, f32) = (0.9, 0.999, 1e-8);
+    let mut m = vec![0.0; num_params];
+    let mut v = vec![0.0; num_params];
+    let mut step = 0;
+    if let Some(net) = &options.resume {
+        match read_optimizer(net, num_params)? {
+            Some(state) => { (step, m, v) = state; },
+            None => { eprintln!("ERR: no optimizer state in {}, so Adam starts over from the resumed weights", optimizer_name(net)); }
+        }
+    }
+    let mut last_chunk: Vec<PackedPosition> = Vec::new();
+
+    for epoch in options.start_epoch..=options.epochs {
+        let start = get_time_millis();
+        let lr = options.lr(epoch);
+        let mut reader = DataReader::open(fname)?;
+        let mut total_loss = 0.0;
+        let mut positions = 0;
+        let mut skipped = 0;
+        loop {
+            // read a chunk and shuffle it, the data is streamed so it never
+            // has to fit in memory
+            let mut chunk = Vec::with_capacity(options.chunk_size);
+            for p in reader.by_ref() {
+                match p {
+                    Ok(p) => { chunk.push(p); },
+                    Err(e) if is_packed_file(fname) => { return Err(e); },
+                    Err(_) => { skipped += 1; }
+                }
+                if chunk.len() >= options.chunk_size { break; }
+            }
+            if chunk.is_empty() { break; }
+            for i in (1..chunk.len()).rev() {
+                chunk.swap(i, (u64::rand_with(&mut rng) % (i as u64 + 1)) as usize);
+            }
+            let samples: Vec<Sample> = chunk.iter().map(|p| sample(p, options)).collect();
+
+            for batch in samples.chunks(cmp::max(options.batch_size, 1)) {
+                total_loss += batch_gradient(&w, batch, options.k, &mut grads) as f64;
+                positions += batch.len();
+
+                step += 1;
+                let correction1 = 1.0 - beta1.powi(step);
+                let correction2 = 1.0 - beta2.powi(step);
+                let n = batch.len() as f32;
+                for i in 0..num_params {
+                    let g = grads[0][i] / n;
+                    m[i] = beta1 * m[i] + (1.0 - beta1) * g;
+                    v[i] = beta2 * v[i] + (1.0 - beta2) * g * g;
+                    w[i] -= lr * (m[i] / correction1) / ((v[i] / correction2).sqrt() + epsilon);
+                }
+            }
+            chunk.truncate(1000);
+            last_chunk = chunk;
+        }
+        if positions == 0 {
+            return Err(format!("no positions in {}", fname));
+        }
+        if skipped > 0 && epoch == options.start_epoch {
+            eprintln!("ERR: skipped {} lines that weren't positions with a result", skipped);
+        }
+
+        let elapsed = cmp::max(get_time_millis() - start, 1);
+        println!("epoch {:>4} lr {:.6} loss {:.7} ({} positions, {}ms, {} positions/s)",
+                 epoch, lr, total_loss / positions as f64, positions, elapsed, (positions as u128 * 1000) / elapsed);
+        if options.save_every > 0 && epoch % options.save_every == 0 && epoch != options.epochs {
+            let checkpoint = checkpoint_name(&options.out, epoch);
+            write_net(&w, &checkpoint)?;
+            write_optimizer(&checkpoint, step, &m, &v)?;
+        }
+    }
+
+    write_net(&w, &options.out)?;
+    write_optimizer(&options.out, step, &m, &v)?;
+    println!("wrote {}", options.out);
+    return check_net(&w, &options.out, &last_chunk, options.tolerance);
+}
@@ -131,6 +131,7 @@ impl TT {
     pub fn set(&mut self, hash: u64, mv: Move, value: i32, node_type: u8, depth: i32, ply: i32) {
         let idx: usize = (hash & self.mask) as usize;
         let depth = depth as i8;
+        // plies are only kept mod 256, so entry ages have to wrap with them
         let ply = ply as i8;
         let mut l = self.locks[(hash % 4096) as usize].lock().unwrap();
         let row = &mut self.tt[idx];
@@ -156,7 +157,7 @@ impl TT {
             if e2.valid() && e2.hash == hash {
                 row[1] = entry;
                 return;
-            } else if !e1.valid() || e1.depth <= depth || (ply - e1.ply) as i32 > age_threshold(e1.depth, depth) {
+            } else if !e1.valid() || e1.depth <= depth || ply.wrapping_sub(e1.ply) as i32 > age_threshold(e1.depth, depth) {
                 row[0] = entry;
                 return;
             } else {
@@ -1,534 +1,32 @@
 use std::cmp;
-use std::iter::FromIterator;
 use std::fs::File;
-use std::io::{BufReader, BufRead, BufWriter, Write};
+use std::io::{BufReader, BufRead};
 
 use crate::bitboard::*;
-use crate::tuning_eval::*;
+use crate::eval::*;
+use crate::evalparams::*;
+use crate::packed::*;
 use crate::rand::*;
 use crate::search::get_time_millis;
 use crate::see::*;
 use crate::moveorder::*;
-use crate::tuning_psqt::*;
 use crate::util::*;
 
 const LB: i32 = -10000000;
-const UB: i32 = -10000000;
-
-macro_rules! S {
-    ($a:expr, $b:expr) => {
-        make_score($a, $b)
-    }
-}
-
-// queen_value
-// rook_value
-// bishop_value
-// knight_value
-// pawn_value
-
-// knight_mobility
-// bishop_mobility
-// rook_mobility
-// queen_mobility
-
-// queen_king_danger
-// rook_king_danger
-// bishop_king_danger
-// knight_king_danger
-// king danger scale
-
-// double_bishop_bonus
-
-// passed_pawn_value
-// center_pawn_value
-// isolated_pawn_value
-// doubled_pawn_value
-// backwards_pawn_value
-// advanced_pawn_value
-// supported_pawn_bonus
-// space_value
-
-// bishop_color
-
-// rook_on_seventh
-// rook_on_open
-
-// tempo_bonus
-
-fn set_mg_score(score: Score, val: i32) -> i64 {
-    S!(val, eg_score(score))
-}
-
-fn set_eg_score(score: Score, val: i32) -> i64 {
-    S!(mg_score(score), val)
-}
+const UB: i32 = 10000000;
 
+// the tuner works on the flat vector view of EvalParams,
+// see EvalParams::to_vector for the layout
 pub fn get_params_vector() -> Vec<i32> {
-    let mut v = Vec::new();
-
-    for i in 0..728 {
-        v.push(get_param(i));
-    }
-
-    return v;
-}
-
-fn load_params_vector(v: &Vec<i32>) {
-    for i in 0..728 {
-        set_param(i, v[i]);
-    }
+    return eval_params().to_vector();
 }
 
-fn print_score(name: &str, mg: i32, eg: i32) {
-    println!("{} S!({}, {})", name, mg, eg);
-}
-
-fn print_score_vector(name: &str, v_mg: &Vec<i32>, v_eg: &Vec<i32>) {
-    let mut s = format!("");
-    for i in 0..v_mg.len() {
-        if s.len() > 0 {
-            s = format!("{}, S!({}, {})", s, v_mg[i], v_eg[i]);
-        } else {
-            s = format!("S!({}, {})", v_mg[i], v_eg[i]);
-        }
-    }
-    println!("{} [{}]", name, s);
-}
-
-fn v_copy(v: &Vec<i32>, l: usize, u: usize) -> Vec<i32> {
-    Vec::from_iter(v[l..u].iter().cloned())
-}
-
-pub fn print_params_vector(v: &Vec<i32>) {
-    print_score("queen value", v[0], v[1]);
-    print_score("rook value", v[2], v[3]);
-    print_score("bishop value", v[4], v[5]);
-    print_score("knight value", v[6], v[7]);
-    print_score("pawn value", v[8], v[9]);
-
-    print_score_vector("knight mobility", &v_copy(v, 10, 19), &v_copy(v, 19, 28));
-    print_score_vector("bishop mobility", &v_copy(v, 28, 42), &v_copy(v, 42, 56));
-    print_score_vector("rook mobility", &v_copy(v, 56, 71), &v_copy(v, 71, 86));
-    print_score_vector("queen mobility", &v_copy(v, 86, 114), &v_copy(v, 114, 142));
-
-    print_score_vector("queen king danger", &v_copy(v, 142, 150), &v_copy(v, 606, 614));
-    print_score_vector("rook king danger", &v_copy(v, 150, 158), &v_copy(v, 614, 622));
-    print_score_vector("bishop king danger", &v_copy(v, 158, 166), &v_copy(v, 622, 630));
-    print_score_vector("knight king danger", &v_copy(v, 166, 174), &v_copy(v, 630, 638));
-    print_score("no queen attack value", v[598], v[599]);
-    print_score("weak square", v[718], v[719]);
-    print_score("queen check", v[720], v[721]);
-    print_score("rook check", v[722], v[723]);
-    print_score("bishop check", v[724], v[725]);
-    print_score("knight check", v[726], v[727]);
-
-    print_score("double bishop", v[174], v[175]);
-    print_score("bishop color", v[584], v[585]);
-    print_score("bishop long diagonal", v[604], v[605]);
-
-    print_score("knight outpost", v[600], v[601]);
-    print_score("bishop outpost", v[602], v[603]);
-
-    print_score_vector("candidate passed pawn", &v_copy(v, 586, 592), &v_copy(v, 592, 598));
-    print_score_vector("passed pawn", &v_copy(v, 176, 182), &v_copy(v, 182, 188));
-    print_score("center pawn", v[188], v[189]);
-    print_score("isolated pawn", v[190], v[191]);
-    print_score("doubled pawn", v[192], v[193]);
-    print_score("backwards pawn", v[194], v[195]);
-    print_score_vector("advanced pawn", &v_copy(v, 196, 202), &v_copy(v, 202, 208));
-    print_score("supported pawn", v[208], v[209]);
-    print_score("space", v[210], v[211]);
-    print_score_vector("pawn_proximity", &v_copy(v, 638, 646), &v_copy(v, 646, 654));
-    print_score_vector("pawn_shelter non-king file", &v_copy(v, 654, 662), &v_copy(v, 662, 670));
-    print_score_vector("pawn_shelter king file", &v_copy(v, 670, 678), &v_copy(v, 678, 686));
-    print_score_vector("pawn_storm non-king file", &v_copy(v, 686, 694), &v_copy(v, 694, 702));
-    print_score_vector("pawn_storm king file", &v_copy(v, 702, 710), &v_copy(v, 710, 718));
-
-    print_score("rook on 7th", v[212], v[213]);
-    print_score("rook on open", v[214], v[215]);
-
-    print_score_vector("pawn psqt 2", &v_copy(v, 216, 220), &v_copy(v, 240, 244));
-    print_score_vector("pawn psqt 3", &v_copy(v, 220, 224), &v_copy(v, 244, 248));
-    print_score_vector("pawn psqt 4", &v_copy(v, 224, 228), &v_copy(v, 248, 252));
-    print_score_vector("pawn psqt 5", &v_copy(v, 228, 232), &v_copy(v, 252, 256));
-    print_score_vector("pawn psqt 6", &v_copy(v, 232, 236), &v_copy(v, 256, 260));
-    print_score_vector("pawn psqt 7", &v_copy(v, 236, 240), &v_copy(v, 260, 264));
-
-    print_score_vector("knight psqt 1", &v_copy(v, 264, 268), &v_copy(v, 296, 300));
-    print_score_vector("knight psqt 2", &v_copy(v, 268, 272), &v_copy(v, 300, 304));
-    print_score_vector("knight psqt 3", &v_copy(v, 272, 276), &v_copy(v, 304, 308));
-    print_score_vector("knight psqt 4", &v_copy(v, 276, 280), &v_copy(v, 308, 312));
-    print_score_vector("knight psqt 5", &v_copy(v, 280, 284), &v_copy(v, 312, 316));
-    print_score_vector("knight psqt 6", &v_copy(v, 284, 288), &v_copy(v, 316, 320));
-    print_score_vector("knight psqt 7", &v_copy(v, 288, 292), &v_copy(v, 320, 324));
-    print_score_vector("knight psqt 8", &v_copy(v, 292, 296), &v_copy(v, 324, 328));
-
-    print_score_vector("bishop psqt 1", &v_copy(v, 328, 332), &v_copy(v, 360, 364));
-    print_score_vector("bishop psqt 2", &v_copy(v, 332, 336), &v_copy(v, 364, 368));
-    print_score_vector("bishop psqt 3", &v_copy(v, 336, 340), &v_copy(v, 368, 372));
-    print_score_vector("bishop psqt 4", &v_copy(v, 340, 344), &v_copy(v, 372, 376));
-    print_score_vector("bishop psqt 5", &v_copy(v, 344, 348), &v_copy(v, 376, 380));
-    print_score_vector("bishop psqt 6", &v_copy(v, 348, 352), &v_copy(v, 380, 384));
-    print_score_vector("bishop psqt 7", &v_copy(v, 352, 356), &v_copy(v, 384, 388));
-    print_score_vector("bishop psqt 8", &v_copy(v, 356, 360), &v_copy(v, 388, 392));
-
-    print_score_vector("rook psqt 1", &v_copy(v, 392, 396), &v_copy(v, 424, 428));
-    print_score_vector("rook psqt 2", &v_copy(v, 396, 400), &v_copy(v, 428, 432));
-    print_score_vector("rook psqt 3", &v_copy(v, 400, 404), &v_copy(v, 432, 436));
-    print_score_vector("rook psqt 4", &v_copy(v, 404, 408), &v_copy(v, 436, 440));
-    print_score_vector("rook psqt 5", &v_copy(v, 408, 412), &v_copy(v, 440, 444));
-    print_score_vector("rook psqt 6", &v_copy(v, 412, 416), &v_copy(v, 444, 448));
-    print_score_vector("rook psqt 7", &v_copy(v, 416, 420), &v_copy(v, 448, 452));
-    print_score_vector("rook psqt 8", &v_copy(v, 420, 424), &v_copy(v, 452, 456));
-
-    print_score_vector("queen psqt 1", &v_copy(v, 456, 460), &v_copy(v, 488, 492));
-    print_score_vector("queen psqt 2", &v_copy(v, 460, 464), &v_copy(v, 492, 496));
-    print_score_vector("queen psqt 3", &v_copy(v, 464, 468), &v_copy(v, 496, 500));
-    print_score_vector("queen psqt 4", &v_copy(v, 468, 472), &v_copy(v, 500, 504));
-    print_score_vector("queen psqt 5", &v_copy(v, 472, 476), &v_copy(v, 504, 508));
-    print_score_vector("queen psqt 6", &v_copy(v, 476, 480), &v_copy(v, 508, 512));
-    print_score_vector("queen psqt 7", &v_copy(v, 480, 484), &v_copy(v, 512, 516));
-    print_score_vector("queen psqt 8", &v_copy(v, 484, 488), &v_copy(v, 516, 520));
-
-    print_score_vector("king psqt 1", &v_copy(v, 520, 524), &v_copy(v, 552, 556));
-    print_score_vector("king psqt 2", &v_copy(v, 524, 528), &v_copy(v, 556, 560));
-    print_score_vector("king psqt 3", &v_copy(v, 528, 532), &v_copy(v, 560, 564));
-    print_score_vector("king psqt 4", &v_copy(v, 532, 536), &v_copy(v, 564, 568));
-    print_score_vector("king psqt 5", &v_copy(v, 536, 540), &v_copy(v, 568, 572));
-    print_score_vector("king psqt 6", &v_copy(v, 540, 544), &v_copy(v, 572, 576));
-    print_score_vector("king psqt 7", &v_copy(v, 544, 548), &v_copy(v, 576, 580));
-    print_score_vector("king psqt 8", &v_copy(v, 548, 552), &v_copy(v, 580, 584));
-}
-
-fn get_param(idx: usize) -> i32 {
-    unsafe {
-        return match idx {
-            0 => { mg_score(QUEEN_VALUE) },
-            1 => { eg_score(QUEEN_VALUE) },
-            2 => { mg_score(ROOK_VALUE) },
-            3 => { eg_score(ROOK_VALUE) },
-            4 => { mg_score(BISHOP_VALUE) },
-            5 => { eg_score(BISHOP_VALUE) },
-            6 => { mg_score(KNIGHT_VALUE) },
-            7 => { eg_score(KNIGHT_VALUE) },
-            8 => { mg_score(PAWN_VALUE) },
-            9 => { eg_score(PAWN_VALUE) },
-
-            10..19 => { mg_score(KNIGHT_MOBILITY[idx - 10]) },
-            19..28 => { eg_score(KNIGHT_MOBILITY[idx - 19]) },
-            28..42 => { mg_score(BISHOP_MOBILITY[idx - 28]) },
-            42..56 => { eg_score(BISHOP_MOBILITY[idx - 42]) },
-            56..71 => { mg_score(ROOK_MOBILITY[idx - 56]) },
-            71..86 => { eg_score(ROOK_MOBILITY[idx - 71]) },
-            86..114 => { mg_score(QUEEN_MOBILITY[idx - 86]) },
-            114..142 => { eg_score(QUEEN_MOBILITY[idx - 114]) },
-
-            142..150 => { mg_score(QUEEN_KING_DANGER[idx - 142]) },
-            150..158 => { mg_score(ROOK_KING_DANGER[idx - 150]) },
-            158..166 => { mg_score(BISHOP_KING_DANGER[idx - 158]) },
-            166..174 => { mg_score(KNIGHT_KING_DANGER[idx - 166]) },
-
-            174 => { mg_score(DOUBLE_BISHOP_BONUS) },
-            175 => { eg_score(DOUBLE_BISHOP_BONUS) },
-
-            176..182 => { mg_score(PASSED_PAWN_VALUE[idx - 175]) },
-            182..188 => { eg_score(PASSED_PAWN_VALUE[idx - 181]) },
-            188 => { mg_score(CENTER_PAWN_VALUE) },
-            189 => { eg_score(CENTER_PAWN_VALUE) },
-            190 => { mg_score(ISOLATED_PAWN_VALUE) },
-            191 => { eg_score(ISOLATED_PAWN_VALUE) },
-            192 => { mg_score(DOUBLED_PAWN_VALUE) },
-            193 => { eg_score(DOUBLED_PAWN_VALUE) },
-            194 => { mg_score(BACKWARDS_PAWN_VALUE) },
-            195 => { eg_score(BACKWARDS_PAWN_VALUE) },
-            196..202 => { mg_score(ADVANCED_PAWN_VALUE[idx - 195]) },
-            202..208 => { eg_score(ADVANCED_PAWN_VALUE[idx - 201]) },
-            208 => { mg_score(SUPPORTED_PAWN_BONUS) },
-            209 => { eg_score(SUPPORTED_PAWN_BONUS) },
-            210 => { mg_score(SPACE_VALUE) },
-            211 => { eg_score(SPACE_VALUE) },
-
-            212 => { mg_score(ROOK_ON_SEVENTH) },
-            213 => { eg_score(ROOK_ON_SEVENTH) },
-            214 => { mg_score(ROOK_ON_OPEN) },
-            215 => { eg_score(ROOK_ON_OPEN) },
-
-            // pawn table is special, we only care about the middle 6
-            216..240 => {
-                let pr = (idx + 4 - 216) / 4;
-                let pc = (idx + 4 - 216) % 4;
-                mg_score(PAWN_PSQT[pr][pc])
-            },
-            240..264 => {
-                let pr = (idx + 4 - 240) / 4;
-                let pc = (idx + 4 - 240) % 4;
-                eg_score(PAWN_PSQT[pr][pc])
-            },
-            264..296 => {
-                let pr = (idx - 264) / 4;
-                let pc = (idx - 264) % 4;
-                mg_score(KNIGHT_PSQT[pr][pc])
-            },
-            296..328 => {
-                let pr = (idx - 296) / 4;
-                let pc = (idx - 296) % 4;
-                eg_score(KNIGHT_PSQT[pr][pc])
-            },
-            328..360 => {
-                let pr = (idx - 328) / 4;
-                let pc = (idx - 328) % 4;
-                mg_score(BISHOP_PSQT[pr][pc])
-            },
-            360..392 => {
-                let pr = (idx - 360) / 4;
-                let pc = (idx - 360) % 4;
-                eg_score(BISHOP_PSQT[pr][pc])
-            },
-            392..424 => {
-                let pr = (idx - 392) / 4;
-                let pc = (idx - 392) % 4;
-                mg_score(ROOK_PSQT[pr][pc])
-            },
-            424..456 => {
-                let pr = (idx - 424) / 4;
-                let pc = (idx - 424) % 4;
-                eg_score(ROOK_PSQT[pr][pc])
-            },
-            456..488 => {
-                let pr = (idx - 456) / 4;
-                let pc = (idx - 456) % 4;
-                mg_score(QUEEN_PSQT[pr][pc])
-            },
-            488..520 => {
-                let pr = (idx - 488) / 4;
-                let pc = (idx - 488) % 4;
-                eg_score(QUEEN_PSQT[pr][pc])
-            },
-            520..552 => {
-                let pr = (idx - 520) / 4;
-                let pc = (idx - 520) % 4;
-                mg_score(KING_PSQT[pr][pc])
-            },
-            552..584 => {
-                let pr = (idx - 552) / 4;
-                let pc = (idx - 552) % 4;
-                eg_score(KING_PSQT[pr][pc])
-            },
-
-            584 => { mg_score(BISHOP_COLOR) },
-            585 => { eg_score(BISHOP_COLOR) },
-
-            586..592 => { mg_score(CANDIDATE_PASSED_PAWN_VALUE[idx - 585]) },
-            592..598 => { eg_score(CANDIDATE_PASSED_PAWN_VALUE[idx - 591]) },
-
-            598 => { mg_score(NO_QUEEN_ATTACK_VALUE) },
-            599 => { eg_score(NO_QUEEN_ATTACK_VALUE) },
-
-            600 => { mg_score(KNIGHT_OUTPOST_VALUE) },
-            601 => { eg_score(KNIGHT_OUTPOST_VALUE) },
-            602 => { mg_score(BISHOP_OUTPOST_VALUE) },
-            603 => { eg_score(BISHOP_OUTPOST_VALUE) },
-            604 => { mg_score(BISHOP_LONG_DIAGONAL_VALUE) },
-            605 => { eg_score(BISHOP_LONG_DIAGONAL_VALUE) },
-
-            606..614 => { eg_score(QUEEN_KING_DANGER[idx - 606]) },
-            614..622 => { eg_score(ROOK_KING_DANGER[idx - 614]) },
-            622..630 => { eg_score(BISHOP_KING_DANGER[idx - 622]) },
-            630..638 => { eg_score(KNIGHT_KING_DANGER[idx - 630]) },
-
-            638..646 => { mg_score(PAWN_PROXIMITY_VALUE[idx - 638]) },
-            646..654 => { eg_score(PAWN_PROXIMITY_VALUE[idx - 646]) },
-            654..662 => { mg_score(PAWN_SHELTER_VALUE[0][idx - 654]) },
-            662..670 => { eg_score(PAWN_SHELTER_VALUE[0][idx - 662]) },
-            670..678 => { mg_score(PAWN_SHELTER_VALUE[1][idx - 670]) },
-            678..686 => { eg_score(PAWN_SHELTER_VALUE[1][idx - 678]) },
-
-            686..694 => { mg_score(PAWN_STORM_VALUE[0][idx - 686]) },
-            694..702 => { eg_score(PAWN_STORM_VALUE[0][idx - 694]) },
-            702..710 => { mg_score(PAWN_STORM_VALUE[1][idx - 702]) },
-            710..718 => { eg_score(PAWN_STORM_VALUE[1][idx - 710]) },
-
-            718 => { mg_score(WEAK_SQUARE_VALUE) },
-            719 => { eg_score(WEAK_SQUARE_VALUE) },
-
-            720 => { mg_score(QUEEN_CHECK_VALUE) },
-            721 => { eg_score(QUEEN_CHECK_VALUE) },
-            722 => { mg_score(ROOK_CHECK_VALUE) },
-            723 => { eg_score(ROOK_CHECK_VALUE) },
-            724 => { mg_score(BISHOP_CHECK_VALUE) },
-            725 => { eg_score(BISHOP_CHECK_VALUE) },
-            726 => { mg_score(KNIGHT_CHECK_VALUE) },
-            727 => { eg_score(KNIGHT_CHECK_VALUE) },
-
-            _ => {0}
-        }
-    }
+pub fn print_params_vector(v: &[i32]) {
+    print!("{}", EvalParams::from_vector(v).to_rust());
 }
 
-fn set_param(idx: usize, val: i32) {
-    unsafe {
-        // ...
-        match idx {
-            0 => { QUEEN_VALUE = set_mg_score(QUEEN_VALUE, val); },
-            1 => { QUEEN_VALUE = set_eg_score(QUEEN_VALUE, val); },
-            2 => { ROOK_VALUE = set_mg_score(ROOK_VALUE, val); },
-            3 => { ROOK_VALUE = set_eg_score(ROOK_VALUE, val); },
-            4 => { BISHOP_VALUE = set_mg_score(BISHOP_VALUE, val); },
-            5 => { BISHOP_VALUE = set_eg_score(BISHOP_VALUE, val); },
-            6 => { KNIGHT_VALUE = set_mg_score(KNIGHT_VALUE, val); },
-            7 => { KNIGHT_VALUE = set_eg_score(KNIGHT_VALUE, val); },
-            8 => { PAWN_VALUE = set_mg_score(PAWN_VALUE, val); },
-            9 => { PAWN_VALUE = set_eg_score(PAWN_VALUE, val); },
-
-            10..19 => { KNIGHT_MOBILITY[idx - 10] = set_mg_score(KNIGHT_MOBILITY[idx - 10], val); },
-            19..28 => { KNIGHT_MOBILITY[idx - 19] = set_eg_score(KNIGHT_MOBILITY[idx - 19], val); },
-            28..42 => { BISHOP_MOBILITY[idx - 28] = set_mg_score(BISHOP_MOBILITY[idx - 28], val); },
-            42..56 => { BISHOP_MOBILITY[idx - 42] = set_eg_score(BISHOP_MOBILITY[idx - 42], val); },
-            56..71 => { ROOK_MOBILITY[idx - 56] = set_mg_score(ROOK_MOBILITY[idx - 56], val); },
-            71..86 => { ROOK_MOBILITY[idx - 71] = set_eg_score(ROOK_MOBILITY[idx - 71], val); },
-            86..114 => { QUEEN_MOBILITY[idx - 86] = set_mg_score(QUEEN_MOBILITY[idx - 86], val); },
-            114..142 => { QUEEN_MOBILITY[idx - 114] = set_eg_score(QUEEN_MOBILITY[idx - 114], val); },
-
-            142..150 => { QUEEN_KING_DANGER[idx - 142] = set_mg_score(QUEEN_KING_DANGER[idx-142], val); },
-            150..158 => { ROOK_KING_DANGER[idx - 150] = set_mg_score(ROOK_KING_DANGER[idx-150], val); },
-            158..166 => { BISHOP_KING_DANGER[idx - 158] = set_mg_score(BISHOP_KING_DANGER[idx-158], val); },
-            166..174 => { KNIGHT_KING_DANGER[idx - 166] = set_mg_score(KNIGHT_KING_DANGER[idx-166], val); },
-
-            174 => { DOUBLE_BISHOP_BONUS = set_mg_score(DOUBLE_BISHOP_BONUS, val); },
-            175 => { DOUBLE_BISHOP_BONUS = set_eg_score(DOUBLE_BISHOP_BONUS, val); },
-
-            176..182 => { PASSED_PAWN_VALUE[idx - 175] = set_mg_score(PASSED_PAWN_VALUE[idx - 175], val); }, // skip 0th entry
-            182..188 => { PASSED_PAWN_VALUE[idx - 181] = set_eg_score(PASSED_PAWN_VALUE[idx - 181], val); },
-            188 => { CENTER_PAWN_VALUE = set_mg_score(CENTER_PAWN_VALUE, val); },
-            189 => { CENTER_PAWN_VALUE = set_eg_score(CENTER_PAWN_VALUE, val); },
-            190 => { ISOLATED_PAWN_VALUE = set_mg_score(ISOLATED_PAWN_VALUE, val); },
-            191 => { ISOLATED_PAWN_VALUE = set_eg_score(ISOLATED_PAWN_VALUE, val); },
-            192 => { DOUBLED_PAWN_VALUE = set_mg_score(DOUBLED_PAWN_VALUE, val); },
-            193 => { DOUBLED_PAWN_VALUE = set_eg_score(DOUBLED_PAWN_VALUE, val); },
-            194 => { BACKWARDS_PAWN_VALUE = set_mg_score(BACKWARDS_PAWN_VALUE, val); },
-            195 => { BACKWARDS_PAWN_VALUE = set_eg_score(BACKWARDS_PAWN_VALUE, val); },
-            196..202 => { ADVANCED_PAWN_VALUE[idx - 195] = set_mg_score(ADVANCED_PAWN_VALUE[idx - 195], val); }, // skip 0th entry
-            202..208 => { ADVANCED_PAWN_VALUE[idx - 201] = set_eg_score(ADVANCED_PAWN_VALUE[idx - 201], val); },
-            208 => { SUPPORTED_PAWN_BONUS = set_mg_score(SUPPORTED_PAWN_BONUS, val); },
-            209 => { SUPPORTED_PAWN_BONUS = set_eg_score(SUPPORTED_PAWN_BONUS, val); },
-            210 => { SPACE_VALUE = set_mg_score(SPACE_VALUE, val); },
-            211 => { SPACE_VALUE = set_eg_score(SPACE_VALUE, val); },
-
-            212 => { ROOK_ON_SEVENTH = set_mg_score(ROOK_ON_SEVENTH, val); },
-            213 => { ROOK_ON_SEVENTH = set_eg_score(ROOK_ON_SEVENTH, val); },
-            214 => { ROOK_ON_OPEN = set_mg_score(ROOK_ON_OPEN, val); },
-            215 => { ROOK_ON_OPEN = set_eg_score(ROOK_ON_OPEN, val); },
-
-            // pawn table is special, we only care about the middle 6
-            216..240 => {
-                let pr = (idx + 4 - 216) / 4;
-                let pc = (idx + 4 - 216) % 4;
-                PAWN_PSQT[pr][pc] = set_mg_score(PAWN_PSQT[pr][pc], val);
-            },
-            240..264 => {
-                let pr = (idx + 4 - 240) / 4;
-                let pc = (idx + 4 - 240) % 4;
-                PAWN_PSQT[pr][pc] = set_eg_score(PAWN_PSQT[pr][pc], val);
-            },
-            264..296 => {
-                let pr = (idx - 264) / 4;
-                let pc = (idx - 264) % 4;
-                KNIGHT_PSQT[pr][pc] = set_mg_score(KNIGHT_PSQT[pr][pc], val);
-            },
-            296..328 => {
-                let pr = (idx - 296) / 4;
-                let pc = (idx - 296) % 4;
-                KNIGHT_PSQT[pr][pc] = set_eg_score(KNIGHT_PSQT[pr][pc], val);
-            },
-            328..360 => {
-                let pr = (idx - 328) / 4;
-                let pc = (idx - 328) % 4;
-                BISHOP_PSQT[pr][pc] = set_mg_score(BISHOP_PSQT[pr][pc], val);
-            },
-            360..392 => {
-                let pr = (idx - 360) / 4;
-                let pc = (idx - 360) % 4;
-                BISHOP_PSQT[pr][pc] = set_eg_score(BISHOP_PSQT[pr][pc], val);
-            },
-            392..424 => {
-                let pr = (idx - 392) / 4;
-                let pc = (idx - 392) % 4;
-                ROOK_PSQT[pr][pc] = set_mg_score(ROOK_PSQT[pr][pc], val);
-            },
-            424..456 => {
-                let pr = (idx - 424) / 4;
-                let pc = (idx - 424) % 4;
-                ROOK_PSQT[pr][pc] = set_eg_score(ROOK_PSQT[pr][pc], val);
-            },
-            456..488 => {
-                let pr = (idx - 456) / 4;
-                let pc = (idx - 456) % 4;
-                QUEEN_PSQT[pr][pc] = set_mg_score(QUEEN_PSQT[pr][pc], val);
-            },
-            488..520 => {
-                let pr = (idx - 488) / 4;
-                let pc = (idx - 488) % 4;
-                QUEEN_PSQT[pr][pc] = set_eg_score(QUEEN_PSQT[pr][pc], val);
-            },
-            520..552 => {
-                let pr = (idx - 520) / 4;
-                let pc = (idx - 520) % 4;
-                KING_PSQT[pr][pc] = set_mg_score(KING_PSQT[pr][pc], val);
-            },
-            552..584 => {
-                let pr = (idx - 552) / 4;
-                let pc = (idx - 552) % 4;
-                KING_PSQT[pr][pc] = set_eg_score(KING_PSQT[pr][pc], val);
-            },
-            584 => { BISHOP_COLOR = set_mg_score(BISHOP_COLOR, val); },
-            585 => { BISHOP_COLOR = set_eg_score(BISHOP_COLOR, val); },
-
-            586..592 => { CANDIDATE_PASSED_PAWN_VALUE[idx - 585] = set_mg_score(CANDIDATE_PASSED_PAWN_VALUE[idx - 585], val); },
-            592..598 => { CANDIDATE_PASSED_PAWN_VALUE[idx - 591] = set_eg_score(CANDIDATE_PASSED_PAWN_VALUE[idx - 591], val); },
-
-            598 => { NO_QUEEN_ATTACK_VALUE = set_mg_score(NO_QUEEN_ATTACK_VALUE, val); },
-            599 => { NO_QUEEN_ATTACK_VALUE = set_eg_score(NO_QUEEN_ATTACK_VALUE, val); },
-
-            600 => { KNIGHT_OUTPOST_VALUE = set_mg_score(KNIGHT_OUTPOST_VALUE, val); },
-            601 => { KNIGHT_OUTPOST_VALUE = set_eg_score(KNIGHT_OUTPOST_VALUE, val); },
-            602 => { BISHOP_OUTPOST_VALUE = set_mg_score(BISHOP_OUTPOST_VALUE, val); },
-            603 => { BISHOP_OUTPOST_VALUE = set_eg_score(BISHOP_OUTPOST_VALUE, val); },
-            604 => { BISHOP_LONG_DIAGONAL_VALUE = set_mg_score(BISHOP_LONG_DIAGONAL_VALUE, val); },
-            605 => { BISHOP_LONG_DIAGONAL_VALUE = set_eg_score(BISHOP_LONG_DIAGONAL_VALUE, val); },
-
-            606..614 => { QUEEN_KING_DANGER[idx - 606] = set_eg_score(QUEEN_KING_DANGER[idx-606], val); },
-            614..622 => { ROOK_KING_DANGER[idx - 614] = set_eg_score(ROOK_KING_DANGER[idx-614], val); },
-            622..630 => { BISHOP_KING_DANGER[idx - 622] = set_eg_score(BISHOP_KING_DANGER[idx-622], val); },
-            630..638 => { KNIGHT_KING_DANGER[idx - 630] = set_eg_score(KNIGHT_KING_DANGER[idx-630], val); },
-
-            638..646 => { PAWN_PROXIMITY_VALUE[idx - 638] = set_mg_score(PAWN_PROXIMITY_VALUE[idx - 638], val); },
-            646..654 => { PAWN_PROXIMITY_VALUE[idx - 646] = set_eg_score(PAWN_PROXIMITY_VALUE[idx - 646], val); },
-
-            654..662 => { PAWN_SHELTER_VALUE[0][idx - 654] = set_mg_score(PAWN_SHELTER_VALUE[0][idx - 654], val); },
-            662..670 => { PAWN_SHELTER_VALUE[0][idx - 662] = set_eg_score(PAWN_SHELTER_VALUE[0][idx - 662], val); },
-            670..678 => { PAWN_SHELTER_VALUE[1][idx - 670] = set_mg_score(PAWN_SHELTER_VALUE[1][idx - 670], val); },
-            678..686 => { PAWN_SHELTER_VALUE[1][idx - 678] = set_eg_score(PAWN_SHELTER_VALUE[1][idx - 678], val); },
-
-            686..694 => { PAWN_STORM_VALUE[0][idx - 686] = set_mg_score(PAWN_STORM_VALUE[0][idx - 686], val); },
-            694..702 => { PAWN_STORM_VALUE[0][idx - 694] = set_eg_score(PAWN_STORM_VALUE[0][idx - 694], val); },
-            702..710 => { PAWN_STORM_VALUE[1][idx - 702] = set_mg_score(PAWN_STORM_VALUE[1][idx - 702], val); },
-            710..718 => { PAWN_STORM_VALUE[1][idx - 710] = set_eg_score(PAWN_STORM_VALUE[1][idx - 710], val); },
-
-            718 => { WEAK_SQUARE_VALUE = set_mg_score(WEAK_SQUARE_VALUE, val); },
-            719 => { WEAK_SQUARE_VALUE = set_eg_score(WEAK_SQUARE_VALUE, val); },
-            720 => { QUEEN_CHECK_VALUE = set_mg_score(QUEEN_CHECK_VALUE, val); },
-            721 => { QUEEN_CHECK_VALUE = set_eg_score(QUEEN_CHECK_VALUE, val); },
-            722 => { ROOK_CHECK_VALUE = set_mg_score(ROOK_CHECK_VALUE, val); },
-            723 => { ROOK_CHECK_VALUE = set_eg_score(ROOK_CHECK_VALUE, val); },
-            724 => { BISHOP_CHECK_VALUE = set_mg_score(BISHOP_CHECK_VALUE, val); },
-            725 => { BISHOP_CHECK_VALUE = set_eg_score(BISHOP_CHECK_VALUE, val); },
-            726 => { KNIGHT_CHECK_VALUE = set_mg_score(KNIGHT_CHECK_VALUE, val); },
-            727 => { KNIGHT_CHECK_VALUE = set_eg_score(KNIGHT_CHECK_VALUE, val); },
-
-            _ => {}
-        }
-    }
+fn static_eval(node: &Bitboard) -> i32 {
+    return evaluate_with_params(node, eval_params());
 }
 
 fn get_value(node: &mut Bitboard) -> i32 {
@@ -559,7 +57,7 @@ fn tuning_qsearch(node: &mut Bitboard, alpha: i32, beta: i32) -> (i32, Bitboard)
     let mut best_val = stand_pat;
     let mut best_bb = node.thread_copy();
 
-    let mut movepicker = MovePicker::q_new();
+    let mut movepicker = MovePicker::q_new(is_check);
     loop {
         let (mv, score) = movepicker.next(node);
         if mv.is_null() {
@@ -620,11 +118,11 @@ fn tuning_qsearch(node: &mut Bitboard, alpha: i32, beta: i32) -> (i32, Bitboard)
     return (best_val, best_bb);
 }
 
-pub fn err(v: &mut Vec<(Bitboard, f64)>, k: f64) -> f64 {
+pub fn err(v: &mut Vec<(Bitboard, f64)>, params: &EvalParams, k: f64) -> f64 {
     let mut err = 0.0;
     let n = v.len();
     for e in v {
-        let score = static_eval(&mut e.0);
+        let score = evaluate_with_params(&e.0, params);
         let sigma = sigmoid(k, score);
         err += (e.1 - sigma).powi(2);
     }
@@ -644,7 +142,7 @@ pub fn find_optimal_k(v: &mut Vec<(Bitboard, f64)>) -> f64 {
     let mut best_k = k;
     let mut min_err = 1000000.;
     loop {
-        let err = err(v, k);
+        let err = err(v, eval_params(), k);
         println!("{} {}", k, err);
         if err <= min_err {
             min_err = err;
@@ -667,81 +165,33 @@ pub fn score_positions(v: &mut Vec<(Bitboard, f64)>) -> i32 {
     return s;
 }
 
-pub fn neighbor(param_vec: &Vec<i32>, reach: f64) -> Vec<i32> {
-    let mut new_params = param_vec.clone();
-
-    // (minimum, maximum, scale @ zero reach, scale @ full reach)
-    let mut dimens: Vec<(f64, f64, f64, f64)> = Vec::new();
-    for i in 0..728 {
-        let d = match i {
-            0..2   => {(8000.0, 18000.0,  300.0, 2000.0)},
-            2..4   => {(4000.0, 12000.0,  200.0, 1000.0)},
-            4..6   => {(2500.0,  8000.0,  200.0, 1000.0)},
-            6..8   => {(2500.0,  8000.0,  200.0, 1000.0)},
-            8..10  => {( 800.0,  3000.0,  200.0,  200.0)},
-
-            10..28  => {(-1000.0, 1500.0,  100.0,  500.0)},
-            28..56  => {(-1000.0, 1500.0,  100.0,  500.0)},
-            56..86  => {(-1000.0, 1500.0,  100.0,  500.0)},
-            86..142 => {(-1000.0, 1500.0,  100.0,  500.0)},
-
-            142..150 => {(   1.0, 1400.0,  100.0,  500.0)},
-            150..158 => {(   1.0, 1400.0,  100.0,  500.0)},
-            158..166 => {(   1.0, 1400.0,  100.0,  500.0)},
-            166..174 => {(   1.0, 1400.0,  100.0,  500.0)},
-
-            174..176 => {(   0.0, 1500.0,  100.0,  300.0)},
-
-            176..188 => {(   0.0, 2000.0,  100.0,  200.0)},
-
-            188..190 => {(    0.0, 1000.0,  100.0,  150.0)},
-            190..196 => {(-1000.0,    0.0,  100.0,  150.0)},
-
-            196..208 => {(    0.0,  500.0,  100.0,   100.0)},
-            208..210 => {(    0.0,  300.0,  100.0,    70.0)},
-            210..212 => {(    0.0,  300.0,  100.0,   100.0)},
-
-            212..216 => {(    0.0,  500.0,  100.0,   100.0)},
-
-            216..584 => {(-2000.0, 2000.0,  75.0,   500.0)},
-
-            584..586 => {( -500.0,    0.0,  30.0,   100.0)},
-
-            586..598 => {(    0.0, 2000.0,  70.0,   200.0)},
-            598..600 => {(-1000.0,    0.0,  70.0,   400.0)},
-            600..606 => {(    0.0, 1000.0,  70.0,   300.0)},
-            606..638 => {(   1.0, 1400.0,  100.0,  500.0)},
-
-            638..718 => {(-2000.0, 2000.0,  75.0,   500.0)},
-            718..720 => {(    0.0,  500.0,  75.0,   200.0)},
-            720..728 => {(    0.0, 1000.0, 100.0,   400.0)},
-
-            _ => {(0.0, 0.0, 0.0, 0.0)}
-        };
-        dimens.push(d);
-    }
+pub fn neighbor(param_vec: &[i32], reach: f64) -> Vec<i32> {
+    let mut new_params = param_vec.to_vec();
+    let n = new_params.len();
 
-    let mut delta = [0f64; 728];
+    let mut delta = vec![0f64; n];
     let axes = (u32::rand() % 5) + 1;
     for _ in 0..axes {
-        let axis = u32::rand() % 728;
-        delta[axis as usize] = symunif();
+        let axis = u32::rand() as usize % n;
+        delta[axis] = symunif();
     }
 
-    for idx in 0..728 {
-        let (min, max, zero_scale, full_scale) = dimens[idx];
+    let bounds = eval_params().vector_bounds();
+    for idx in 0..n {
+        let (min, max) = bounds[idx];
+        // step sizes scale with how wide the parameter's range is
+        let range = (max - min) as f64;
+        let zero_scale = (range / 10.0).min(100.0);
+        let full_scale = (range / 8.0).clamp(zero_scale, 2000.0);
         let scale = zero_scale + (full_scale - zero_scale) * reach;
         let next = new_params[idx] + (delta[idx] * scale) as i32;
-        new_params[idx] = cmp::min(max as i32, cmp::max(min as i32, next));
+        new_params[idx] = cmp::min(max, cmp::max(min, next));
     }
     return new_params;
 }
 
-fn get_error(v: &mut Vec<(Bitboard, f64)>, params: &Vec<i32>) -> f64 {
-    let old_params = get_params_vector();
-    load_params_vector(params);
-    let mut e = err(v, K);
-    load_params_vector(&old_params);
+fn get_error(v: &mut Vec<(Bitboard, f64)>, params: &[i32]) -> f64 {
+    let mut e = err(v, &EvalParams::from_vector(params), K);
     e = (e * 10000000.0).round() / 10000000.0;
     return e;
 }
@@ -823,7 +273,7 @@ pub fn get_position_vector(fname: &str) -> Vec<(Bitboard, f64)> {
                     None => {panic!("bad fen {}", idx)}
                 }
             }
-            let mut board = Bitboard::from_position(format!("{}", position_str));
+            let board = Bitboard::from_position(format!("{}", position_str));
 
             let winner: f64 = match params.next() {
                 Some(p) => match p.trim().parse() {
@@ -842,3 +292,370 @@ pub fn get_position_vector(fname: &str) -> Vec<(Bitboard, f64)> {
     println!("length {}", v.len());
     return v;
 }
+
+// gradient tuning
+//
+// every eval term is a weight times a count, so each position can be reduced
+// once to the coefficient of every weight it touches. after that evaluating a
+// position is a short dot product and the gradient of the error falls out of
+// the same coefficients, so there's no need to run the real eval again.
+
+// evaluating with the weights set to powers of COEF_BASE packs COEF_DIGITS
+// coefficients into a single score. the coefficients are small counts (pieces,
+// squares, checks) so they fit comfortably in a balanced base 256 digit.
+const COEF_BASE: i64 = 256;
+const COEF_DIGITS: usize = 7;
+
+pub struct TuningEntry {
+    // (score index, coefficient) for every weight this position uses
+    coefs: Vec<(u16, i16)>,
+    // taper and halfmove scaling folded together
+    mg_scale: f32,
+    eg_scale: f32,
+    result: f32
+}
+
+pub struct GradientOptions {
+    pub epochs: usize,
+    pub batch_size: usize,
+    pub learning_rate: f64,
+    pub threads: usize,
+    pub k: f64,             // 0 to fit K to the data
+    pub out: String
+}
+
+fn coefficient_probes() -> Vec<EvalParams> {
+    let n = eval_params().vector_len() / 2;
+    let mut probes = Vec::new();
+    let mut start = 0;
+    while start < n {
+        let mut probe = EvalParams::from_vector(&vec![0; n * 2]);
+        let mut idx = 0;
+        for (_, scores) in probe.params_mut() {
+            for s in scores.iter_mut() {
+                if idx >= start && idx < start + COEF_DIGITS {
+                    *s = COEF_BASE.pow((idx - start) as u32);
+                }
+                idx += 1;
+            }
+        }
+        probes.push(probe);
+        start += COEF_DIGITS;
+    }
+    return probes;
+}
+
+fn current_scores() -> Vec<Score> {
+    let mut scores = Vec::new();
+    for param in eval_params().params() {
+        scores.extend_from_slice(param.scores);
+    }
+    return scores;
+}
+
+fn tuning_entry(pos: &Bitboard, result: f64, probes: &[EvalParams], scores: &[Score]) -> Option<TuningEntry> {
+    let mut coefs = Vec::new();
+    for (i, probe) in probes.iter().enumerate() {
+        let mut packed = evaluate_score(pos, probe);
+        for d in 0..COEF_DIGITS {
+            let c = (packed + COEF_BASE / 2).rem_euclid(COEF_BASE) - COEF_BASE / 2;
+            packed = (packed - c) / COEF_BASE;
+            if c != 0 {
+                coefs.push(((i * COEF_DIGITS + d) as u16, c as i16));
+            }
+        }
+    }
+
+    // the coefficients have to reproduce the real eval exactly. if they don't,
+    // a term isn't linear in its weights or a coefficient overflowed its digit
+    let mut check: Score = 0;
+    for (j, c) in &coefs {
+        check += scores[*j as usize] * *c as Score;
+    }
+    if check != evaluate_score(pos, eval_params()) {
+        return None;
+    }
+
+    let phase = pos.get_phase() as f32;
+    let halfmove = (100 - pos.halfmove as i32) as f32 / 100.0;
+    return Some(TuningEntry {
+        coefs: coefs,
+        mg_scale: (256.0 - phase) / 256.0 * halfmove,
+        eg_scale: phase / 256.0 * halfmove,
+        result: result as f32
+    });
+}
+
+// accepts the `fen;...;outcome:x` lines written by convert-pgn as well as
+// plain `fen result` lines, the result being from white's point of view
+fn parse_tuning_line(line: &str) -> Option<(String, f64)> {
+    let (fen, result) = if line.contains(';') {
+        let mut fields = line.split(';');
+        let fen = fields.next()?.trim().to_string();
+        let outcome = fields.find_map(|f| f.trim().strip_prefix("outcome:"))?;
+        (fen, outcome.trim().parse().ok()?)
+    } else {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() != 7 { return None; }
+        (fields[..6].join(" "), fields[6].parse().ok()?)
+    };
+    if !(0.0..=1.0).contains(&result) || Bitboard::validate_fen(&fen).is_err() {
+        return None;
+    }
+    return Some((fen, result));
+}
+
+// splits items into one chunk per thread and runs f on each
+fn parallel_chunks<T: Sync, R: Send>(items: &[T], threads: usize, f: impl Fn(&[T]) -> R + Sync) -> Vec<R> {
+    let chunk_size = cmp::max(1, items.len().div_ceil(cmp::max(threads, 1)));
+    return std::thread::scope(|s| {
+        let handles: Vec<_> = items.chunks(chunk_size).map(|chunk| {
+            let f = &f;
+            s.spawn(move || f(chunk))
+        }).collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+}
+
+// linearizes a chunk of positions across threads.  parse turns an item into
+// a board and result, returning None for anything that isn't one
+fn linearize_chunk<T: Sync>(items: &[T], threads: usize, parse: impl Fn(&T) -> Option<(Bitboard, f64)> + Sync, probes: &[EvalParams], scores: &[Score]) -> (Vec<TuningEntry>, usize, usize) {
+    let results = parallel_chunks(items, threads, |items| {
+        let mut out = Vec::new();
+        let mut bad = (0, 0);
+        for item in items {
+            let (pos, result) = match parse(item) {
+                Some(r) => r,
+                None => { bad.0 += 1; continue; }
+            };
+            match tuning_entry(&pos, result, probes, scores) {
+                Some(e) => { out.push(e); },
+                None => { bad.1 += 1; }
+            }
+        }
+        (out, bad)
+    });
+
+    let mut entries = Vec::new();
+    let mut skipped = 0;
+    let mut nonlinear = 0;
+    for (mut out, bad) in results {
+        entries.append(&mut out);
+        skipped += bad.0;
+        nonlinear += bad.1;
+    }
+    return (entries, skipped, nonlinear);
+}
+
+pub fn load_tuning_entries(fname: &str, threads: usize) -> Result<Vec<TuningEntry>, String> {
+    let probes = coefficient_probes();
+    let scores = current_scores();
+
+    let mut entries = Vec::new();
+    let mut skipped = 0;
+    let mut nonlinear = 0;
+    if is_packed_file(fname) {
+        let mut reader = PackedReader::open(fname)?;
+        loop {
+            let chunk = reader.read_chunk(1 << 16)?;
+            if chunk.is_empty() { break; }
+            let (mut out, s, n) = linearize_chunk(&chunk, threads, |p| Some((p.to_board(), p.outcome())), &probes, &scores);
+            entries.append(&mut out);
+            skipped += s;
+            nonlinear += n;
+        }
+    } else {
+        let f = match File::open(fname) {
+            Ok(f) => f,
+            Err(e) => { return Err(format!("unable to open file {}: {}", fname, e)); }
+        };
+        let mut lines = BufReader::new(f).lines();
+        loop {
+            let mut chunk = Vec::new();
+            for line in lines.by_ref().take(1 << 16) {
+                match line {
+                    Ok(l) => { chunk.push(l); },
+                    Err(e) => { return Err(format!("unable to read {}: {}", fname, e)); }
+                }
+            }
+            if chunk.is_empty() { break; }
+            let parse = |line: &String| {
+                let (fen, result) = parse_tuning_line(line)?;
+                Some((Bitboard::from_position(fen), result))
+            };
+            let (mut out, s, n) = linearize_chunk(&chunk, threads, parse, &probes, &scores);
+            entries.append(&mut out);
+            skipped += s;
+            nonlinear += n;
+        }
+    }
+
+    if skipped > 0 {
+        eprintln!("ERR: skipped {} lines that weren't positions with a result", skipped);
+    }
+    if nonlinear > 0 {
+        eprintln!("ERR: skipped {} positions whose eval couldn't be linearized", nonlinear);
+    }
+    return Ok(entries);
+}
+
+fn linear_eval(e: &TuningEntry, w: &[f64]) -> f64 {
+    let mut mg = 0.0;
+    let mut eg = 0.0;
+    for (j, c) in &e.coefs {
+        let j = *j as usize;
+        mg += *c as f64 * w[2 * j];
+        eg += *c as f64 * w[2 * j + 1];
+    }
+    return mg * e.mg_scale as f64 + eg * e.eg_scale as f64;
+}
+
+fn sigmoid_f(k: f64, eval: f64) -> f64 {
+    return 1.0 / (1.0 + (-k * eval).exp());
+}
+
+fn linear_error(entries: &[TuningEntry], w: &[f64], k: f64, threads: usize) -> f64 {
+    let sums = parallel_chunks(entries, threads, |chunk| {
+        let mut err = 0.0;
+        for e in chunk {
+            err += (sigmoid_f(k, linear_eval(e, w)) - e.result as f64).powi(2);
+        }
+        err
+    });
+    return sums.iter().sum::<f64>() / entries.len() as f64;
+}
+
+// golden section search, the error is unimodal in K
+fn fit_k(entries: &[TuningEntry], w: &[f64], threads: usize) -> f64 {
+    let ratio = (5f64.sqrt() - 1.0) / 2.0;
+    let (mut lo, mut hi) = (0.0, 0.01);
+    let mut a = hi - ratio * (hi - lo);
+    let mut b = lo + ratio * (hi - lo);
+    let mut err_a = linear_error(entries, w, a, threads);
+    let mut err_b = linear_error(entries, w, b, threads);
+    while hi - lo > 1e-8 {
+        if err_a < err_b {
+            hi = b;
+            b = a;
+            err_b = err_a;
+            a = hi - ratio * (hi - lo);
+            err_a = linear_error(entries, w, a, threads);
+        } else {
+            lo = a;
+            a = b;
+            err_a = err_b;
+            b = lo + ratio * (hi - lo);
+            err_b = linear_error(entries, w, b, threads);
+        }
+    }
+    return (lo + hi) / 2.0;
+}
+
+fn batch_gradient(batch: &[TuningEntry], w: &[f64], k: f64, threads: usize) -> Vec<f64> {
+    let partials = parallel_chunks(batch, threads, |chunk| {
+        let mut grad = vec![0.0; w.len()];
+        for e in chunk {
+            let s = sigmoid_f(k, linear_eval(e, w));
+            let g = (s - e.result as f64) * s * (1.0 - s);
+            let (g_mg, g_eg) = (g * e.mg_scale as f64, g * e.eg_scale as f64);
+            for (j, c) in &e.coefs {
+                let j = *j as usize;
+                grad[2 * j] += g_mg * *c as f64;
+                grad[2 * j + 1] += g_eg * *c as f64;
+            }
+        }
+        grad
+    });
+
+    let mut grad = vec![0.0; w.len()];
+    for partial in partials {
+        for (g, p) in grad.iter_mut().zip(partial) {
+            *g += p;
+        }
+    }
+    let scale = 2.0 * k / batch.len() as f64;
+    for g in grad.iter_mut() {
+        *g *= scale;
+    }
+    return grad;
+}
+
+fn write_params(w: &[f64], fname: &str) -> Result<(), String> {
+    let v: Vec<i32> = w.iter().map(|x| x.round() as i32).collect();
+    return write_eval_params(&EvalParams::from_vector(&v), fname);
+}
+
+// Adam over mini-batches, starting from the current weights
+pub fn gradient_tune(entries: &mut [TuningEntry], options: &GradientOptions) -> Result<Vec<i32>, String> {
+    if entries.is_empty() {
+        return Err("no positions to tune on".to_string());
+    }
+    let threads = cmp::max(options.threads, 1);
+    let mut w: Vec<f64> = get_params_vector().iter().map(|x| *x as f64).collect();
+    let bounds = eval_params().vector_bounds();
+
+    let k = if options.k > 0.0 { options.k } else { fit_k(entries, &w, threads) };
+    println!("{} positions, K = {:.7}, initial error {:.7}", entries.len(), k, linear_error(entries, &w, k, threads));
+
+    let (beta1, beta2, epsilon) = (0.9, 0.999, 1e-8);
+    let mut m = vec![0.0; w.len()];
+    let mut v = vec![0.0; w.len()];
+    let mut step = 0;
+    for epoch in 1..=options.epochs {
+        let start = get_time_millis();
+        // shuffle so every epoch sees different batches
+        for i in (1..entries.len()).rev() {
+            entries.swap(i, (u64::rand() % (i as u64 + 1)) as usize);
+        }
+
+        for batch in entries.chunks(cmp::max(options.batch_size, 1)) {
+            let grad = batch_gradient(batch, &w, k, threads);
+            step += 1;
+            let correction1 = 1.0 - f64::powi(beta1, step);
+            let correction2 = 1.0 - f64::powi(beta2, step);
+            for i in 0..w.len() {
+                m[i] = beta1 * m[i] + (1.0 - beta1) * grad[i];
+                v[i] = beta2 * v[i] + (1.0 - beta2) * grad[i] * grad[i];
+                let m_hat = m[i] / correction1;
+                let v_hat = v[i] / correction2;
+                w[i] -= options.learning_rate * m_hat / (v_hat.sqrt() + epsilon);
+                w[i] = w[i].clamp(bounds[i].0 as f64, bounds[i].1 as f64);
+            }
+        }
+
+        let err = linear_error(entries, &w, k, threads);
+        println!("epoch {:>4} error {:.7} ({}ms)", epoch, err, get_time_millis() - start);
+        // written every epoch so an interrupted run still leaves something usable
+        write_params(&w, &options.out)?;
+    }
+    write_params(&w, &options.out)?;
+    println!("wrote {}", options.out);
+    return Ok(w.iter().map(|x| x.round() as i32).collect());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pgn::START_FEN;
+
+    // the coefficients only reproduce the eval if every term is linear in
+    // its weights, which the king check threats weren't while they were
+    // gated on the attack sum.  these positions all have a king under
+    // attack with safe checks available
+    #[test]
+    fn linearized_eval() {
+        crate::test_init();
+        let probes = coefficient_probes();
+        let scores = current_scores();
+        for fen in [
+            START_FEN,
+            "r1bq1rk1/pppp1ppp/2n2n2/2b1p1N1/2B1P3/8/PPPP1PPP/RNBQK2R w KQ - 6 5",
+            "r4rk1/1pp2ppp/p1n5/3Nq3/4P1b1/3P4/PPPQ1PPP/R3KB1R w KQ - 0 1",
+            "6k1/5ppp/8/8/8/8/1Q3PPP/6K1 w - - 0 1",
+            "2kr3r/ppp2ppp/2n5/2bq4/3P2b1/2N1BN2/PPP2PPP/R2QK2R b KQ - 0 10",
+        ] {
+            let pos = Bitboard::from_position(fen.to_string());
+            assert!(tuning_entry(&pos, 0.5, &probes, &scores).is_some(), "{}", fen);
+        }
+    }
+}
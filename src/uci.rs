@@ -3,12 +3,19 @@ use std::str::SplitWhitespace;
 use std::thread;
 use std::time;
 
+use crate::advisor::*;
 use crate::bitboard::*;
+use crate::epd::*;
+use crate::defaultparams::*;
 use crate::eval::*;
+use crate::handbrain::*;
+use crate::evalparams::*;
 use crate::moveutil::*;
+use crate::nnue::*;
 use crate::pht::*;
 use crate::search::*;
 use crate::searchutil::*;
+use crate::skill::*;
 use crate::syzygy::*;
 use crate::util::*;
 use crate::tt::*;
@@ -19,10 +26,27 @@ pub struct UCIOptions {
     pub move_overhead: i32,
     pub hash: i32,
     pub bh_mode: u8,
+    pub hand_piece: HandPiece,
     pub probe_depth: i32,
-    pub syzygy_path: String
+    pub syzygy_path: String,
+    pub show_wdl: bool,
+    pub normalize_score: bool,
+    pub contempt: i32,          // centipawns
+    pub dynamic_contempt: bool,
+    pub rating_adv: i32,        // our rating minus the opponent's
+    pub skill_level: i32,
+    pub limit_strength: bool,
+    pub elo: i32,
+    pub show_advice: bool,
+    pub resign_score: i32,      // centipawns, 0 to never resign
+    pub resign_moves: i32,
+    pub draw_margin: i32,       // centipawns
+    pub draw_moves: i32,
+    pub draw_min_move: i32
 }
 
+const MAX_DYNAMIC_CONTEMPT: i32 = 50;
+
 impl UCIOptions {
     pub fn default() -> UCIOptions {
         UCIOptions {
@@ -30,13 +54,39 @@ impl UCIOptions {
             move_overhead: 10,
             hash: 64,
             bh_mode: OFF,
+            hand_piece: HandPiece::Any,
             probe_depth: 0,
-            syzygy_path: ("").to_string()
+            syzygy_path: ("").to_string(),
+            show_wdl: false,
+            normalize_score: false,
+            contempt: 0,
+            dynamic_contempt: false,
+            rating_adv: 0,
+            skill_level: MAX_SKILL_LEVEL,
+            limit_strength: false,
+            elo: LEVEL_ELO[MAX_SKILL_LEVEL as usize],
+            show_advice: false,
+            resign_score: 1000,
+            resign_moves: 5,
+            draw_margin: 15,
+            draw_moves: 10,
+            draw_min_move: 40
+        }
+    }
+
+    // contempt in internal units.  dynamic contempt adds a centipawn for every
+    // 10 points of rating advantage, up to half a pawn either way
+    pub fn contempt(&self) -> i32 {
+        let mut contempt = self.contempt;
+        if self.dynamic_contempt {
+            contempt += (self.rating_adv / 10).clamp(-MAX_DYNAMIC_CONTEMPT, MAX_DYNAMIC_CONTEMPT);
         }
+        return contempt * 10;
     }
 }
 
 fn uci_go(board: &Bitboard, options: UCIOptions, params: &mut SplitWhitespace) {
+    let mut options = options;
     if ongoing_search() { println!("ERR: Search still ongoing."); return; }
 
     let mut search_limit = SearchLimits::movetime(10000);
@@ -52,8 +102,6 @@ fn uci_go(board: &Bitboard, options: UCIOptions, params: &mut SplitWhitespace) {
 
     let mut infinite = false;
 
-    let mut bh_piece = -1;
-
     loop {
         let p = match params.next() {
             Some(p) => p,
@@ -102,10 +150,11 @@ fn uci_go(board: &Bitboard, options: UCIOptions, params: &mut SplitWhitespace) {
         } else if p == "infinite" {
             infinite = true;
         } else if p == "piece" {
-            bh_piece = match params.next() {
-                Some(p) => { str_to_idx(p.trim().to_string()) },
-                None => panic!("empty piece!")
-            };
+            // the hand's piece for just this move
+            match params.next().and_then(HandPiece::from_str) {
+                Some(piece) => { options.hand_piece = piece; },
+                None => { eprintln!("ERR: invalid piece"); return; }
+            }
         }
     }
 
@@ -126,7 +175,7 @@ fn uci_go(board: &Bitboard, options: UCIOptions, params: &mut SplitWhitespace) {
 
     let mut thread_board = board.thread_copy();
     thread::spawn(move || {
-        best_move(&mut thread_board, options.num_threads, search_limit, options, bh_piece);
+        best_move(&mut thread_board, options.num_threads, search_limit, options);
     });
 }
 
@@ -176,27 +225,35 @@ fn stop() {
 
 fn set_position(params: &mut SplitWhitespace) -> Bitboard {
     let mut board = Bitboard::default_board();
+    let mut has_moves = false;
     match params.next() {
         Some(p) => {
-            if p == "startpos" {}
-            else if p == "fen" {
-                let mut position_str: String = String::from("");
-                for i in 0..6 {
-                    match params.next() {
-                        Some(param) => {
-                            if i != 0 {
-                                position_str = format!("{} {}", position_str, param);
-                            } else {
-                                position_str = format!("{}", param);
-                            }
-                        },
-                        None => {println!("bad fen"); return board;}
+            if p == "startpos" {
+                has_moves = params.next() == Some("moves");
+            } else if p == "fen" {
+                let mut fields: Vec<&str> = Vec::new();
+                for param in params.by_ref() {
+                    if param == "moves" { has_moves = true; break; }
+                    fields.push(param);
+                }
+                // full FENs are taken as-is, anything else is read as EPD so
+                // four-field positions work too
+                let position_str = if fields.len() == 6 && fields[4].parse::<u32>().is_ok() && fields[5].parse::<u32>().is_ok() {
+                    fields.join(" ")
+                } else {
+                    match EpdRecord::parse(&fields.join(" ")) {
+                        Ok(r) => r.fen(),
+                        Err(e) => { println!("info string bad fen: {}", e); return board; }
                     }
+                };
+                if let Err(e) = Bitboard::validate_fen(&position_str) {
+                    println!("info string bad fen: {}", e);
+                    return board;
                 }
-                board = Bitboard::from_position(format!("{}", position_str));
+                board = Bitboard::from_position(position_str);
             }
 
-            if params.next() == Some("moves") {
+            if has_moves {
                 loop {
                     match params.next() {
                         Some(mv) => { apply_uci_move(&mut board, mv.to_string()); },
@@ -211,7 +268,7 @@ fn set_position(params: &mut SplitWhitespace) -> Bitboard {
 }
 
 
-fn setoption(params: &mut SplitWhitespace, options: &mut UCIOptions) {
+fn setoption(params: &mut SplitWhitespace, options: &mut UCIOptions, board: &mut Bitboard) {
     match params.next() {
         Some(p) => match p {
             "name" => {
@@ -305,6 +362,243 @@ fn setoption(params: &mut SplitWhitespace, options: &mut UCIOptions) {
                     }
                     options.probe_depth = depth;
                 }
+
+                else if option_name.as_str() == "EvalParamsFile" {
+                    if ongoing_search() {
+                        eprintln!("ERR: Cannot change eval parameters during search");
+                        return;
+                    }
+                    // paths may contain spaces
+                    let mut path = value_str.to_string();
+                    for p in params.by_ref() {
+                        path.push(' ');
+                        path.push_str(p);
+                    }
+                    if path.is_empty() || path.starts_with("<empty>") {
+                        set_eval_params(DEFAULT_EVAL_PARAMS);
+                        println!("info string using default eval parameters");
+                        return;
+                    }
+                    match load_eval_params(&path) {
+                        Ok(p) => {
+                            set_eval_params(p);
+                            println!("info string loaded eval parameters from {}", path);
+                        },
+                        Err(e) => { println!("info string failed to load eval parameters: {}", e); }
+                    }
+                }
+
+                else if option_name.as_str() == "UCI_ShowWDL" {
+                    match value_str.trim() {
+                        "true" => { options.show_wdl = true; },
+                        "false" => { options.show_wdl = false; },
+                        _ => { eprintln!("ERR: invalid value provided for UCI_ShowWDL"); }
+                    }
+                }
+
+                else if option_name.as_str() == "NormalizeScore" {
+                    match value_str.trim() {
+                        "true" => { options.normalize_score = true; },
+                        "false" => { options.normalize_score = false; },
+                        _ => { eprintln!("ERR: invalid value provided for NormalizeScore"); }
+                    }
+                }
+
+                else if option_name.as_str() == "Contempt" {
+                    let contempt: i32 = match value_str.trim().parse() {
+                        Ok(num) => num,
+                        Err(_) => {eprintln!("ERR: invalid value provided for Contempt"); return;}
+                    };
+                    if contempt > 100 || contempt < -100 {
+                        eprintln!("ERR: invalid value provided for Contempt");
+                        return;
+                    }
+                    options.contempt = contempt;
+                }
+
+                else if option_name.as_str() == "DynamicContempt" {
+                    match value_str.trim() {
+                        "true" => { options.dynamic_contempt = true; },
+                        "false" => { options.dynamic_contempt = false; },
+                        _ => { eprintln!("ERR: invalid value provided for DynamicContempt"); }
+                    }
+                }
+
+                else if option_name.as_str() == "UCI_RatingAdv" {
+                    let adv: i32 = match value_str.trim().parse() {
+                        Ok(num) => num,
+                        Err(_) => {eprintln!("ERR: invalid value provided for UCI_RatingAdv"); return;}
+                    };
+                    options.rating_adv = adv;
+                }
+
+                // "<title> <rating> <computer|human> <name>", either of the
+                // first two may be "none"
+                else if option_name.as_str() == "UCI_Opponent" {
+                    let rating = match params.next() {
+                        Some(r) => r,
+                        None => { eprintln!("ERR: invalid value provided for UCI_Opponent"); return; }
+                    };
+                    if rating == "none" {
+                        options.rating_adv = 0;
+                        return;
+                    }
+                    match rating.parse::<i32>() {
+                        Ok(r) => {
+                            let own = if options.limit_strength {options.elo} else {FULL_STRENGTH_ELO};
+                            options.rating_adv = own - r;
+                        },
+                        Err(_) => { eprintln!("ERR: invalid value provided for UCI_Opponent"); }
+                    }
+                }
+
+                else if option_name.as_str() == "Skill Level" {
+                    let level: i32 = match value_str.trim().parse() {
+                        Ok(num) => num,
+                        Err(_) => {eprintln!("ERR: invalid value provided for Skill Level"); return;}
+                    };
+                    if level > MAX_SKILL_LEVEL || level < 0 {
+                        eprintln!("ERR: invalid value provided for Skill Level");
+                        return;
+                    }
+                    options.skill_level = level;
+                }
+
+                else if option_name.as_str() == "UCI_LimitStrength" {
+                    match value_str.trim() {
+                        "true" => { options.limit_strength = true; },
+                        "false" => { options.limit_strength = false; },
+                        _ => { eprintln!("ERR: invalid value provided for UCI_LimitStrength"); }
+                    }
+                }
+
+                else if option_name.as_str() == "UCI_Elo" {
+                    let elo: i32 = match value_str.trim().parse() {
+                        Ok(num) => num,
+                        Err(_) => {eprintln!("ERR: invalid value provided for UCI_Elo"); return;}
+                    };
+                    if elo > LEVEL_ELO[MAX_SKILL_LEVEL as usize] || elo < LEVEL_ELO[0] {
+                        eprintln!("ERR: invalid value provided for UCI_Elo");
+                        return;
+                    }
+                    options.elo = elo;
+                }
+
+                else if option_name.as_str() == "ShowAdvice" {
+                    match value_str.trim() {
+                        "true" => { options.show_advice = true; },
+                        "false" => { options.show_advice = false; },
+                        _ => { eprintln!("ERR: invalid value provided for ShowAdvice"); }
+                    }
+                }
+
+                else if option_name.as_str() == "ResignScore" {
+                    let value: i32 = match value_str.trim().parse() {
+                        Ok(num) => num,
+                        Err(_) => {eprintln!("ERR: invalid value provided for ResignScore"); return;}
+                    };
+                    if value > 10000 || value < 0 {
+                        eprintln!("ERR: invalid value provided for ResignScore");
+                        return;
+                    }
+                    options.resign_score = value;
+                }
+
+                else if option_name.as_str() == "ResignMoves" {
+                    let value: i32 = match value_str.trim().parse() {
+                        Ok(num) => num,
+                        Err(_) => {eprintln!("ERR: invalid value provided for ResignMoves"); return;}
+                    };
+                    if value > 100 || value < 1 {
+                        eprintln!("ERR: invalid value provided for ResignMoves");
+                        return;
+                    }
+                    options.resign_moves = value;
+                }
+
+                else if option_name.as_str() == "DrawMargin" {
+                    let value: i32 = match value_str.trim().parse() {
+                        Ok(num) => num,
+                        Err(_) => {eprintln!("ERR: invalid value provided for DrawMargin"); return;}
+                    };
+                    if value > 1000 || value < 0 {
+                        eprintln!("ERR: invalid value provided for DrawMargin");
+                        return;
+                    }
+                    options.draw_margin = value;
+                }
+
+                else if option_name.as_str() == "DrawMoves" {
+                    let value: i32 = match value_str.trim().parse() {
+                        Ok(num) => num,
+                        Err(_) => {eprintln!("ERR: invalid value provided for DrawMoves"); return;}
+                    };
+                    if value > 100 || value < 1 {
+                        eprintln!("ERR: invalid value provided for DrawMoves");
+                        return;
+                    }
+                    options.draw_moves = value;
+                }
+
+                else if option_name.as_str() == "DrawMinMove" {
+                    let value: i32 = match value_str.trim().parse() {
+                        Ok(num) => num,
+                        Err(_) => {eprintln!("ERR: invalid value provided for DrawMinMove"); return;}
+                    };
+                    if value > 1000 || value < 1 {
+                        eprintln!("ERR: invalid value provided for DrawMinMove");
+                        return;
+                    }
+                    options.draw_min_move = value;
+                }
+
+                else if option_name.as_str() == "HandAndBrain" {
+                    match bh_mode_from_str(value_str.trim()) {
+                        Some(mode) => { options.bh_mode = mode; },
+                        None => { eprintln!("ERR: invalid value provided for HandAndBrain"); }
+                    }
+                }
+
+                else if option_name.as_str() == "HandPiece" {
+                    match HandPiece::from_str(value_str) {
+                        Some(piece) => { options.hand_piece = piece; },
+                        None => { eprintln!("ERR: invalid value provided for HandPiece"); }
+                    }
+                }
+
+                else if option_name.as_str() == "EvalMode" {
+                    match EvalMode::from_str(value_str.trim()) {
+                        Some(mode) => { set_eval_mode(mode); },
+                        None => { eprintln!("ERR: invalid value provided for EvalMode"); }
+                    }
+                }
+
+                else if option_name.as_str() == "EvalFile" {
+                    if ongoing_search() {
+                        eprintln!("ERR: Cannot change the network during search");
+                        return;
+                    }
+                    let mut path = value_str.to_string();
+                    for p in params.by_ref() {
+                        path.push(' ');
+                        path.push_str(p);
+                    }
+                    if path.is_empty() || path.starts_with("<empty>") {
+                        use_embedded_net();
+                        println!("info string using the embedded network");
+                    } else {
+                        match load_eval_file(&path) {
+                            Ok(shape) => { println!("info string loaded {} network from {}", shape.describe(), path); },
+                            Err(e) => {
+                                use_embedded_net();
+                                println!("info string failed to load network, using the embedded one: {}", e);
+                            }
+                        }
+                    }
+                    // the current position still has the old net's activations
+                    board.net = get_default_net();
+                    board.set_activations();
+                }
             },
             _ => { return; }
         },
@@ -312,13 +606,9 @@ fn setoption(params: &mut SplitWhitespace, options: &mut UCIOptions) {
     };
 }
 
-const OFF: u8 = 0;
-const BRAIN: u8 = 1;
-const HAND: u8 = 2;
-
 pub fn uci_loop() {
     let mut board = Bitboard::default_board();
-    let mut options = UCIOptions::default();// {num_threads: 1, move_overhead: 10, hash: 64, bh_mode: OFF, probe_depth: 0};
+    let mut options = UCIOptions::default();
 
     loop {
         let mut inp: String = String::new();
@@ -341,43 +631,62 @@ pub fn uci_loop() {
             println!("option name Move Overhead type spin default 10 min 1 max 1000");
             println!("option name SyzygyPath type string default <empty>");
             println!("option name SyzygyProbeDepth type spin default 0 min 0 max 64");
+            println!("option name EvalParamsFile type string default <empty>");
+            println!("option name EvalFile type string default <empty>");
+            println!("option name EvalMode type combo default NNUE var NNUE var HCE var Hybrid");
+            println!("option name UCI_ShowWDL type check default false");
+            println!("option name NormalizeScore type check default false");
+            println!("option name Contempt type spin default 0 min -100 max 100");
+            println!("option name DynamicContempt type check default false");
+            println!("option name UCI_Opponent type string default <empty>");
+            println!("option name UCI_RatingAdv type spin default 0 min -10000 max 10000");
+            // UCI_LimitStrength and UCI_Elo are still accepted, but a gui
+            // takes a listed UCI_Elo as calibrated, which LEVEL_ELO isn't yet
+            println!("option name Skill Level type spin default {} min 0 max {}", MAX_SKILL_LEVEL, MAX_SKILL_LEVEL);
+            println!("option name ShowAdvice type check default false");
+            println!("option name ResignScore type spin default 1000 min 0 max 10000");
+            println!("option name ResignMoves type spin default 5 min 1 max 100");
+            println!("option name DrawMargin type spin default 15 min 0 max 1000");
+            println!("option name DrawMoves type spin default 10 min 1 max 100");
+            println!("option name DrawMinMove type spin default 40 min 1 max 1000");
+            println!("option name HandAndBrain type combo default Off var Off var Brain var Hand");
+            println!("option name HandPiece type string default any");
             println!("uciok");
         } else if cmd == "ucinewgame" {
             // clear the transposition table
             board = Bitboard::default_board();
             clear_tt();
             clear_info();
+            clear_score_history();
             unsafe {
                 setup_tb(options.syzygy_path.as_str());
             }
         } else if cmd == "isready" {
             println!("readyok");
         } else if cmd == "setoption" {
-            setoption(&mut params, &mut options);
+            setoption(&mut params, &mut options, &mut board);
         } else if cmd == "position" {
             board = set_position(&mut params);
         } else if cmd == "go" {
             uci_go(&mut board, options.clone(), &mut params);
         } else if cmd == "stop" {
             stop();
+        } else if cmd == "advice" {
+            println!("info string {}", advise(&board, &options));
         } else if cmd == "eval" {
+            if params.next() == Some("json") {
+                println!("{}", eval_trace(&mut board));
+                continue;
+            }
             println!("{}", static_eval(&mut board, &mut PHT::get_pht(1)) / 10);
         } else if cmd == "bhmode" || cmd == "bh_mode" || cmd == "bh" {
-            let mode = match params.next() {
-                Some(p) => p,
-                None => {println!("You didn't say a mode"); continue;}
-            };
-            if mode == "brain" {
-                eprintln!("Brain mode.");
-                options.bh_mode = BRAIN;
-            } else if mode == "hand" {
-                eprintln!("Hand mode.");
-                options.bh_mode = HAND;
-            } else if mode == "off" {
-                eprintln!("Normal mode.");
-                options.bh_mode = OFF;
-            } else {
-                println!("Err: Invalid mode {}?", mode);
+            // shorthand for setting HandAndBrain
+            match params.next().and_then(bh_mode_from_str) {
+                Some(mode) => {
+                    options.bh_mode = mode;
+                    println!("info string hand and brain mode {}", bh_mode_name(mode));
+                },
+                None => { eprintln!("ERR: expected a mode of off, brain or hand"); }
             }
         } else if cmd == "nnue" {
             let b = board.thread_copy();
@@ -386,7 +695,7 @@ pub fn uci_loop() {
         } else if cmd == "hce" {
             let mut b = board.thread_copy();
             print_eval(&mut b);
-            println!("NNUE Eval (White View): {:+.2}", board.net.nnue_eval() as f32 / 1000.0)
+            println!("NNUE Eval (White View): {:+.2}", board.nnue_eval() as f32 / 1000.0)
         }
         else {
             println!("unrecognized command.");
@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+
+use crate::bitboard::*;
+use crate::packed::*;
+use crate::search::get_time_millis;
+use crate::searchutil::*;
+use crate::util::*;
+
+// the win/draw/loss model.  the chance of winning at a score s is
+// sigmoid((s - a) / b) and the chance of losing sigmoid((-s - a) / b), the
+// same logistic curve the tuner's K describes, but with a draw band in the
+// middle.  a and b are cubics in the material left on the board.  a is the
+// score that wins half the time and b how quickly the chances change
+// around it.  fit-wdl refits them from game data
+
+// fitted with
+//     mantissa datagen --games 3000 --eval-mode hce --seed 1 --out wdl.bin
+//     mantissa fit-wdl wdl.bin
+// which gives a of 799, 1127, 1365 and 3460 at material 78, 58, 38 and 17.
+// in these games the same score wins less often as material comes off, so
+// a score means less in an endgame, not more.  worth refitting on games
+// played with the net
+const WDL_A: [f64; 4] = [-7212.7, 21261.3, -20847.9, 7926.0];
+const WDL_B: [f64; 4] = [-2863.1, 7784.6, -6415.6, 3244.7];
+
+// material the model is indexed by: pawns 1, minors 3, rooks 5, queens 9
+const WDL_MIN_MATERIAL: i32 = 17;
+const WDL_MAX_MATERIAL: i32 = 78;
+const WDL_MATERIAL_SCALE: f64 = 58.0;
+
+fn material_count(pieces: &[[u64; 6]; 2]) -> i32 {
+    let weights = [1, 3, 3, 5, 9];
+    let mut material = 0;
+    for side in pieces.iter() {
+        for (bb, w) in side.iter().zip(weights.iter()) {
+            material += bb.count_ones() as i32 * w;
+        }
+    }
+    return material.clamp(WDL_MIN_MATERIAL, WDL_MAX_MATERIAL);
+}
+
+pub fn wdl_material(pos: &Bitboard) -> i32 {
+    return material_count(&pos.piece_boards());
+}
+
+fn polynomial(coefs: &[f64; 4], x: f64) -> f64 {
+    return ((coefs[0] * x + coefs[1]) * x + coefs[2]) * x + coefs[3];
+}
+
+fn wdl_params(coefs: &[f64; 8], material: i32) -> (f64, f64) {
+    let x = material as f64 / WDL_MATERIAL_SCALE;
+    let a = polynomial(&[coefs[0], coefs[1], coefs[2], coefs[3]], x);
+    let b = polynomial(&[coefs[4], coefs[5], coefs[6], coefs[7]], x);
+    return (a, b.max(1.0));
+}
+
+fn model_coefs() -> [f64; 8] {
+    let mut coefs = [0.0; 8];
+    coefs[..4].copy_from_slice(&WDL_A);
+    coefs[4..].copy_from_slice(&WDL_B);
+    return coefs;
+}
+
+fn sigmoid(x: f64) -> f64 {
+    return 1.0 / (1.0 + (-x).exp());
+}
+
+// per mille chances of a win, draw and loss for the side the score is for
+pub fn wdl(score: i32, material: i32) -> (i32, i32, i32) {
+    if score.abs() >= MIN_MATE_SCORE {
+        return if score > 0 { (1000, 0, 0) } else { (0, 0, 1000) };
+    }
+    let (a, b) = wdl_params(&model_coefs(), material);
+    let win = (1000.0 * sigmoid((score as f64 - a) / b)).round() as i32;
+    let loss = (1000.0 * sigmoid((-score as f64 - a) / b)).round() as i32;
+    return (win, 1000 - win - loss, loss);
+}
+
+// a score in centipawns scaled so that 100 wins half the time
+pub fn normalized_cp(score: i32, material: i32) -> i32 {
+    let (a, _) = wdl_params(&model_coefs(), material);
+    return (100.0 * score as f64 / a).round() as i32;
+}
+
+// positions are grouped by material and score so each step of the fit only
+// goes over the distinct cells
+const SCORE_STEP: i32 = 50;
+const MAX_FIT_SCORE: i32 = 10000;
+
+struct Cell {
+    material: i32,
+    score: f64,
+    counts: [f64; 3]    // white wins, draws, black wins
+}
+
+// the negative log likelihood of the cells and its gradient
+fn fit_error(cells: &[Cell], coefs: &[f64; 8], total: f64) -> (f64, [f64; 8]) {
+    let mut error = 0.0;
+    let mut grad = [0.0; 8];
+    for c in cells {
+        let (a, b) = wdl_params(coefs, c.material);
+        let u = (c.score - a) / b;
+        let v = (-c.score - a) / b;
+        let win = sigmoid(u).max(1e-12);
+        let loss = sigmoid(v).max(1e-12);
+        let draw = (1.0 - win - loss).max(1e-12);
+        error -= c.counts[0] * win.ln() + c.counts[1] * draw.ln() + c.counts[2] * loss.ln();
+
+        let (dwin_da, dwin_db) = (-win * (1.0 - win) / b, -win * (1.0 - win) * u / b);
+        let (dloss_da, dloss_db) = (-loss * (1.0 - loss) / b, -loss * (1.0 - loss) * v / b);
+        let de_da = -(c.counts[0] * dwin_da / win - c.counts[1] * (dwin_da + dloss_da) / draw + c.counts[2] * dloss_da / loss);
+        let de_db = -(c.counts[0] * dwin_db / win - c.counts[1] * (dwin_db + dloss_db) / draw + c.counts[2] * dloss_db / loss);
+        let x = c.material as f64 / WDL_MATERIAL_SCALE;
+        let powers = [x * x * x, x * x, x, 1.0];
+        for (k, p) in powers.iter().enumerate() {
+            grad[k] += de_da * p;
+            grad[4 + k] += de_db * p;
+        }
+    }
+    for g in grad.iter_mut() {
+        *g /= total;
+    }
+    return (error / total, grad);
+}
+
+pub fn fit_wdl(fname: &str, iterations: usize) -> Result<(), String> {
+    let start = get_time_millis();
+    let mut cells: HashMap<(i32, i32), [f64; 3]> = HashMap::new();
+    let mut total = 0.0;
+    let mut skipped = 0;
+    for p in DataReader::open(fname)? {
+        let p = match p {
+            Ok(p) => p,
+            Err(e) if is_packed_file(fname) => { return Err(e); },
+            Err(_) => { skipped += 1; continue; }
+        };
+        // scores are stored in centipawns, the model works in internal units
+        let score = p.score as i32 * 10;
+        if score.abs() > MAX_FIT_SCORE {
+            continue;
+        }
+        let bin = (score + SCORE_STEP / 2).div_euclid(SCORE_STEP);
+        let outcome = match p.result { 2 => 0, 1 => 1, _ => 2 };
+        cells.entry((material_count(&p.piece_boards()), bin)).or_insert([0.0; 3])[outcome] += 1.0;
+        total += 1.0;
+    }
+    if skipped > 0 {
+        eprintln!("ERR: skipped {} lines that weren't positions with a result", skipped);
+    }
+    if total == 0.0 {
+        return Err(format!("no positions to fit in {}", fname));
+    }
+    let cells: Vec<Cell> = cells.iter().map(|(&(material, bin), &counts)| Cell {
+        material: material,
+        score: (bin * SCORE_STEP) as f64,
+        counts: counts
+    }).collect();
+
+    // Adam, starting from a flat model rather than the current one, so the
+    // same data always gives the same coefficients
+    let mut coefs = [0.0, 0.0, 0.0, 1000.0, 0.0, 0.0, 0.0, 500.0];
+    let lr = 20.0;
+    let (beta1, beta2, epsilon) = (0.9, 0.999, 1e-8);
+    let mut m = [0.0; 8];
+    let mut v = [0.0; 8];
+    let initial = fit_error(&cells, &coefs, total).0;
+    for step in 1..=iterations {
+        let (_, grad) = fit_error(&cells, &coefs, total);
+        let correction1 = 1.0 - f64::powi(beta1, step as i32);
+        let correction2 = 1.0 - f64::powi(beta2, step as i32);
+        for (i, g) in grad.iter().enumerate() {
+            m[i] = beta1 * m[i] + (1.0 - beta1) * g;
+            v[i] = beta2 * v[i] + (1.0 - beta2) * g * g;
+            coefs[i] -= lr * (m[i] / correction1) / ((v[i] / correction2).sqrt() + epsilon);
+        }
+    }
+    let error = fit_error(&cells, &coefs, total).0;
+
+    println!("fit {} positions in {} cells, log loss {:.5} -> {:.5} ({}ms)", total, cells.len(), initial, error, get_time_millis() - start);
+    for material in [WDL_MAX_MATERIAL, 58, 38, WDL_MIN_MATERIAL] {
+        let (a, b) = wdl_params(&coefs, material);
+        println!("material {:>2}: a {:.1}, b {:.1}", material, a, b);
+    }
+    println!("const WDL_A: [f64; 4] = [{:.1}, {:.1}, {:.1}, {:.1}];", coefs[0], coefs[1], coefs[2], coefs[3]);
+    println!("const WDL_B: [f64; 4] = [{:.1}, {:.1}, {:.1}, {:.1}];", coefs[4], coefs[5], coefs[6], coefs[7]);
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wdl_shape() {
+        for material in WDL_MIN_MATERIAL..=WDL_MAX_MATERIAL {
+            let mut last = wdl(-4000, material);
+            for score in (-4000..=4000).step_by(5) {
+                let (w, d, l) = wdl(score, material);
+                assert_eq!(w + d + l, 1000, "score {} material {}", score, material);
+                assert!(w >= 0 && d >= 0 && l >= 0, "score {} material {}", score, material);
+                // the same score from the other side swaps the chances
+                assert_eq!(wdl(-score, material), (l, d, w));
+                // a better score never wins less or loses more
+                assert!(w >= last.0 && l <= last.2, "score {} material {}", score, material);
+                last = (w, d, l);
+            }
+        }
+        assert_eq!(wdl(MATE_SCORE - 10, 40), (1000, 0, 0));
+        assert_eq!(wdl(-MATE_SCORE + 10, 40), (0, 0, 1000));
+    }
+
+    #[test]
+    fn normalized_scale() {
+        for material in [WDL_MIN_MATERIAL, 38, 58, WDL_MAX_MATERIAL] {
+            let (a, _) = wdl_params(&model_coefs(), material);
+            assert_eq!(normalized_cp(a.round() as i32, material), 100);
+            assert_eq!(normalized_cp(0, material), 0);
+        }
+    }
+}